@@ -0,0 +1,371 @@
+// Admin Endpoints
+// Operator-only routes, gated by the X-Gateway-Secret header
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+
+use crate::auth::{
+    self, invalidate_user, reload_access_rules, AppState, AuthzCacheKey, GATEWAY_SECRET_HEADER,
+};
+use crate::openfga::{Consistency, OpenFgaError, TupleKey};
+use crate::webhooks::is_duplicate_tuple_error;
+
+/// Header carrying the admin secret
+pub const ADMIN_SECRET_HEADER: &str = GATEWAY_SECRET_HEADER;
+
+#[derive(Debug, Serialize)]
+pub struct AdminResponse {
+    pub status: String,
+    pub message: String,
+}
+
+/// Reject requests without the configured admin secret
+///
+/// With no `ADMIN_SECRET` configured every admin call is rejected.
+pub async fn require_admin(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(expected) = state.admin_secret.as_deref() else {
+        tracing::warn!("Admin request rejected: no admin secret configured");
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    let provided = req
+        .headers()
+        .get(ADMIN_SECRET_HEADER)
+        .map(|h| h.as_bytes())
+        .unwrap_or_default();
+
+    if !constant_time_eq(provided, expected.as_bytes()) {
+        tracing::warn!("Admin request rejected: invalid secret");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// POST /admin/reload-rules - re-read access rules without a restart
+pub async fn reload_rules(
+    State(state): State<AppState>,
+) -> Result<Json<AdminResponse>, (StatusCode, Json<AdminResponse>)> {
+    match reload_access_rules(&state).await {
+        Ok(()) => Ok(Json(AdminResponse {
+            status: "success".to_string(),
+            message: format!("Reloaded access rules from {}", state.access_rules_path),
+        })),
+        Err(e) => {
+            let message = format!("Keeping current access rules, reload failed: {}", e);
+            tracing::error!("{}", message);
+            Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(AdminResponse {
+                    status: "error".to_string(),
+                    message,
+                }),
+            ))
+        }
+    }
+}
+
+/// Which cached decisions to drop; omitted fields match everything
+#[derive(Debug, Default, Deserialize)]
+pub struct InvalidateRequest {
+    pub user: Option<String>,
+    pub feature: Option<String>,
+}
+
+/// POST /admin/cache/invalidate - drop cached decisions so an out-of-band
+/// permission change takes effect immediately
+pub async fn invalidate_cache(
+    State(state): State<AppState>,
+    Json(request): Json<InvalidateRequest>,
+) -> Result<Json<AdminResponse>, StatusCode> {
+    let message = match (request.user, request.feature) {
+        (Some(user), None) => {
+            invalidate_user(&state, &user);
+            format!("Invalidated cached decisions for user {}", user)
+        }
+        (user, Some(feature)) => {
+            invalidate_feature(&state, user.clone(), feature.clone())?;
+            match user {
+                Some(user) => format!(
+                    "Invalidated cached decisions for user {} on feature {}",
+                    user, feature
+                ),
+                None => format!("Invalidated cached decisions for feature {}", feature),
+            }
+        }
+        (None, None) => {
+            state.cache.invalidate_all();
+            if let Some(stale_grants) = &state.stale_grants {
+                stale_grants.invalidate_all();
+            }
+            state.list_objects_cache.invalidate_all();
+            "Invalidated all cached decisions".to_string()
+        }
+    };
+
+    tracing::info!("{}", message);
+    Ok(Json(AdminResponse {
+        status: "success".to_string(),
+        message,
+    }))
+}
+
+/// Drop cached decisions on `feature`, for one user or (None) everyone
+fn invalidate_feature(
+    state: &AppState,
+    user: Option<String>,
+    feature: String,
+) -> Result<(), StatusCode> {
+    let matches = move |key: &AuthzCacheKey| {
+        key.feature == feature && user.as_ref().is_none_or(|user| key.user == *user)
+    };
+    let matches_stale = matches.clone();
+    state
+        .cache
+        .invalidate_entries_if(move |key, _| matches(key))
+        .map_err(|e| {
+            tracing::error!("Cache invalidation failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Some(stale_grants) = &state.stale_grants {
+        stale_grants
+            .invalidate_entries_if(move |key, _| matches_stale(key))
+            .map_err(|e| {
+                tracing::error!("Stale grant invalidation failed: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+    // Listings aren't keyed by feature, so drop them all
+    state.list_objects_cache.invalidate_all();
+    Ok(())
+}
+
+/// Permission to grant: `user:{user}` gets `relation` on `feature:{feature}`
+#[derive(Debug, Deserialize)]
+pub struct GrantRequest {
+    pub user: String,
+    pub feature: String,
+    pub relation: String,
+}
+
+/// POST /admin/grants - write a permission tuple through the gateway, so
+/// its cached decisions are dropped in the same step. Responds 201 with the
+/// tuple, or 200 if it already existed.
+pub async fn create_grant(
+    State(state): State<AppState>,
+    Json(request): Json<GrantRequest>,
+) -> Result<(StatusCode, Json<TupleKey>), (StatusCode, Json<AdminResponse>)> {
+    let error = |status: StatusCode, message: String| {
+        (
+            status,
+            Json(AdminResponse {
+                status: "error".to_string(),
+                message,
+            }),
+        )
+    };
+
+    // Anything that would change the tuple's shape is rejected outright
+    let invalid = |s: &str| s.is_empty() || s.contains([':', '#', ' ']);
+    if invalid(&request.user) || invalid(&request.feature) || invalid(&request.relation) {
+        return Err(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "user, feature and relation must be non-empty, without ':', '#' or spaces".to_string(),
+        ));
+    }
+
+    let tuple = TupleKey::new(
+        format!("user:{}", request.user),
+        request.relation.as_str(),
+        format!("feature:{}", request.feature),
+    );
+    let status = match state
+        .fga_client
+        .write(&state.http_client, std::slice::from_ref(&tuple), &[])
+        .await
+    {
+        Ok(()) => StatusCode::CREATED,
+        Err(OpenFgaError::Status { status, body })
+            if status.is_client_error() && is_duplicate_tuple_error(&body) =>
+        {
+            StatusCode::OK
+        }
+        Err(e) => {
+            tracing::error!("Failed to write grant {:?}: {}", tuple, e);
+            return Err(error(StatusCode::BAD_GATEWAY, e.to_string()));
+        }
+    };
+
+    // The previous (denied) decision may be cached
+    invalidate_feature(&state, Some(request.user), request.feature).map_err(|status| {
+        error(
+            status,
+            "Grant written, but cached decisions could not be dropped".to_string(),
+        )
+    })?;
+    tracing::info!(
+        "Granted {} {} on {}",
+        tuple.user,
+        tuple.relation,
+        tuple.object
+    );
+    Ok((status, Json(tuple)))
+}
+
+/// Token to revoke: its `jti`, and its `exp` so the denylist entry lasts
+/// exactly as long as the token would
+#[derive(Debug, Deserialize)]
+pub struct RevokeTokenRequest {
+    pub jti: String,
+    pub exp: i64,
+}
+
+/// POST /admin/tokens/revoke - reject a still-valid token from now on
+/// (logout, compromise). Needs `TOKEN_DENYLIST` enabled to take effect.
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    Json(request): Json<RevokeTokenRequest>,
+) -> Result<Json<AdminResponse>, StatusCode> {
+    if request.jti.is_empty() {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let remaining = request.exp - now;
+
+    let message = if remaining <= 0 {
+        // Already expired, so already rejected
+        format!("Token {} has expired, nothing to revoke", request.jti)
+    } else {
+        let ttl = std::time::Duration::from_secs(remaining as u64);
+        auth::revoke_token(&state.redis_client, &request.jti, ttl)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to revoke token {}: {}", request.jti, e);
+                StatusCode::SERVICE_UNAVAILABLE
+            })?;
+        format!("Revoked token {} for {}s", request.jti, remaining)
+    };
+    if !state.token_denylist {
+        tracing::warn!("Token revoked but TOKEN_DENYLIST is disabled, so it isn't enforced");
+    }
+
+    tracing::info!("{}", message);
+    Ok(Json(AdminResponse {
+        status: "success".to_string(),
+        message,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+}
+
+/// POST /admin/maintenance - switch maintenance mode on or off. While on,
+/// proxied routes answer 503; health, webhook and admin routes still work.
+pub async fn set_maintenance(
+    State(state): State<AppState>,
+    Json(request): Json<MaintenanceRequest>,
+) -> Json<AdminResponse> {
+    state
+        .maintenance_mode
+        .store(request.enabled, Ordering::Relaxed);
+    let message = if request.enabled {
+        "Maintenance mode enabled, proxied routes return 503"
+    } else {
+        "Maintenance mode disabled"
+    };
+    tracing::warn!("{}", message);
+    Json(AdminResponse {
+        status: "success".to_string(),
+        message: message.to_string(),
+    })
+}
+
+/// The check to explain; `action` is mapped to a relation as for rules
+#[derive(Debug, Deserialize)]
+pub struct DebugAuthzRequest {
+    pub user: String,
+    pub feature: String,
+    pub action: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DebugAuthzResponse {
+    pub tuple: TupleKey,
+    pub allowed: Option<bool>, // None when OpenFGA answered with an error
+    pub openfga_request: serde_json::Value,
+    pub openfga_status: u16,
+    pub openfga_response: serde_json::Value, // Includes `resolution` when OpenFGA sends one
+}
+
+/// POST /debug/authz - run the middleware's OpenFGA check for a user and
+/// feature, bypassing the decision cache, and show the raw exchange
+pub async fn debug_authz(
+    State(state): State<AppState>,
+    Json(request): Json<DebugAuthzRequest>,
+) -> Result<Json<DebugAuthzResponse>, (StatusCode, Json<AdminResponse>)> {
+    let error = |status: StatusCode, message: String| {
+        (
+            status,
+            Json(AdminResponse {
+                status: "error".to_string(),
+                message,
+            }),
+        )
+    };
+
+    let relation = state
+        .fga_client
+        .relation_for(request.action.as_deref())
+        .map_err(|e| error(StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    let tuple = TupleKey::new(
+        format!("user:{}", request.user),
+        relation,
+        format!("feature:{}", request.feature),
+    );
+
+    let explanation = state
+        .fga_client
+        // Reflect grants written moments ago, not OpenFGA's cached view
+        .explain_check(
+            &state.http_client,
+            &tuple,
+            None,
+            Some(Consistency::HigherConsistency),
+        )
+        .await
+        .map_err(|e| error(StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    let allowed = explanation
+        .status
+        .is_success()
+        .then(|| explanation.response["allowed"].as_bool())
+        .flatten();
+    Ok(Json(DebugAuthzResponse {
+        tuple,
+        allowed,
+        openfga_request: explanation.request,
+        openfga_status: explanation.status.as_u16(),
+        openfga_response: explanation.response,
+    }))
+}
+
+/// Compare secrets without leaking the mismatch position through timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,220 @@
+// Audit Events
+// Durable record of authorization decisions (every denial, and a sample of
+// the rest), handed to a pluggable sink off the request path
+
+use futures_util::future::BoxFuture;
+use redis::aio::ConnectionManager;
+use redis::Client as RedisClient;
+use reqwest::Client as HttpClient;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, OnceCell};
+
+use crate::auth::AppState;
+use crate::telemetry;
+
+/// Events waiting for the sink by default; more are dropped
+pub const DEFAULT_AUDIT_QUEUE_CAPACITY: usize = 1024;
+
+/// Where audit events are recorded. Implement this for a new destination;
+/// `AuditSink` builds the built-in ones.
+pub trait DecisionSink: Send + Sync {
+    /// Record one event. Runs off the request path; an error is logged and
+    /// the event dropped.
+    fn record(&self, event: AuditEvent) -> BoxFuture<'_, Result<(), String>>;
+}
+
+/// Built-in sink, written as `tracing`, `redis:<stream>` or an `http(s)://`
+/// URL
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuditSink {
+    /// An `audit` log line per event
+    Tracing,
+    /// `XADD <stream> * event <json>`
+    RedisStream(String),
+    /// POST of the event as JSON
+    Http(String),
+}
+
+impl AuditSink {
+    /// The sink, sharing the gateway's Redis and HTTP clients
+    pub fn build(
+        &self,
+        redis_client: &RedisClient,
+        http_client: &HttpClient,
+    ) -> Arc<dyn DecisionSink> {
+        match self {
+            Self::Tracing => Arc::new(TracingSink),
+            Self::RedisStream(stream) => {
+                Arc::new(RedisStreamSink::new(redis_client.clone(), stream.clone()))
+            }
+            Self::Http(url) => Arc::new(HttpSink {
+                client: http_client.clone(),
+                url: url.clone(),
+            }),
+        }
+    }
+}
+
+impl std::str::FromStr for AuditSink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "tracing" {
+            return Ok(Self::Tracing);
+        }
+        if let Some(stream) = s.strip_prefix("redis:") {
+            return match stream.trim() {
+                "" => Err("Audit sink redis: needs a stream name".to_string()),
+                stream => Ok(Self::RedisStream(stream.to_string())),
+            };
+        }
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(Self::Http(s.to_string()));
+        }
+        Err(format!("Invalid audit sink: {}", s))
+    }
+}
+
+/// One request's authorization outcome
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEvent {
+    pub timestamp: i64, // Unix seconds
+    pub request_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub client_ip: Option<IpAddr>,
+    pub status: u16,
+    pub result: &'static str, // forbidden, unauthorized, rate_limited, allowed, ...
+    pub allowed: Option<bool>, // The authz decision, when one was made
+    pub user: Option<String>,
+    pub feature: Option<String>,
+    pub action: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default()
+    }
+}
+
+/// Bounded queue in front of a sink, drained by a single worker. A burst of
+/// denials (credential stuffing, rate limiting) costs at most `capacity`
+/// buffered events rather than a task each.
+#[derive(Clone)]
+pub struct AuditQueue {
+    tx: mpsc::Sender<AuditEvent>,
+}
+
+impl AuditQueue {
+    /// Start the worker recording queued events to `sink`
+    pub fn spawn(sink: Arc<dyn DecisionSink>, capacity: usize) -> Self {
+        let (tx, mut rx) = mpsc::channel::<AuditEvent>(capacity.max(1));
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                if let Err(e) = sink.record(event).await {
+                    tracing::error!("Failed to write audit event: {}", e);
+                }
+            }
+        });
+        Self { tx }
+    }
+}
+
+/// Queue `event` for the configured sink, so auditing never adds latency to
+/// the response. When the queue is full the event is dropped and counted.
+pub fn emit(state: &AppState, event: AuditEvent) {
+    let Some(queue) = &state.audit_queue else {
+        return;
+    };
+    if queue.tx.try_send(event).is_err() {
+        metrics::counter!(telemetry::AUDIT_EVENTS_DROPPED_TOTAL).increment(1);
+    }
+}
+
+/// Logs each event under the `audit` target
+pub struct TracingSink;
+
+impl DecisionSink for TracingSink {
+    fn record(&self, event: AuditEvent) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            let json = serde_json::to_string(&event).map_err(|e| e.to_string())?;
+            tracing::info!(target: "audit", event = %json);
+            Ok(())
+        })
+    }
+}
+
+/// Appends each event to a Redis stream over one shared connection, opened
+/// on the first event and re-established by the manager when it drops
+pub struct RedisStreamSink {
+    client: RedisClient,
+    stream: String,
+    conn: OnceCell<ConnectionManager>,
+}
+
+impl RedisStreamSink {
+    pub fn new(client: RedisClient, stream: String) -> Self {
+        Self {
+            client,
+            stream,
+            conn: OnceCell::new(),
+        }
+    }
+}
+
+impl DecisionSink for RedisStreamSink {
+    fn record(&self, event: AuditEvent) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            let error = |e: redis::RedisError| format!("Redis stream {}: {}", self.stream, e);
+            let json = serde_json::to_string(&event).map_err(|e| e.to_string())?;
+            // A failed connect leaves the cell empty, so the next event retries
+            let mut conn = self
+                .conn
+                .get_or_try_init(|| self.client.get_connection_manager())
+                .await
+                .map_err(error)?
+                .clone();
+            redis::cmd("XADD")
+                .arg(&self.stream)
+                .arg("*")
+                .arg("event")
+                .arg(json)
+                .query_async::<String>(&mut conn)
+                .await
+                .map(|_| ())
+                .map_err(error)
+        })
+    }
+}
+
+/// POSTs each event as JSON
+pub struct HttpSink {
+    pub client: HttpClient,
+    pub url: String,
+}
+
+impl DecisionSink for HttpSink {
+    fn record(&self, event: AuditEvent) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .post(&self.url)
+                .json(&event)
+                .send()
+                .await
+                .map_err(|e| format!("{}: {}", self.url, e))?;
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(format!("{}: status {}", self.url, response.status()))
+            }
+        })
+    }
+}
@@ -1,47 +1,767 @@
+use arc_swap::ArcSwap;
 use axum::{
-    body::Body,
-    extract::{Request, State},
-    http::{header, Method, StatusCode},
+    body::{Body, HttpBody},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
     middleware::{self, Next},
-    response::Response,
+    response::{IntoResponse, Response},
     routing::any,
 };
+use http_body_util::{BodyExt, LengthLimitError, Limited};
 use jsonwebtoken::{Algorithm, DecodingKey, Validation};
 use matchit::Router;
 use moka::future::Cache;
-use redis::AsyncCommands;
+use moka::Expiry;
 use reqwest::Client as HttpClient;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::trace::TraceLayer;
+use tracing::Instrument;
 
+use crate::audit::{self, AuditEvent, AuditQueue};
+use crate::concurrency::ConcurrencyLimiter;
+use crate::introspection::{self, IntrospectionCache, IntrospectionConfig};
+pub use crate::openfga::{Consistency, OpenFgaClient, RetryPolicy};
+use crate::openfga::{OpenFgaError, TupleKey};
+use crate::telemetry;
+use crate::webhooks::UserRegistration;
+
+/// Correlation ID header shared by client, gateway and upstream
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Shared secret proving a request came through the gateway: sent by
+/// operators to /admin/* and by the gateway to upstream services
+pub const GATEWAY_SECRET_HEADER: &str = "x-gateway-secret";
+
+/// Longer incoming IDs are replaced rather than logged and forwarded
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Identity headers set by the gateway for upstream services. Any
+/// client-supplied values are stripped so they can't be spoofed.
+pub const USER_ID_HEADER: &str = "x-user-id";
+pub const USER_SCOPES_HEADER: &str = "x-user-scopes";
+pub const USER_ROLES_HEADER: &str = "x-user-roles";
+pub const USER_ORG_HEADER: &str = "x-user-org";
+const IDENTITY_HEADERS: [&str; 4] = [
+    USER_ID_HEADER,
+    USER_SCOPES_HEADER,
+    USER_ROLES_HEADER,
+    USER_ORG_HEADER,
+];
+
+/// Response headers naming the matched rule and the outcome, only sent
+/// with DEBUG_HEADERS enabled
+pub const DEBUG_FEATURE_HEADER: &str = "x-gateway-feature";
+pub const DEBUG_ACTION_HEADER: &str = "x-gateway-action";
+pub const DEBUG_DECISION_HEADER: &str = "x-gateway-decision";
+
+/// Redis key prefix for revoked token IDs: `revoked_tokens:{jti}`, each
+/// expiring when the token itself would
+pub const REVOKED_TOKENS_PREFIX: &str = "revoked_tokens";
+
+/// Requests per window applied when a rule doesn't set its own limit
+pub const DEFAULT_RATE_LIMIT: u32 = 100;
+pub const DEFAULT_RATE_WINDOW_SECS: u64 = 60;
+
+/// Request body cap when `MAX_BODY_BYTES` isn't set
+pub const DEFAULT_MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Longest path plus query string when `MAX_URL_LEN` isn't set
+pub const DEFAULT_MAX_URL_LEN: usize = 8 * 1024;
+
+/// Upstream timeout when `UPSTREAM_TIMEOUT_SECS` isn't set
+pub const DEFAULT_UPSTREAM_TIMEOUT_SECS: u64 = 30;
+
+/// Tries per idempotent upstream request when `UPSTREAM_RETRY_ATTEMPTS`
+/// isn't set (1 = no retries)
+pub const DEFAULT_UPSTREAM_RETRY_ATTEMPTS: u32 = 1;
+
+/// Most cached authz decisions when `AUTHZ_CACHE_MAX_CAPACITY` isn't set;
+/// the least recently/frequently used are evicted beyond it
+pub const DEFAULT_AUTHZ_CACHE_MAX_CAPACITY: u64 = 100_000;
+
+/// Most cached JWKS keys when `JWKS_CACHE_MAX_CAPACITY` isn't set
+pub const DEFAULT_JWKS_CACHE_MAX_CAPACITY: u64 = 1_000;
+
+/// How long a fetched JWKS key is trusted before it's fetched again
+const JWKS_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `Retry-After` sent during maintenance when `MAINTENANCE_RETRY_AFTER_SECS`
+/// isn't set
+pub const DEFAULT_MAINTENANCE_RETRY_AFTER_SECS: u64 = 300;
+
+/// Connection pool settings for the outbound HTTP clients
 #[derive(Clone, Debug)]
+pub struct HttpClientConfig {
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Duration,
+    pub connect_timeout: Duration,
+    pub tcp_keepalive: Duration,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout: Duration::from_secs(90),
+            connect_timeout: Duration::from_secs(5),
+            tcp_keepalive: Duration::from_secs(60),
+        }
+    }
+}
+
+impl HttpClientConfig {
+    pub fn build(&self) -> reqwest::Result<HttpClient> {
+        self.builder().build()
+    }
+
+    /// Client for proxied traffic. It never decompresses, so a body reaches
+    /// the caller exactly as encoded upstream, consistent with the forwarded
+    /// `Content-Encoding`/`Content-Length` (even if a dependency turns on
+    /// reqwest's decompression features).
+    pub fn build_upstream(&self) -> reqwest::Result<HttpClient> {
+        self.upstream_builder().build()
+    }
+
+    /// Like `build_upstream`, but speaking HTTP/2 from the first byte
+    /// (prior knowledge, no upgrade), as gRPC upstreams over h2c expect
+    pub fn build_upstream_h2(&self) -> reqwest::Result<HttpClient> {
+        self.upstream_builder().http2_prior_knowledge().build()
+    }
+
+    fn upstream_builder(&self) -> reqwest::ClientBuilder {
+        self.builder().no_gzip().no_brotli().no_deflate().no_zstd()
+    }
+
+    fn builder(&self) -> reqwest::ClientBuilder {
+        HttpClient::builder()
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .connect_timeout(self.connect_timeout)
+            .tcp_keepalive(self.tcp_keepalive)
+    }
+}
+
+/// OpenFGA relation checked without an action: none on the rule and none
+/// mapped for the method (see `default_method_actions`)
+pub const DEFAULT_RELATION: &str = "viewer";
+
+/// Action for rules without one when `METHOD_DEFAULT_ACTIONS` isn't set.
+/// Methods not listed fall back to `DEFAULT_RELATION`.
+pub fn default_method_actions() -> HashMap<Method, String> {
+    [
+        (Method::GET, "view"),
+        (Method::HEAD, "view"),
+        (Method::POST, "edit"),
+        (Method::PUT, "edit"),
+        (Method::PATCH, "edit"),
+        (Method::DELETE, "delete"),
+    ]
+    .into_iter()
+    .map(|(method, action)| (method, action.to_string()))
+    .collect()
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct RouteConfig {
     pub feature: String,
     pub action: Option<String>, // NEW: view, edit, delete
     pub target: Option<String>,
+    pub rate_limit: Option<u32>,       // Requests allowed per window
+    pub rate_window_secs: Option<u64>, // Window length in seconds
+    pub object: Option<String>,        // OpenFGA object template, e.g. document:{id}
+    pub rewrite: Option<PathRewrite>,  // Upstream path when it differs from ours
+    pub cache_ttl_secs: Option<u64>,   // Grant cache TTL, overriding AUTHZ_CACHE_TTL_SECS
+    pub action_from_body: Option<BodyAction>, // Action read from the JSON body (RPC-style routes)
+}
+
+/// Where an RPC-style route (`POST /graphql`, `/rpc`) finds its action,
+/// written as `{ "pointer": "/operation", "actions": { "deleteUser": "delete" } }`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct BodyAction {
+    /// JSON pointer to a string in the request body
+    pub pointer: String,
+    /// Body value -> rule action; any other value is denied
+    pub actions: HashMap<String, String>,
+}
+
+impl BodyAction {
+    /// Action for a JSON request body, or why it has none
+    pub fn resolve(&self, body: &[u8]) -> Result<&str, BodyActionError> {
+        let body: serde_json::Value =
+            serde_json::from_slice(body).map_err(|_| BodyActionError::Malformed)?;
+        let value = body
+            .pointer(&self.pointer)
+            .and_then(|v| v.as_str())
+            .ok_or(BodyActionError::Malformed)?;
+        self.actions
+            .get(value)
+            .map(String::as_str)
+            .ok_or_else(|| BodyActionError::Unmapped(value.to_string()))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BodyActionError {
+    /// Not JSON, or no string at the pointer
+    Malformed,
+    /// A value with no action mapped
+    Unmapped(String),
+}
+
+/// How a route's path is rewritten before proxying, written in the rules as
+/// `{ "strip_prefix": "/api/v1" }` or `{ "to": "/users/{id}" }`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PathRewrite {
+    /// Drop a leading prefix, e.g. /api/v1/users -> /users
+    StripPrefix(String),
+    /// Build the path from a template filled with the matched path params
+    To(String),
+}
+
+impl RouteConfig {
+    /// OpenFGA object to check, filling `{param}` placeholders in the
+    /// `object` template from the matched path parameters. Without a
+    /// template the object is the feature itself.
+    pub fn resolve_object(&self, params: &matchit::Params) -> Result<String, String> {
+        let Some(template) = &self.object else {
+            return Ok(format!("feature:{}", self.feature));
+        };
+
+        // Don't let a crafted path alter the shape of the OpenFGA object
+        fill_template(template, params, |value| {
+            !value.is_empty()
+                && !value.contains(|c: char| c == '#' || c == ':' || c.is_whitespace())
+        })
+    }
+
+    /// Action checked for `method` when the request body doesn't name one:
+    /// the rule's own, else the method's default from `method_actions`
+    pub fn action_for<'a>(
+        &'a self,
+        method: &Method,
+        method_actions: &'a HashMap<Method, String>,
+    ) -> Option<&'a str> {
+        self.action
+            .as_deref()
+            .or_else(|| method_actions.get(method).map(String::as_str))
+    }
+
+    /// Path to request upstream for `path`, after applying any `rewrite`
+    pub fn upstream_path(&self, path: &str, params: &matchit::Params) -> Result<String, String> {
+        match &self.rewrite {
+            None => Ok(path.to_string()),
+            Some(PathRewrite::StripPrefix(prefix)) => match path.strip_prefix(prefix.as_str()) {
+                Some(rest) if rest.starts_with('/') => Ok(rest.to_string()),
+                Some(rest) => Ok(format!("/{}", rest)),
+                None => Ok(path.to_string()),
+            },
+            Some(PathRewrite::To(template)) => {
+                fill_template(template, params, |value| !value.is_empty())
+            }
+        }
+    }
+
+    /// Rate limit for this route as `(requests, window_secs)`, falling back
+    /// to the global defaults for anything the rule omits
+    pub fn effective_rate_limit(&self) -> (u32, u64) {
+        (
+            self.rate_limit.unwrap_or(DEFAULT_RATE_LIMIT),
+            self.rate_window_secs.unwrap_or(DEFAULT_RATE_WINDOW_SECS),
+        )
+    }
+}
+
+/// Fill `{param}` placeholders in `template` from the matched path
+/// parameters, rejecting any value `valid` refuses
+fn fill_template(
+    template: &str,
+    params: &matchit::Params,
+    valid: impl Fn(&str) -> bool,
+) -> Result<String, String> {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .map(|i| start + i)
+            .ok_or_else(|| format!("Unclosed '{{' in template {}", template))?;
+        let name = &rest[start + 1..end];
+        let value = params.get(name).ok_or_else(|| {
+            format!(
+                "Path parameter '{}' missing for template {}",
+                name, template
+            )
+        })?;
+
+        if !valid(value) {
+            return Err(format!(
+                "Invalid value for path parameter '{}': {:?}",
+                name, value
+            ));
+        }
+
+        filled.push_str(&rest[..start]);
+        filled.push_str(value);
+        rest = &rest[end + 1..];
+    }
+    filled.push_str(rest);
+
+    Ok(filled)
+}
+
+/// All access rules registered for a single path, keyed by HTTP method.
+///
+/// `matchit` can only key on the path, so rules that share a path but differ
+/// by method are grouped here. A rule with method `*` applies to any method
+/// that has no explicit rule of its own.
+#[derive(Clone, Debug, Default)]
+pub struct MethodRoutes {
+    pub methods: HashMap<Method, RouteConfig>,
+    pub any_method: Option<RouteConfig>,
+}
+
+impl MethodRoutes {
+    /// Resolve the rule for `method`, preferring an exact match over `*`
+    pub fn get(&self, method: &Method) -> Option<&RouteConfig> {
+        self.methods.get(method).or(self.any_method.as_ref())
+    }
+
+    /// Fill in methods this path has no rule for from an enclosing prefix
+    /// default (a catch-all like `/admin/*rest`). A `*` rule here already
+    /// covers every method, so nothing is inherited then.
+    fn inherit(&mut self, prefix: &MethodRoutes) {
+        if self.any_method.is_some() {
+            return;
+        }
+        for (method, route_config) in &prefix.methods {
+            self.methods
+                .entry(method.clone())
+                .or_insert_with(|| route_config.clone());
+        }
+        self.any_method = prefix.any_method.clone();
+    }
+}
+
+/// The part of a catch-all path before its `/*name` segment, e.g. `/admin`
+/// for `/admin/*rest`; None for any other path
+fn catch_all_prefix(path: &str) -> Option<&str> {
+    let (prefix, name) = path.rsplit_once("/*")?;
+    (!name.contains('/')).then_some(prefix)
 }
 
 #[derive(Clone)]
 pub struct AppState {
-    pub http_client: HttpClient,
+    pub http_client: HttpClient,        // OpenFGA, JWKS and Zitadel calls
+    pub upstream_client: HttpClient, // Proxied traffic, pooled apart so slow upstreams can't starve authz
+    pub upstream_h2_client: HttpClient, // Proxied traffic to `http2_services`
     pub fga_client: OpenFgaClient,
-    pub router: Arc<Router<RouteConfig>>,
-    pub cache: Cache<(String, String), bool>,
-    pub jwks_cache: Cache<String, DecodingKey>,
-    pub jwks_url: String,
+    pub router: Arc<ArcSwap<Router<MethodRoutes>>>, // Swapped wholesale on rules reload
+    pub access_rules_path: String,                  // Re-read by reload_access_rules
+    pub default_policy: DefaultPolicy,              // Paths with no matching rule
+    pub unmatched_status: UnmatchedStatus,          // Rejection for them under `Deny`
+    pub cache: Cache<AuthzCacheKey, AuthzDecision>,
+    pub authz_cache_ttl: Duration, // How long grants are cached
+    pub authz_negative_cache_ttl: Duration, // How long denials are cached (zero = never)
+    pub authz_cache_verify_rate: f64, // Fraction of cache hits re-checked in the background (0 = off)
+    pub stale_grants: Option<StaleGrantCache>, // Grants served while OpenFGA fails (None = disabled)
+    pub model_error_status: ModelErrorStatus,  // Rejection when OpenFGA reports a model error
+    pub jwks_cache: Cache<(String, String), DecodingKey>, // Keyed by (issuer, kid)
+    pub jwt_issuers: Arc<HashMap<String, JwtIssuer>>, // Accepted `iss` -> its JWKS
+    pub jwt_audience: Option<Vec<String>>,     // Accepted `aud` values (None = skip check)
+    pub jwt_algorithms: Vec<Algorithm>,        // Allow-list of token signing algorithms
+    pub jwt_leeway_secs: u64,                  // Clock-skew tolerance for exp/nbf
+    pub jwks_retry: RetryPolicy,               // Backoff for JWKS refreshes
+    pub introspection: Option<IntrospectionConfig>, // Validates opaque (non-JWT) tokens
+    pub introspection_cache: IntrospectionCache,
+    pub token_cache: Option<TokenCache>, // Verified claims by token hash (None = verify every request)
+    pub token_denylist: bool,            // Reject tokens whose `jti` was revoked (checked in Redis)
     pub zitadel_api_url: String,
     pub openfga_url: String,
     pub redis_client: redis::Client,
+    pub rate_limit_fail_mode: RateLimitFailMode,
+    pub trusted_proxies: Vec<IpAddr>, // Peers whose X-Forwarded-For / X-Real-IP is believed
+    pub trusted_proxy_hops: usize, // Proxies in front of the gateway, counted back through X-Forwarded-For
+    pub concurrency_limiter: Option<Arc<ConcurrencyLimiter>>, // Per-user in-flight cap (None = unlimited)
+    pub openfga_context_headers: Vec<String>, // Request headers passed as OpenFGA check context
+    pub method_actions: Arc<HashMap<Method, String>>, // Action checked for rules without one
+    pub tenant_source: Option<TenantSource>, // Namespaces OpenFGA objects per tenant (None = single-tenant)
     pub upstream_url: String,
+    pub services: Arc<HashMap<String, String>>, // Rule `target` name -> base URL
+    pub http2_services: Arc<HashSet<String>>, // Targets spoken to over HTTP/2 prior knowledge (gRPC)
+    pub max_body_bytes: usize,                // Largest request body proxied upstream
+    pub max_url_len: usize, // Longest path plus query accepted by the middleware (414 beyond)
+    pub upstream_timeout: Duration, // Whole proxied exchange, including the response body
+    pub upstream_retry_attempts: u32, // Tries for bodiless GET/HEAD/OPTIONS on connection failure
+    pub maintenance_mode: Arc<AtomicBool>, // Reject proxied routes with 503 (toggled at runtime by admins)
+    pub maintenance_retry_after_secs: u64,
+    pub webhook_signing_secret: Option<String>, // HMAC key for Zitadel webhooks (None = reject all)
+    pub webhook_timestamp_tolerance: Option<Duration>, // Max age of a signed webhook (None = no timestamp)
+    pub user_registration: UserRegistration, // Tuple written for each user Zitadel creates
+    pub admin_secret: Option<String>,        // X-Gateway-Secret for /admin/* (None = disabled)
+    pub upstream_secret: Option<HeaderValue>, // X-Gateway-Secret sent upstream (None = not sent)
+    pub strip_upstream_auth: bool, // Drop Authorization except for zitadel/openfga targets
+    pub forward_header_mode: ForwardHeaderMode,
+    pub forward_headers: Vec<header::HeaderName>, // Request headers passed upstream in allowlist mode
+    pub response_compression: bool, // Gzip/brotli responses for clients that accept it
+    pub access_log: bool,           // One structured `access_log` event per request
+    pub debug_headers: bool,        // Describe the matched rule in X-Gateway-* response headers
+    pub audit_queue: Option<AuditQueue>, // Where audit events go (None = logs only)
+    pub audit_allow_sample_rate: f64, // Share of requests not denied that are audited too (0 = denials only)
+    pub listable_objects: Vec<(String, String)>, // (type, relation) pairs for GET /me/features
+    pub list_objects_cache: Cache<ListObjectsKey, Arc<Vec<String>>>,
+}
+
+/// Last-known grants, kept well past the authz cache TTL
+pub type StaleGrantCache = Cache<AuthzCacheKey, ()>;
+
+/// Claims of already-verified tokens, keyed by SHA-256 of the raw token
+pub type TokenCache = Cache<String, Claims>;
+
+/// `(user_id, object_type, relation)` for a cached ListObjects result
+pub type ListObjectsKey = (String, String, String);
+
+impl AppState {
+    /// Base URL for a rule's `target`: the configured service map first, then
+    /// the built-in `zitadel` and `openfga` targets
+    pub fn service_url(&self, name: &str) -> Option<&str> {
+        match self.services.get(name) {
+            Some(url) => Some(url),
+            None if name == "zitadel" => Some(&self.zitadel_api_url),
+            None if name == "openfga" => Some(&self.openfga_url),
+            None => None,
+        }
+    }
+}
+
+/// What a cached authorization decision was computed for.
+///
+/// Covers the full checked tuple, so a cached `view` on one document never
+/// answers a `delete` or a different document under the same feature.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct AuthzCacheKey {
+    pub user: String,
+    pub feature: String,
+    pub object: String,
+    pub relation: String,
+    pub context: String, // Canonical JSON of the check context ("" when none)
+    pub tenant: String,  // "" when tenant scoping is off
+}
+
+impl AuthzCacheKey {
+    /// Key for a feature-level check with the default relation
+    pub fn new(user: &str, feature: &str, context: Option<&CheckContext>) -> Self {
+        Self {
+            user: user.to_string(),
+            feature: feature.to_string(),
+            object: format!("feature:{}", feature),
+            relation: DEFAULT_RELATION.to_string(),
+            context: context.map(CheckContext::fingerprint).unwrap_or_default(),
+            tenant: String::new(),
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant: Option<&str>) -> Self {
+        self.tenant = tenant.unwrap_or_default().to_string();
+        self
+    }
+
+    pub fn with_object(mut self, object: &str) -> Self {
+        self.object = object.to_string();
+        self
+    }
+
+    pub fn with_relation(mut self, relation: Option<&str>) -> Self {
+        self.relation = relation.unwrap_or(DEFAULT_RELATION).to_string();
+        self
+    }
+}
+
+/// Extra request-time data for ABAC conditions and contextual tuples
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CheckContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub contextual_tuples: Vec<serde_json::Value>,
+}
+
+impl CheckContext {
+    /// Stable string form for cache keys (serde_json sorts object keys)
+    pub fn fingerprint(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Build a check context from the configured request headers, e.g.
+    /// `X-Org-ID: acme` becomes `{"x_org_id": "acme"}`
+    pub fn from_headers(names: &[String], headers: &HeaderMap) -> Option<Self> {
+        let context: serde_json::Map<String, serde_json::Value> = names
+            .iter()
+            .filter_map(|name| {
+                let value = headers.get(name.as_str())?.to_str().ok()?;
+                Some((
+                    name.to_ascii_lowercase().replace('-', "_"),
+                    serde_json::Value::String(value.to_string()),
+                ))
+            })
+            .collect();
+
+        (!context.is_empty()).then(|| Self {
+            context: Some(serde_json::Value::Object(context)),
+            contextual_tuples: Vec::new(),
+        })
+    }
+}
+
+/// A cached authorization decision together with how long it may be served
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuthzDecision {
+    pub allowed: bool,
+    pub ttl: Duration,
+}
+
+/// Expires each cached decision after its own TTL, so denials can be kept
+/// much shorter than grants
+pub struct DecisionExpiry;
+
+impl Expiry<AuthzCacheKey, AuthzDecision> for DecisionExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &AuthzCacheKey,
+        value: &AuthzDecision,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(value.ttl)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &AuthzCacheKey,
+        value: &AuthzDecision,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(value.ttl)
+    }
+}
+
+/// Build the authz decision cache with per-entry expiry, holding at most
+/// `max_capacity` decisions
+pub fn build_authz_cache(max_capacity: u64) -> Cache<AuthzCacheKey, AuthzDecision> {
+    Cache::builder()
+        .max_capacity(max_capacity)
+        .expire_after(DecisionExpiry)
+        .support_invalidation_closures()
+        .build()
+}
+
+/// Build the JWKS key cache, holding at most `max_capacity` keys
+pub fn build_jwks_cache(max_capacity: u64) -> Cache<(String, String), DecodingKey> {
+    Cache::builder()
+        .max_capacity(max_capacity)
+        .time_to_live(JWKS_CACHE_TTL)
+        .build()
+}
+
+/// Build the ListObjects cache; results live as long as positive decisions
+pub fn build_list_objects_cache(ttl: Duration) -> Cache<ListObjectsKey, Arc<Vec<String>>> {
+    Cache::builder()
+        .time_to_live(ttl)
+        .support_invalidation_closures()
+        .build()
+}
+
+/// Build the cache of verified token claims. The TTL should be short; a
+/// cached token is still rejected once past its own `exp`.
+pub fn build_token_cache(ttl: Duration) -> TokenCache {
+    Cache::builder().time_to_live(ttl).build()
+}
+
+/// Build the stale-while-error cache of last-known grants
+pub fn build_stale_grant_cache(ttl: Duration) -> StaleGrantCache {
+    Cache::builder()
+        .time_to_live(ttl)
+        .support_invalidation_closures()
+        .build()
+}
+
+/// Drop every cached decision and object listing for `user_id`, e.g. after
+/// a webhook changed their tuples. Entries are removed lazily by moka, but
+/// are never returned again once this returns.
+pub fn invalidate_user(state: &AppState, user_id: &str) {
+    let user = user_id.to_string();
+    if let Err(e) = state
+        .cache
+        .invalidate_entries_if(move |key, _| key.user == user)
+    {
+        tracing::error!("Failed to invalidate authz cache for {}: {}", user_id, e);
+    }
+    let user = user_id.to_string();
+    if let Err(e) = state
+        .list_objects_cache
+        .invalidate_entries_if(move |key, _| key.0 == user)
+    {
+        tracing::error!("Failed to invalidate list cache for {}: {}", user_id, e);
+    }
+    if let Some(stale_grants) = &state.stale_grants {
+        let user = user_id.to_string();
+        if let Err(e) = stale_grants.invalidate_entries_if(move |key, _| key.user == user) {
+            tracing::error!("Failed to invalidate stale grants for {}: {}", user_id, e);
+        }
+    }
+}
+
+/// Cache a fresh OpenFGA decision using the positive or negative TTL.
+/// `grant_ttl` is the route's own TTL; denials never outlive it either.
+pub async fn cache_decision(
+    state: &AppState,
+    key: AuthzCacheKey,
+    allowed: bool,
+    grant_ttl: Option<Duration>,
+) {
+    remember_grant(state, &key, allowed).await;
+
+    let decision = fresh_decision(state, allowed, grant_ttl);
+    if !decision.ttl.is_zero() {
+        state.cache.insert(key, decision).await;
+    }
+}
+
+/// A fresh decision with its positive or negative TTL (see `cache_decision`)
+fn fresh_decision(state: &AppState, allowed: bool, grant_ttl: Option<Duration>) -> AuthzDecision {
+    let grant_ttl = grant_ttl.unwrap_or(state.authz_cache_ttl);
+    let ttl = if allowed {
+        grant_ttl
+    } else {
+        state.authz_negative_cache_ttl.min(grant_ttl)
+    };
+    AuthzDecision { allowed, ttl }
+}
+
+/// Remember grants for stale-while-error; a fresh denial revokes one
+async fn remember_grant(state: &AppState, key: &AuthzCacheKey, allowed: bool) {
+    if let Some(stale_grants) = &state.stale_grants {
+        if allowed {
+            stale_grants.insert(key.clone(), ()).await;
+        } else {
+            stale_grants.invalidate(key).await;
+        }
+    }
+}
+
+/// Re-check a sampled cache hit against OpenFGA in the background, counting
+/// disagreements so cache TTLs can be tuned. Never delays the response.
+fn verify_cache_hit(
+    state: &AppState,
+    key: &AuthzCacheKey,
+    object: &str,
+    action: Option<&str>,
+    context: Option<&CheckContext>,
+    cached: bool,
+) {
+    if !rand::random_bool(state.authz_cache_verify_rate) {
+        return;
+    }
+
+    let state = state.clone();
+    let key = key.clone();
+    let object = object.to_string();
+    let action = action.map(str::to_string);
+    let context = context.cloned();
+    tokio::spawn(async move {
+        // Bypass OpenFGA's own cache, or both sides could be equally stale
+        let checked = try_check_openfga_permission(
+            &state.http_client,
+            &state.fga_client,
+            &key.user,
+            &object,
+            action.as_deref(),
+            context.as_ref(),
+            Some(Consistency::HigherConsistency),
+        )
+        .await;
+        // Failed checks are already logged and counted
+        let Ok(Some(allowed)) = checked else {
+            return;
+        };
+        if allowed != cached {
+            let kind = if cached { "stale_allow" } else { "stale_deny" };
+            tracing::warn!(
+                "Cached decision for {:?} is stale: cached {}, OpenFGA says {}",
+                key,
+                cached,
+                allowed
+            );
+            metrics::counter!(telemetry::AUTHZ_CACHE_STALENESS_TOTAL, "kind" => kind).increment(1);
+        }
+    });
+}
+
+/// Why a check on a cache miss produced no decision
+#[derive(Debug)]
+enum CheckFailure {
+    /// OpenFGA gave no answer (see `try_check_openfga_permission`)
+    Unavailable,
+    /// OpenFGA rejected the check as invalid for its model
+    ModelError,
+}
+
+/// A single OpenFGA check for `key` on a cache miss
+async fn fetch_decision(
+    state: &AppState,
+    key: &AuthzCacheKey,
+    object: &str,
+    action: Option<&str>,
+    context: Option<&CheckContext>,
+    grant_ttl: Option<Duration>,
+) -> Result<AuthzDecision, CheckFailure> {
+    tracing::debug!("Cache miss for {:?}, checking OpenFGA", key);
+    let check_started = Instant::now();
+    let checked = try_check_openfga_permission(
+        &state.http_client,
+        &state.fga_client,
+        &key.user,
+        object,
+        action,
+        context,
+        None,
+    )
+    .await
+    .or_else(|e| {
+        if e.is::<OpenFgaError>() {
+            Err(CheckFailure::ModelError)
+        } else {
+            Ok(Some(false)) // e.g. an unmapped action
+        }
+    });
+    metrics::histogram!(telemetry::OPENFGA_CHECK_DURATION_SECONDS)
+        .record(check_started.elapsed().as_secs_f64());
+
+    let allowed = checked?.ok_or(CheckFailure::Unavailable)?;
+    remember_grant(state, key, allowed).await;
+    Ok(fresh_decision(state, allowed, grant_ttl))
 }
 
 #[derive(Debug, Deserialize)]
 struct Jwk {
     kid: String,
-    n: String,
-    e: String,
+    kty: Option<String>, // RSA, EC or OKP (assume RSA when absent)
+    n: Option<String>,
+    e: Option<String>,
+    #[allow(dead_code)]
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+    x5c: Option<Vec<String>>, // Certificate chain (base64 DER), leaf first
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,278 +769,2104 @@ struct Jwks {
     keys: Vec<Jwk>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Claims {
-    sub: String,
-    exp: i64,
+/// Minimum time between JWKS fetches triggered by unknown `kid`s
+pub const DEFAULT_JWKS_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A trusted token issuer and the keys from its last successful JWKS fetch
+///
+/// The last good key set is the fallback when the JWKS endpoint is down;
+/// refreshes are throttled so tokens with made-up `kid`s can't hammer the IdP.
+/// An issuer given a static key set never fetches at all.
+pub struct JwtIssuer {
+    pub jwks_url: String,
+    last_good: ArcSwap<HashMap<String, DecodingKey>>,
+    last_refresh: std::sync::Mutex<Option<Instant>>,
+    min_refresh_interval: Duration,
+    static_keys: bool,
 }
 
-#[derive(Clone)]
-pub struct OpenFgaClient {
-    pub url: String,
-    pub store_id: String,
+impl JwtIssuer {
+    pub fn new(jwks_url: String, min_refresh_interval: Duration) -> Self {
+        Self {
+            jwks_url,
+            last_good: ArcSwap::default(),
+            last_refresh: std::sync::Mutex::new(None),
+            min_refresh_interval,
+            static_keys: false,
+        }
+    }
+
+    /// Trust only `keys` (e.g. from `load_jwks_file`) instead of `jwks_url`
+    pub fn with_static_keys(mut self, keys: HashMap<String, DecodingKey>) -> Self {
+        self.last_good = ArcSwap::from_pointee(keys);
+        self.static_keys = true;
+        self
+    }
+
+    /// Whether keys come from a static JWKS rather than `jwks_url`
+    pub fn has_static_keys(&self) -> bool {
+        self.static_keys
+    }
+
+    /// Claim the next refresh slot, or false if one ran too recently
+    fn try_begin_refresh(&self) -> bool {
+        let mut last = self.last_refresh.lock().unwrap();
+        if last.is_some_and(|at| at.elapsed() < self.min_refresh_interval) {
+            return false;
+        }
+        *last = Some(Instant::now());
+        true
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Audience {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Claims {
+    pub sub: String,
+    pub iss: String,
+    pub exp: i64,
+    pub nbf: Option<i64>,
+    pub aud: Option<Audience>,
+    pub scope: Option<String>, // Space-separated OAuth scopes
+    pub roles: Option<Vec<String>>,
+    pub org_id: Option<String>,
+    pub jti: Option<String>, // Token ID, checked against the revocation denylist
+}
+
+impl Claims {
+    /// Forward scopes, roles and org upstream; values that aren't valid
+    /// header values are dropped rather than failing the request
+    fn apply_headers(&self, headers: &mut HeaderMap) {
+        let roles = self.roles.as_ref().map(|roles| roles.join(","));
+        for (name, value) in [
+            (USER_SCOPES_HEADER, self.scope.as_deref()),
+            (USER_ROLES_HEADER, roles.as_deref()),
+            (USER_ORG_HEADER, self.org_id.as_deref()),
+        ] {
+            let Some(value) = value.filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            match HeaderValue::from_str(value) {
+                Ok(value) => {
+                    headers.insert(name, value);
+                }
+                Err(_) => tracing::warn!("Dropping {} claim not valid as a header", name),
+            }
+        }
+    }
 }
 
-impl OpenFgaClient {
-    pub fn new(url: String, store_id: String) -> Self {
-        Self { url, store_id }
+/// Remove any client-supplied identity headers before auth runs
+fn strip_identity_headers(headers: &mut HeaderMap) {
+    for name in IDENTITY_HEADERS {
+        headers.remove(name);
     }
 }
 
 #[derive(Debug, Deserialize)]
 struct AccessRule {
     path: String,
-    #[allow(dead_code)]
     method: String,
     feature: String,
     action: Option<String>, // NEW: view, edit, delete
     target: Option<String>,
+    rate_limit: Option<u32>,
+    rate_window_secs: Option<u64>,
+    object: Option<String>,
+    rewrite: Option<PathRewrite>,
+    cache_ttl_secs: Option<u64>,
+    action_from_body: Option<BodyAction>,
+}
+
+/// Rule actions accepted when no OpenFGA relation map is configured
+pub const DEFAULT_RULE_ACTIONS: &[&str] = &["view", "edit", "delete"];
+
+/// Rule targets that always resolve (see `AppState::service_url`)
+const BUILTIN_TARGETS: &[&str] = &["zitadel", "openfga"];
+
+/// What access rules may refer to, for `validate_rules`
+#[derive(Clone, Debug)]
+pub struct RuleValidation {
+    pub actions: BTreeSet<String>,
+    pub targets: BTreeSet<String>, // Including the built-in zitadel/openfga
+}
+
+impl RuleValidation {
+    /// Actions are the relation map's keys (or `DEFAULT_RULE_ACTIONS`
+    /// without one); targets are the configured services plus built-ins
+    pub fn new(relations: &HashMap<String, String>, services: &HashMap<String, String>) -> Self {
+        let actions = if relations.is_empty() {
+            DEFAULT_RULE_ACTIONS.iter().map(|a| a.to_string()).collect()
+        } else {
+            relations.keys().cloned().collect()
+        };
+        let targets = BUILTIN_TARGETS
+            .iter()
+            .map(|t| t.to_string())
+            .chain(services.keys().cloned())
+            .collect();
+        Self { actions, targets }
+    }
+}
+
+/// Every problem `validate_rules` found, one per line
+#[derive(Debug)]
+pub struct InvalidRules(pub Vec<String>);
+
+impl std::fmt::Display for InvalidRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} problem(s) in access rules:", self.0.len())?;
+        for problem in &self.0 {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidRules {}
+
+/// Check an access rules file before it's loaded: actions and targets must
+/// be known, `public_access` rules can't carry an action, and no two rules
+/// may claim the same or conflicting routes. All problems are reported at
+/// once as `InvalidRules`.
+pub async fn validate_rules(
+    path: &str,
+    known: &RuleValidation,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = tokio::fs::read_to_string(path).await?;
+    let rules: Vec<AccessRule> = serde_json::from_str(&content)?;
+
+    let mut problems = Vec::new();
+    let mut endpoints = HashSet::new();
+    // Each distinct path once, to surface matchit conflicts between paths
+    let mut routes: Router<()> = Router::new();
+    let mut paths = HashSet::new();
+    for rule in &rules {
+        let name = format!("{} {}", rule.method, rule.path);
+
+        if !endpoints.insert((&rule.path, &rule.method)) {
+            problems.push(format!("{}: duplicate rule", name));
+        }
+        if paths.insert(&rule.path) {
+            if let Err(e) = routes.insert(rule.path.as_str(), ()) {
+                problems.push(format!(
+                    "{}: path conflicts with another rule ({})",
+                    name, e
+                ));
+            }
+        }
+
+        if rule.action.is_some() && rule.action_from_body.is_some() {
+            problems.push(format!("{}: has both action and action_from_body", name));
+        }
+        let body_actions = rule
+            .action_from_body
+            .iter()
+            .flat_map(|b| b.actions.values());
+        for action in rule.action.iter().chain(body_actions) {
+            if rule.feature == "public_access" {
+                problems.push(format!(
+                    "{}: public_access rule has action '{}'",
+                    name, action
+                ));
+            } else if !known.actions.contains(action) {
+                problems.push(format!(
+                    "{}: unknown action '{}' (known: {})",
+                    name,
+                    action,
+                    known.actions.iter().cloned().collect::<Vec<_>>().join(", ")
+                ));
+            }
+        }
+
+        if let Some(target) = &rule.target {
+            if !known.targets.contains(target) {
+                problems.push(format!(
+                    "{}: unknown target '{}' (known: {})",
+                    name,
+                    target,
+                    known.targets.iter().cloned().collect::<Vec<_>>().join(", ")
+                ));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(InvalidRules(problems).into())
+    }
 }
 
 pub async fn load_access_rules(
     path: &str,
-) -> Result<Arc<Router<RouteConfig>>, Box<dyn std::error::Error>> {
+) -> Result<Arc<Router<MethodRoutes>>, Box<dyn std::error::Error>> {
     let content = tokio::fs::read_to_string(path).await?;
     let rules: Vec<AccessRule> = serde_json::from_str(&content)?;
 
-    let mut router = Router::new();
+    // Group rules by path first, since matchit rejects duplicate paths
+    let mut paths: Vec<String> = Vec::new();
+    let mut grouped: HashMap<String, MethodRoutes> = HashMap::new();
     for rule in rules {
-        router.insert(
-            &rule.path,
-            RouteConfig {
-                feature: rule.feature,
-                action: rule.action, // Pass action from access rules
-                target: rule.target,
-            },
-        )?;
+        let route_config = RouteConfig {
+            feature: rule.feature,
+            action: rule.action, // Pass action from access rules
+            target: rule.target,
+            rate_limit: rule.rate_limit,
+            rate_window_secs: rule.rate_window_secs,
+            object: rule.object,
+            rewrite: rule.rewrite,
+            cache_ttl_secs: rule.cache_ttl_secs,
+            action_from_body: rule.action_from_body,
+        };
+
+        let entry = grouped.entry(rule.path.clone()).or_insert_with(|| {
+            paths.push(rule.path.clone());
+            MethodRoutes::default()
+        });
+
+        let duplicate = if rule.method == "*" {
+            entry.any_method.replace(route_config).is_some()
+        } else {
+            let method = Method::from_bytes(rule.method.to_uppercase().as_bytes())
+                .map_err(|_| format!("Invalid method '{}' for path {}", rule.method, rule.path))?;
+            entry.methods.insert(method, route_config).is_some()
+        };
+
+        if duplicate {
+            return Err(format!("Duplicate rule for {} {}", rule.method, rule.path).into());
+        }
+    }
+
+    // A catch-all rule is the default for its whole subtree: matchit already
+    // prefers more specific paths, and those inherit the methods they don't
+    // set themselves. Enclosing prefixes go first so defaults chain down.
+    let mut prefixes: Router<String> = Router::new();
+    for path in paths.iter().filter(|p| catch_all_prefix(p).is_some()) {
+        prefixes.insert(path.clone(), path.clone())?;
+    }
+    let mut ordered: Vec<&String> = paths.iter().collect();
+    ordered.sort_by_key(|p| (catch_all_prefix(p).is_none(), p.len()));
+    for path in ordered {
+        let inner = catch_all_prefix(path).unwrap_or(path);
+        let Ok(parent) = prefixes.at(inner) else {
+            continue;
+        };
+        if let Some(defaults) = grouped.get(parent.value).cloned() {
+            if let Some(routes) = grouped.get_mut(path) {
+                routes.inherit(&defaults);
+            }
+        }
+    }
+
+    let mut router = Router::new();
+    for path in paths {
+        let routes = grouped.remove(&path).unwrap_or_default();
+        router.insert(path, routes)?;
     }
 
     Ok(Arc::new(router))
 }
 
-pub async fn auth_middleware(
-    State(state): State<AppState>,
-    mut req: Request,
-    next: Next,
-) -> Result<Response, StatusCode> {
-    let path = req.uri().path();
+/// Re-read `access_rules_path` and atomically swap in the new router
+///
+/// In-flight requests finish against the rules they started with. On a read,
+/// parse or validation error the current rules stay live.
+pub async fn reload_access_rules(state: &AppState) -> Result<(), Box<dyn std::error::Error>> {
+    let known = RuleValidation::new(&state.fga_client.relations, &state.services);
+    validate_rules(&state.access_rules_path, &known).await?;
+    let router = load_access_rules(&state.access_rules_path).await?;
+    state.router.store(router);
+    tracing::info!("Reloaded access rules from {}", state.access_rules_path);
+    Ok(())
+}
 
-    // Check router for access rules
-    let match_result = state.router.at(path);
+/// Correlation ID for the current request, stored in request extensions
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
 
-    // Check if no route found
-    if let Err(_) = match_result {
-        tracing::warn!("No access rule found for path: {}", path);
-        return Err(StatusCode::FORBIDDEN);
-    };
+/// Reuse the caller's `X-Request-ID` (or generate one), run the request
+/// inside a span carrying it, and echo it back on the response
+pub async fn request_id_middleware(mut req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_owned)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
 
-    let matched = match_result.unwrap();
-    let route_config = matched.value;
+    req.extensions_mut().insert(RequestId(request_id.clone()));
 
-    // 1. Check if path has public_access feature
-    if route_config.feature == "public_access" {
-        tracing::debug!("Public access path, skipping auth/authz for: {}", path);
-        return Ok(next.run(req).await);
+    let span = tracing::info_span!("request", request_id = %request_id);
+    let mut response = next.run(req).instrument(span).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// What `auth_middleware` decided and why, for metrics and the access log
+#[derive(Debug, Default)]
+struct AuthDecision {
+    result: &'static str, // allowed, forbidden, unauthorized, public, ...
+    user: Option<String>,
+    feature: Option<String>,
+    action: Option<String>,
+    cache: Option<&'static str>, // hit or miss, when OpenFGA was consulted
+    allowed: Option<bool>,
+}
+
+impl AuthDecision {
+    /// Describe the matched rule and outcome for developers (debug mode only)
+    fn apply_debug_headers(&self, headers: &mut HeaderMap) {
+        for (name, value) in [
+            (DEBUG_FEATURE_HEADER, self.feature.as_deref()),
+            (DEBUG_ACTION_HEADER, self.action.as_deref()),
+            (DEBUG_DECISION_HEADER, Some(self.result)),
+        ] {
+            let value = value.filter(|v| !v.is_empty());
+            if let Some(value) = value.and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+    }
+}
+
+pub async fn auth_middleware(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    let started = Instant::now();
+    let method = req.method().clone();
+    let path = req.uri().path().to_owned();
+    let request_id = req.extensions().get::<RequestId>().cloned();
+    let client_ip = client_ip(&state, &req);
+
+    let mut decision = AuthDecision::default();
+    let mut result = authorize(&state, req, next, &mut decision).await;
+    telemetry::record_auth_result(decision.result);
+
+    // Upstream pushing back is passed through as-is, but worth knowing about
+    if let Ok(response) = &result {
+        if let Some(retry_after) = upstream_retry_after(response) {
+            tracing::warn!(
+                "Upstream throttled {} {} (status {}, retry after {}) for user {:?} on feature {:?}",
+                method,
+                path,
+                response.status(),
+                retry_after,
+                decision.user,
+                decision.feature
+            );
+            metrics::counter!(
+                telemetry::UPSTREAM_THROTTLED_TOTAL,
+                "feature" => decision.feature.clone().unwrap_or_default()
+            )
+            .increment(1);
+        }
+    }
+
+    let status = match &result {
+        Ok(response) | Err(response) => response.status(),
+    };
+    if state.access_log {
+        tracing::info!(
+            target: "access_log",
+            method = %method,
+            path = %path,
+            user = decision.user.as_deref(),
+            feature = decision.feature.as_deref(),
+            action = decision.action.as_deref(),
+            cache = decision.cache,
+            allowed = decision.allowed,
+            result = decision.result,
+            status = status.as_u16(),
+            latency_ms = started.elapsed().as_secs_f64() * 1000.0,
+        );
+    }
+
+    // Every denial by the gateway itself is audited; other outcomes (including
+    // upstream 401/403/429s passed through) only when sampled
+    let denied = result.is_err()
+        && matches!(
+            decision.result,
+            "unauthorized" | "forbidden" | "rate_limited" | "concurrency_limited"
+        );
+    if denied || rand::random_bool(state.audit_allow_sample_rate) {
+        audit::emit(
+            &state,
+            AuditEvent {
+                timestamp: AuditEvent::now(),
+                request_id: request_id.map(|RequestId(id)| id),
+                method: method.to_string(),
+                path,
+                client_ip,
+                status: status.as_u16(),
+                result: decision.result,
+                allowed: decision.allowed,
+                user: decision.user.clone(),
+                feature: decision.feature.clone(),
+                action: decision.action.clone(),
+            },
+        );
+    }
+
+    if state.debug_headers {
+        let (Ok(response) | Err(response)) = &mut result;
+        decision.apply_debug_headers(response.headers_mut());
+    }
+
+    result
+}
+
+/// `Retry-After` of an upstream throttling response (a 429, or a 503 that
+/// says when to come back); "-" for a 429 without one
+fn upstream_retry_after(response: &Response) -> Option<&str> {
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|h| h.to_str().ok());
+    match response.status() {
+        StatusCode::TOO_MANY_REQUESTS => Some(retry_after.unwrap_or("-")),
+        StatusCode::SERVICE_UNAVAILABLE => retry_after,
+        _ => None,
+    }
+}
+
+/// 503 telling clients the gateway is in maintenance and when to come back
+fn maintenance_response(state: &AppState) -> Response {
+    let mut response = (
+        StatusCode::SERVICE_UNAVAILABLE,
+        axum::Json(serde_json::json!({
+            "status": "maintenance",
+            "message": "Service is undergoing maintenance, please retry later",
+        })),
+    )
+        .into_response();
+    response.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(state.maintenance_retry_after_secs),
+    );
+    response
+}
+
+async fn authorize(
+    state: &AppState,
+    mut req: Request,
+    next: Next,
+    decision: &mut AuthDecision,
+) -> Result<Response, Response> {
+    // Identity headers only ever come from the gateway, whichever branch runs
+    strip_identity_headers(req.headers_mut());
+
+    // Bound everything below (routing, object templates, the upstream URL)
+    let url_len = req.uri().path_and_query().map_or(0, |pq| pq.as_str().len());
+    if url_len > state.max_url_len {
+        tracing::warn!(
+            "Request URL of {} bytes exceeds {}",
+            url_len,
+            state.max_url_len
+        );
+        decision.result = "uri_too_long";
+        return Err(StatusCode::URI_TOO_LONG.into_response());
+    }
+
+    // Browsers send preflights without credentials. Answer them here rather
+    // than 401 if the CORS layer didn't; nothing is proxied, so they can't
+    // reach the resource.
+    if is_preflight(&req) {
+        decision.result = "preflight";
+        return Ok(StatusCode::OK.into_response());
+    }
+
+    // Upstreams are down for planned work; nothing is worth checking
+    if state.maintenance_mode.load(Ordering::Relaxed) {
+        decision.result = "maintenance";
+        return Err(maintenance_response(state));
+    }
+
+    let path = req.uri().path();
+
+    // Check router for access rules
+    let router = state.router.load_full();
+    let matched = match router.at(path) {
+        Ok(matched) => matched,
+        Err(_) => match state.default_policy {
+            DefaultPolicy::Deny => {
+                // Not an authz failure: no rule was ever consulted
+                tracing::warn!("No access rule found for path: {}", path);
+                decision.result = "no_rule";
+                return Err(state.unmatched_status.status().into_response());
+            }
+            DefaultPolicy::Proxy => {
+                tracing::debug!("No access rule for {}, proxying by default policy", path);
+                decision.user = attach_optional_identity(state, &mut req).await;
+                decision.result = "unmatched";
+                return Ok(next.run(req).await);
+            }
+        },
+    };
+
+    // Path is governed, but not necessarily for this method
+    let route_config = match matched.value.get(req.method()) {
+        Some(route_config) => route_config,
+        None => {
+            tracing::warn!("No access rule for {} {}", req.method(), path);
+            decision.result = "method_not_allowed";
+            return Err(StatusCode::METHOD_NOT_ALLOWED.into_response());
+        }
+    };
+
+    decision.feature = Some(route_config.feature.clone());
+    decision.action = route_config.action.clone();
+
+    // Resolve the OpenFGA object now, while the path params are at hand
+    let object = match route_config.resolve_object(&matched.params) {
+        Ok(object) => object,
+        Err(e) => {
+            tracing::error!("Cannot build OpenFGA object for {}: {}", path, e);
+            decision.result = "forbidden";
+            return Err(StatusCode::FORBIDDEN.into_response());
+        }
+    };
+
+    // 1. Check if path has public_access feature
+    //    Auth is optional here: a valid token still identifies the user upstream
+    if route_config.feature == "public_access" {
+        tracing::debug!("Public access path, skipping authz for: {}", path);
+        // Anonymous callers are budgeted by address rather than by user
+        let rate_limit = match client_ip(state, &req) {
+            Some(ip) => {
+                match enforce_rate_limit(state, &RateLimitSubject::Ip(ip), route_config).await {
+                    Ok(rate_limit) => rate_limit,
+                    Err(response) => {
+                        decision.result = rate_limit_result(&response);
+                        return Err(response);
+                    }
+                }
+            }
+            None => {
+                tracing::debug!("No client address for {}, skipping IP rate limit", path);
+                None
+            }
+        };
+        decision.user = attach_optional_identity(state, &mut req).await;
+        decision.result = "public";
+        let mut response = next.run(req).await;
+        if let Some(rate_limit) = rate_limit {
+            rate_limit.apply_headers(response.headers_mut());
+        }
+        return Ok(response);
+    }
+
+    // 2. Extract token
+    let token = match bearer_token(req.headers()) {
+        Some(t) => t,
+        None => {
+            tracing::warn!("Missing or invalid Authorization header");
+            decision.result = "unauthorized";
+            return Err(StatusCode::UNAUTHORIZED.into_response());
+        }
+    };
+
+    // 3. Validate JWT
+    let claims = match validate_jwt(state, token).await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!("JWT validation failed: {:?}", e);
+            decision.result = "unauthorized";
+            return Err(StatusCode::UNAUTHORIZED.into_response());
+        }
+    };
+
+    let user_id = &claims.sub;
+    decision.user = Some(user_id.clone());
+
+    // In a multi-tenant model the object is only meaningful within a tenant
+    let tenant = match &state.tenant_source {
+        None => None,
+        Some(source) => match source.resolve(&claims, req.headers()) {
+            Some(tenant) => Some(tenant.to_string()),
+            None => {
+                tracing::warn!("No tenant for user {} on {}", user_id, path);
+                decision.result = "forbidden";
+                return Err(StatusCode::FORBIDDEN.into_response());
+            }
+        },
+    };
+    let object = scoped_object(object, tenant.as_deref());
+
+    // 4. Rate Limiting (Redis-based, per user and feature)
+    let subject = RateLimitSubject::User(user_id);
+    let rate_limit = match enforce_rate_limit(state, &subject, route_config).await {
+        Ok(rate_limit) => rate_limit,
+        Err(response) => {
+            decision.result = rate_limit_result(&response);
+            return Err(response);
+        }
+    };
+
+    // Cap the user's concurrent requests; the slot is held until the
+    // response body has been sent (or the request fails)
+    let in_flight = match &state.concurrency_limiter {
+        None => None,
+        Some(limiter) => match limiter.try_acquire(user_id) {
+            Some(guard) => Some(guard),
+            None => {
+                tracing::warn!("User {} has too many requests in flight", user_id);
+                decision.result = "concurrency_limited";
+                return Err(StatusCode::TOO_MANY_REQUESTS.into_response());
+            }
+        },
+    };
+
+    // RPC-style routes name the action in the body, so buffer it (within
+    // the body limit) and hand it on to the proxy unchanged
+    let body_action = match &route_config.action_from_body {
+        None => None,
+        Some(body_action) => match read_body_action(state, &mut req, body_action).await {
+            Ok(action) => Some(action),
+            Err(status) => {
+                decision.result = match status {
+                    StatusCode::FORBIDDEN => "forbidden",
+                    StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+                    _ => "bad_request",
+                };
+                return Err(status.into_response());
+            }
+        },
+    };
+    // A rule's own action wins; otherwise the method decides (DELETE -> delete)
+    let action = body_action
+        .as_deref()
+        .or_else(|| route_config.action_for(req.method(), &state.method_actions));
+    decision.action = action.map(str::to_string);
+
+    // 5. Caching & OpenFGA Check (context is part of the key so decisions don't collide)
+    let check_context = CheckContext::from_headers(&state.openfga_context_headers, req.headers());
+    let cache_key = decision_key(
+        user_id,
+        route_config,
+        &object,
+        action,
+        tenant.as_deref(),
+        check_context.as_ref(),
+    );
+
+    // Concurrent misses for one key share a single OpenFGA check
+    let grant_ttl = route_config.cache_ttl_secs.map(Duration::from_secs);
+    let fetched = state
+        .cache
+        .entry_by_ref(&cache_key)
+        .or_try_insert_with(fetch_decision(
+            state,
+            &cache_key,
+            &object,
+            action,
+            check_context.as_ref(),
+            grant_ttl,
+        ))
+        .await;
+
+    let authorized = match fetched {
+        Ok(entry) if !entry.is_fresh() => {
+            tracing::debug!("Cache hit for {:?}", cache_key);
+            metrics::counter!(telemetry::AUTHZ_CACHE_HITS_TOTAL).increment(1);
+            decision.cache = Some("hit");
+            let allowed = entry.into_value().allowed;
+            verify_cache_hit(
+                state,
+                &cache_key,
+                &object,
+                action,
+                check_context.as_ref(),
+                allowed,
+            );
+            allowed
+        }
+        Ok(entry) => {
+            metrics::counter!(telemetry::AUTHZ_CACHE_MISSES_TOTAL).increment(1);
+            decision.cache = Some("miss");
+            entry.into_value().allowed
+        }
+        // Not a deny: the rule names something the model doesn't have
+        Err(e) if matches!(*e, CheckFailure::ModelError) => {
+            metrics::counter!(telemetry::AUTHZ_CACHE_MISSES_TOTAL).increment(1);
+            decision.cache = Some("miss");
+            let status = state.model_error_status.status();
+            decision.result = if status == StatusCode::FORBIDDEN {
+                "forbidden"
+            } else {
+                "openfga_error"
+            };
+            return Err(status.into_response());
+        }
+        // OpenFGA couldn't answer: fall back to a last-known grant
+        Err(_) if has_stale_grant(state, &cache_key) => {
+            tracing::warn!(
+                "OpenFGA unavailable, serving stale grant for user {} on {}",
+                user_id,
+                object
+            );
+            metrics::counter!(telemetry::AUTHZ_CACHE_MISSES_TOTAL).increment(1);
+            decision.cache = Some("stale");
+            true
+        }
+        Err(_) => {
+            metrics::counter!(telemetry::AUTHZ_CACHE_MISSES_TOTAL).increment(1);
+            decision.cache = Some("miss");
+            false
+        }
+    };
+
+    decision.allowed = Some(authorized);
+
+    if !authorized {
+        tracing::warn!(
+            "User {} not authorized for feature {}",
+            user_id,
+            route_config.feature
+        );
+        decision.result = "forbidden";
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    // 6. Inject identity headers for upstream (`sub` was checked by validate_jwt)
+    let Ok(user_header) = HeaderValue::from_str(user_id) else {
+        tracing::error!("User ID {:?} is not a valid header value", user_id);
+        decision.result = "unauthorized";
+        return Err(StatusCode::UNAUTHORIZED.into_response());
+    };
+    req.headers_mut().insert(USER_ID_HEADER, user_header);
+    claims.apply_headers(req.headers_mut());
+
+    decision.result = "allowed";
+
+    // Let well-behaved clients self-throttle
+    let mut response = next.run(req).await;
+    if let Some(rate_limit) = rate_limit {
+        rate_limit.apply_headers(response.headers_mut());
+    }
+    if let Some(guard) = in_flight {
+        // Dropped along with the body, once it's streamed or abandoned
+        response = response.map(|body| {
+            Body::new(body.map_frame(move |frame| {
+                let _ = &guard;
+                frame
+            }))
+        });
+    }
+    Ok(response)
+}
+
+/// A CORS preflight: OPTIONS naming the method the real request will use
+fn is_preflight(req: &Request) -> bool {
+    req.method() == Method::OPTIONS
+        && req
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+/// Buffer the request body, resolve its action and put the body back for
+/// the proxy. Oversize bodies are 413, unreadable ones 400 and values with
+/// no action mapped 403.
+async fn read_body_action(
+    state: &AppState,
+    req: &mut Request,
+    body_action: &BodyAction,
+) -> Result<String, StatusCode> {
+    let body = std::mem::take(req.body_mut());
+    let bytes = axum::body::to_bytes(body, state.max_body_bytes)
+        .await
+        .map_err(|e| {
+            tracing::warn!("Cannot buffer body for action: {}", e);
+            if e.into_inner().is::<LengthLimitError>() {
+                StatusCode::PAYLOAD_TOO_LARGE
+            } else {
+                StatusCode::BAD_REQUEST
+            }
+        })?;
+
+    let action = match body_action.resolve(&bytes) {
+        Ok(action) => action.to_string(),
+        Err(BodyActionError::Malformed) => {
+            tracing::warn!("No string at {} in request body", body_action.pointer);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(BodyActionError::Unmapped(value)) => {
+            tracing::warn!("No action mapped for body value {:?}", value);
+            return Err(StatusCode::FORBIDDEN);
+        }
+    };
+    *req.body_mut() = Body::from(bytes);
+    Ok(action)
+}
+
+/// Set the identity headers from the bearer token if it validates, and
+/// otherwise leave them unset. Returns the identified user, if any.
+async fn attach_optional_identity(state: &AppState, req: &mut Request) -> Option<String> {
+    let token = bearer_token(req.headers())?.to_owned();
+    match validate_jwt(state, &token).await {
+        Ok(claims) => {
+            let user_id = HeaderValue::from_str(&claims.sub).ok()?;
+            req.headers_mut().insert(USER_ID_HEADER, user_id);
+            claims.apply_headers(req.headers_mut());
+            Some(claims.sub)
+        }
+        Err(e) => {
+            tracing::debug!("Ignoring invalid optional token: {:?}", e);
+            None
+        }
+    }
+}
+
+/// The caller's address. With `trusted_proxy_hops` set, it's the entry that
+/// many hops back along `X-Forwarded-For` (the peer being the last hop);
+/// otherwise the TCP peer, or when that peer is a trusted proxy, the client
+/// it reports in `X-Forwarded-For` (nearest untrusted hop) or `X-Real-IP`.
+/// With neither configured, client-supplied headers are ignored. None when
+/// the server wasn't given connection info.
+pub fn client_ip(state: &AppState, req: &Request) -> Option<IpAddr> {
+    let peer = req.extensions().get::<ConnectInfo<SocketAddr>>()?.0.ip();
+    let headers = req.headers();
+    let forwarded = forwarded_for(headers);
+
+    if state.trusted_proxy_hops > 0 {
+        // Each proxy appends the address it saw, so only the last `hops`
+        // entries are trustworthy; anything further left is client-written
+        let mut chain = forwarded;
+        chain.push(Some(peer));
+        let index = chain.len().saturating_sub(state.trusted_proxy_hops + 1);
+        return chain[index].or(Some(peer));
+    }
+
+    if !state.trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+    // Walk back from our side; anything left of an untrusted hop is spoofable
+    for hop in forwarded.into_iter().rev() {
+        match hop {
+            Some(ip) if state.trusted_proxies.contains(&ip) => continue,
+            Some(ip) => return Some(ip),
+            None => break,
+        }
+    }
+
+    headers
+        .get("x-real-ip")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .or(Some(peer))
+}
+
+/// Every `X-Forwarded-For` entry, leftmost first, across repeated headers.
+/// Entries that aren't bare IPs are kept as None so hop positions hold.
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|hop| hop.trim().parse().ok())
+        .collect()
+}
+
+/// `X-Forwarded-For`, `-Proto` and `-Host` for the upstream request.
+///
+/// A trusted proxy's values are kept and the chain extended with it; a
+/// direct client's are replaced, so upstream never sees a spoofed chain.
+pub(crate) struct Forwarded {
+    for_chain: Option<HeaderValue>,
+    proto: HeaderValue,
+    host: Option<HeaderValue>,
+}
+
+impl Forwarded {
+    pub(crate) fn new(
+        state: &AppState,
+        headers: &HeaderMap,
+        extensions: &axum::http::Extensions,
+    ) -> Self {
+        let peer = extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let trusted = peer.is_some_and(|peer| {
+            state.trusted_proxy_hops > 0 || state.trusted_proxies.contains(&peer)
+        });
+        let reported = |name: &str| headers.get(name).filter(|_| trusted).cloned();
+
+        let for_chain = peer.and_then(|peer| {
+            let mut chain: Vec<&str> = headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter(|_| trusted)
+                .filter_map(|v| v.to_str().ok())
+                .collect();
+            let peer = peer.to_string();
+            chain.push(&peer);
+            HeaderValue::from_str(&chain.join(", ")).ok()
+        });
+        Self {
+            for_chain,
+            proto: reported("x-forwarded-proto").unwrap_or(HeaderValue::from_static("http")),
+            host: reported("x-forwarded-host").or_else(|| headers.get(header::HOST).cloned()),
+        }
+    }
+
+    /// Replace whatever the client sent with these values
+    pub(crate) fn apply(self, headers: &mut HeaderMap) {
+        headers.remove("x-forwarded-for");
+        headers.remove("x-forwarded-host");
+        if let Some(chain) = self.for_chain {
+            headers.insert("x-forwarded-for", chain);
+        }
+        if let Some(host) = self.host {
+            headers.insert("x-forwarded-host", host);
+        }
+        headers.insert("x-forwarded-proto", self.proto);
+    }
+}
+
+/// Token from an `Authorization: Bearer ...` header, if present
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+}
+
+pub async fn validate_jwt(
+    state: &AppState,
+    token: &str,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let claims = validate_cached(state, token).await?;
+
+    // Checked on every request, cached or not, so revocation is immediate
+    if state.token_denylist {
+        if let Some(jti) = &claims.jti {
+            match is_revoked(&state.redis_client, jti).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    tracing::warn!("Rejecting revoked token {} for {}", jti, claims.sub);
+                    return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into());
+                }
+                // Fail closed: a revoked token must never get through
+                Err(e) => {
+                    tracing::error!("Token denylist unavailable, rejecting token: {}", e);
+                    return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into());
+                }
+            }
+        }
+    }
+    Ok(claims)
+}
+
+/// Add a token ID to the denylist until `ttl` has passed (the token's
+/// remaining lifetime)
+pub async fn revoke_token(
+    client: &redis::Client,
+    jti: &str,
+    ttl: Duration,
+) -> redis::RedisResult<()> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    redis::cmd("SET")
+        .arg(format!("{}:{}", REVOKED_TOKENS_PREFIX, jti))
+        .arg(1)
+        .arg("EX")
+        .arg(ttl.as_secs().max(1))
+        .query_async(&mut conn)
+        .await
+}
+
+async fn is_revoked(client: &redis::Client, jti: &str) -> redis::RedisResult<bool> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    redis::cmd("EXISTS")
+        .arg(format!("{}:{}", REVOKED_TOKENS_PREFIX, jti))
+        .query_async(&mut conn)
+        .await
+}
+
+/// Claims from the token cache, or verified (and cached) on a miss
+async fn validate_cached(
+    state: &AppState,
+    token: &str,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let Some(token_cache) = &state.token_cache else {
+        return validate_uncached(state, token).await;
+    };
+
+    let key = hex::encode(Sha256::digest(token.as_bytes()));
+    if let Some(claims) = token_cache.get(&key).await {
+        // Never outlive the token itself, even within the cache TTL
+        if claims.exp > introspection::now() {
+            return Ok(claims);
+        }
+        token_cache.invalidate(&key).await;
+    }
+
+    let claims = validate_uncached(state, token).await?;
+    token_cache.insert(key, claims.clone()).await;
+    Ok(claims)
+}
+
+/// Verify a token and check its claims, bypassing the token cache
+async fn validate_uncached(
+    state: &AppState,
+    token: &str,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let claims = verify_token(state, token).await?;
+
+    // `sub` becomes X-User-ID, so it must be a usable header value
+    if claims.sub.is_empty() || HeaderValue::from_str(&claims.sub).is_err() {
+        tracing::warn!("Rejecting token with unusable sub {:?}", claims.sub);
+        return Err(jsonwebtoken::errors::ErrorKind::InvalidSubject.into());
+    }
+    Ok(claims)
+}
+
+/// Verify the signature and standard claims (or introspect an opaque token)
+async fn verify_token(
+    state: &AppState,
+    token: &str,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let header = match jsonwebtoken::decode_header(token) {
+        Ok(header) => header,
+        // Not a JWT: an opaque token only the IdP can vouch for
+        Err(e) => {
+            return match &state.introspection {
+                Some(config) => introspection::introspect(state, config, token).await,
+                None => Err(e),
+            }
+        }
+    };
+
+    // Reject unexpected algorithms up front to prevent algorithm confusion
+    if !state.jwt_algorithms.contains(&header.alg) {
+        return Err(jsonwebtoken::errors::ErrorKind::InvalidAlgorithm.into());
+    }
+
+    let kid = header
+        .kid
+        .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?;
+
+    // Pick the JWKS by the (not yet verified) `iss`; it's checked again below
+    let iss = unverified_issuer(token, header.alg)?;
+    let issuer = state.jwt_issuers.get(&iss).ok_or_else(|| {
+        tracing::warn!("Token from unknown issuer: {}", iss);
+        jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidIssuer)
+    })?;
+
+    // Concurrent misses for the same kid share a single refresh
+    let decoding_key = state
+        .jwks_cache
+        .try_get_with(
+            (iss.clone(), kid.clone()),
+            resolve_jwks_key(state, &iss, issuer, &kid),
+        )
+        .await
+        .map_err(|e| {
+            tracing::warn!("No signing key for kid {} from {}: {}", kid, iss, e);
+            jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken)
+        })?;
+
+    // The key family must also match `alg`, which jsonwebtoken enforces
+    let mut validation = Validation::new(header.alg);
+    validation.validate_exp = true;
+    validation.validate_nbf = true;
+    validation.leeway = state.jwt_leeway_secs;
+    validation.set_issuer(&[&iss]);
+
+    // Only enforce the audience when one is configured, so existing
+    // deployments without JWT_AUDIENCE keep working
+    match &state.jwt_audience {
+        Some(audience) => {
+            validation.set_audience(audience);
+            validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        }
+        None => {
+            validation.validate_aud = false;
+            validation.set_required_spec_claims(&["exp", "iss"]);
+        }
+    }
+
+    jsonwebtoken::decode::<Claims>(token, &decoding_key, &validation).map(|data| data.claims)
+}
+
+/// Read `iss` without verifying the signature, only to choose a JWKS
+fn unverified_issuer(token: &str, alg: Algorithm) -> Result<String, jsonwebtoken::errors::Error> {
+    #[derive(Deserialize)]
+    struct UnverifiedClaims {
+        iss: Option<String>,
+    }
+
+    let mut peek = Validation::new(alg);
+    peek.insecure_disable_signature_validation();
+    peek.validate_exp = false;
+    peek.validate_aud = false;
+    peek.required_spec_claims.clear();
+
+    jsonwebtoken::decode::<UnverifiedClaims>(token, &DecodingKey::from_secret(&[]), &peek)?
+        .claims
+        .iss
+        .ok_or_else(|| jsonwebtoken::errors::ErrorKind::InvalidIssuer.into())
+}
+
+/// Find the key for an uncached `kid`, refreshing the issuer's JWKS first
+///
+/// An unknown kid usually means the IdP rotated keys, so it forces one
+/// refresh (throttled). If the refresh fails, the last good key set is used.
+async fn resolve_jwks_key(
+    state: &AppState,
+    iss: &str,
+    issuer: &JwtIssuer,
+    kid: &str,
+) -> Result<DecodingKey, String> {
+    if !issuer.static_keys && issuer.try_begin_refresh() {
+        match fetch_jwks_with_retry(state, &issuer.jwks_url).await {
+            Ok(keys) => {
+                for (other_kid, key) in keys.iter().filter(|(k, _)| *k != kid) {
+                    state
+                        .jwks_cache
+                        .insert((iss.to_string(), other_kid.clone()), key.clone())
+                        .await;
+                }
+                issuer.last_good.store(Arc::new(keys));
+            }
+            Err(e) => tracing::warn!("JWKS refresh failed, using last known keys: {}", e),
+        }
+    }
+
+    issuer
+        .last_good
+        .load()
+        .get(kid)
+        .cloned()
+        .ok_or_else(|| "kid not found in JWKS".to_string())
+}
+
+/// Fetch and decode every usable key from `jwks_url`
+pub(crate) async fn fetch_jwks(
+    client: &HttpClient,
+    jwks_url: &str,
+) -> Result<HashMap<String, DecodingKey>, String> {
+    let jwks: Jwks = client
+        .get(jwks_url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(decode_jwks(jwks))
+}
+
+/// `fetch_jwks`, retried with jittered backoff so a blip during a refresh
+/// doesn't lock out tokens signed with a newly rotated key
+async fn fetch_jwks_with_retry(
+    state: &AppState,
+    jwks_url: &str,
+) -> Result<HashMap<String, DecodingKey>, String> {
+    let max_attempts = state.jwks_retry.max_attempts.max(1);
+    let mut attempt = 0;
+    loop {
+        match fetch_jwks(&state.http_client, jwks_url).await {
+            Err(e) if attempt + 1 < max_attempts => {
+                tracing::warn!(
+                    "JWKS fetch from {} failed (attempt {}/{}), retrying: {}",
+                    jwks_url,
+                    attempt + 1,
+                    max_attempts,
+                    e
+                );
+                tokio::time::sleep(state.jwks_retry.backoff(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Where Zitadel serves the JWKS under its issuer URL
+pub const ZITADEL_JWKS_PATH: &str = "/oauth/v2/keys";
+
+/// Where to fetch an issuer's JWKS: `explicit` if given, else the
+/// `jwks_uri` from its OpenID discovery document, else Zitadel's path
+pub async fn resolve_jwks_url(client: &HttpClient, issuer: &str, explicit: Option<&str>) -> String {
+    if let Some(url) = explicit {
+        return url.to_string();
+    }
+    match discover_jwks_url(client, issuer).await {
+        Ok(url) => url,
+        Err(e) => {
+            tracing::warn!(
+                "OpenID discovery failed for {}, using Zitadel's JWKS path: {}",
+                issuer,
+                e
+            );
+            format!("{}{}", issuer, ZITADEL_JWKS_PATH)
+        }
+    }
+}
+
+/// Read `jwks_uri` from the issuer's `/.well-known/openid-configuration`
+pub async fn discover_jwks_url(client: &HttpClient, issuer: &str) -> Result<String, String> {
+    #[derive(Deserialize)]
+    struct Discovery {
+        jwks_uri: String,
+    }
+
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    );
+    let discovery: Discovery = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(discovery.jwks_uri)
+}
+
+/// Read a JWKS document from disk, for running without the IdP
+pub fn load_jwks_file(path: &str) -> Result<HashMap<String, DecodingKey>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let jwks: Jwks = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(decode_jwks(jwks))
+}
+
+/// Every usable key in a JWKS by `kid`; unusable ones are logged and skipped
+fn decode_jwks(jwks: Jwks) -> HashMap<String, DecodingKey> {
+    let mut keys = HashMap::new();
+    for jwk in jwks.keys {
+        match decoding_key_from_jwk(&jwk) {
+            Ok(key) => {
+                keys.insert(jwk.kid, key);
+            }
+            Err(e) => tracing::warn!("Skipping unusable JWK {}: {}", jwk.kid, e),
+        }
+    }
+    keys
+}
+
+/// Build a decoding key for the JWK's key type (RSA, EC or OKP/EdDSA)
+///
+/// Raw key components are preferred; keys published only as an `x5c`
+/// chain use the public key of the leaf certificate.
+fn decoding_key_from_jwk(jwk: &Jwk) -> Result<DecodingKey, jsonwebtoken::errors::Error> {
+    let missing =
+        || jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat);
+    let leaf_cert = || x5c_leaf_pem(jwk).ok_or_else(missing);
+
+    match jwk.kty.as_deref().unwrap_or("RSA") {
+        "RSA" => match (jwk.n.as_deref(), jwk.e.as_deref()) {
+            (Some(n), Some(e)) => DecodingKey::from_rsa_components(n, e),
+            _ => DecodingKey::from_rsa_pem(leaf_cert()?.as_bytes()),
+        },
+        "EC" => match (jwk.x.as_deref(), jwk.y.as_deref()) {
+            (Some(x), Some(y)) => DecodingKey::from_ec_components(x, y),
+            _ => DecodingKey::from_ec_pem(leaf_cert()?.as_bytes()),
+        },
+        "OKP" => match jwk.x.as_deref() {
+            Some(x) => DecodingKey::from_ed_components(x),
+            None => DecodingKey::from_ed_pem(leaf_cert()?.as_bytes()),
+        },
+        other => {
+            tracing::warn!("Unsupported JWK key type: {}", other);
+            Err(missing())
+        }
+    }
+}
+
+/// The JWK's leaf `x5c` certificate as PEM. `x5c` entries are standard
+/// base64 DER, which is exactly a PEM body.
+fn x5c_leaf_pem(jwk: &Jwk) -> Option<String> {
+    let leaf = jwk.x5c.as_ref()?.first()?;
+    Some(format!(
+        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+        leaf
+    ))
+}
+
+/// Outcome of a rate limit check, used to build the `X-RateLimit-*` headers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_secs: u64, // Seconds until the window resets
+}
+
+impl RateLimitStatus {
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("x-ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("x-ratelimit-reset", HeaderValue::from(self.reset_secs));
+    }
+}
+
+impl IntoResponse for RateLimitStatus {
+    /// 429 response carrying the rate limit headers and `Retry-After`
+    fn into_response(self) -> Response {
+        let mut response = StatusCode::TOO_MANY_REQUESTS.into_response();
+        self.apply_headers(response.headers_mut());
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(self.reset_secs));
+        response
+    }
+}
+
+/// How the rate limiter behaves when Redis can't be reached
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RateLimitFailMode {
+    /// Let requests through unthrottled
+    Open,
+    /// Reject requests with 503 until Redis is back
+    #[default]
+    Closed,
+}
+
+impl std::str::FromStr for RateLimitFailMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "open" => Ok(Self::Open),
+            "closed" => Ok(Self::Closed),
+            other => Err(format!("Invalid rate limit fail mode: {}", other)),
+        }
+    }
+}
+
+/// What happens to requests whose path matches no access rule
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DefaultPolicy {
+    /// Reject with 403 until a rule is added
+    #[default]
+    Deny,
+    /// Proxy upstream without authz, forwarding identity only for a valid token
+    Proxy,
+}
+
+impl std::str::FromStr for DefaultPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "deny" => Ok(Self::Deny),
+            "proxy" => Ok(Self::Proxy),
+            other => Err(format!("Invalid default policy: {}", other)),
+        }
+    }
+}
+
+/// How a request is rejected when OpenFGA reports a model error for its
+/// check. 403 matches what a denial looks like to clients; 502 makes the
+/// drift visible to them too.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ModelErrorStatus {
+    #[default]
+    Forbidden,
+    BadGateway,
+}
+
+impl ModelErrorStatus {
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::BadGateway => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+impl std::str::FromStr for ModelErrorStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "403" => Ok(Self::Forbidden),
+            "502" => Ok(Self::BadGateway),
+            other => Err(format!("Invalid model error status: {}", other)),
+        }
+    }
+}
+
+/// How a path with no access rule is rejected under `DefaultPolicy::Deny`.
+/// Genuine authz failures are always 403.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnmatchedStatus {
+    /// 403, the same as a denied request
+    #[default]
+    Forbidden,
+    /// 404, so probing clients can't tell which paths the gateway governs
+    NotFound,
+}
+
+impl UnmatchedStatus {
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::NotFound => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+impl std::str::FromStr for UnmatchedStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "403" => Ok(Self::Forbidden),
+            "404" => Ok(Self::NotFound),
+            other => Err(format!("Invalid unmatched status: {}", other)),
+        }
+    }
+}
+
+/// Which client request headers are forwarded upstream
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ForwardHeaderMode {
+    /// Everything except hop-by-hop headers
+    #[default]
+    All,
+    /// Only `forward_headers`, plus the headers the gateway injects
+    Allowlist,
+}
+
+impl std::str::FromStr for ForwardHeaderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "all" => Ok(Self::All),
+            "allowlist" => Ok(Self::Allowlist),
+            other => Err(format!("Invalid forward header mode: {}", other)),
+        }
+    }
+}
+
+/// Where the tenant that namespaces OpenFGA objects comes from, written
+/// as `org_id` or `header:<name>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TenantSource {
+    /// The token's `org_id` claim
+    OrgClaim,
+    /// A request header, e.g. `X-Tenant-Id`
+    Header(header::HeaderName),
+}
+
+impl std::str::FromStr for TenantSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("org_id") {
+            return Ok(Self::OrgClaim);
+        }
+        match s.split_once(':') {
+            Some((kind, name)) if kind.eq_ignore_ascii_case("header") => name
+                .trim()
+                .parse()
+                .map(Self::Header)
+                .map_err(|_| format!("Invalid tenant header: {}", name)),
+            _ => Err(format!("Invalid tenant source: {}", s)),
+        }
+    }
+}
+
+impl TenantSource {
+    /// This request's tenant, or None if it has no usable one. Tenants
+    /// can't contain characters that would change the object's shape.
+    pub fn resolve<'a>(&self, claims: &'a Claims, headers: &'a HeaderMap) -> Option<&'a str> {
+        let tenant = match self {
+            Self::OrgClaim => claims.org_id.as_deref(),
+            Self::Header(name) => headers.get(name).and_then(|v| v.to_str().ok()),
+        }?;
+        let valid = !tenant.is_empty()
+            && !tenant.contains(|c: char| matches!(c, '#' | ':' | '/') || c.is_whitespace());
+        valid.then_some(tenant)
+    }
+}
+
+/// `object` as checked within `tenant`, unchanged without tenant scoping
+fn scoped_object(object: String, tenant: Option<&str>) -> String {
+    match tenant {
+        Some(tenant) => tenant_object(&object, tenant),
+        None => object,
+    }
+}
+
+/// Cache key for `user_id`'s decision on a request to `route_config` that
+/// resolved to `object` (already tenant-scoped) and `action`. Shared by the
+/// middleware and `prefetch_permissions`, so warmed entries get hit.
+fn decision_key(
+    user_id: &str,
+    route_config: &RouteConfig,
+    object: &str,
+    action: Option<&str>,
+    tenant: Option<&str>,
+    context: Option<&CheckContext>,
+) -> AuthzCacheKey {
+    AuthzCacheKey::new(user_id, &route_config.feature, context)
+        .with_object(object)
+        .with_relation(action)
+        .with_tenant(tenant)
+}
+
+/// Namespace an OpenFGA object by tenant: `feature:billing` becomes
+/// `feature:acme/billing`
+pub fn tenant_object(object: &str, tenant: &str) -> String {
+    match object.split_once(':') {
+        Some((object_type, id)) => format!("{}:{}/{}", object_type, tenant, id),
+        None => format!("{}/{}", tenant, object),
     }
+}
 
-    // 2. Extract token
-    let auth_header = req
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .and_then(|h| h.strip_prefix("Bearer "));
+/// Whose budget a request counts against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitSubject<'a> {
+    /// An authenticated user, budgeted per feature
+    User(&'a str),
+    /// An anonymous caller on a public route, budgeted across all of them
+    Ip(IpAddr),
+}
 
-    let token = match auth_header {
-        Some(t) => t,
-        None => {
-            tracing::warn!("Missing or invalid Authorization header");
-            return Err(StatusCode::UNAUTHORIZED);
+impl RateLimitSubject<'_> {
+    fn key(&self, feature: &str) -> String {
+        match self {
+            Self::User(user_id) => format!("rate_limit:{}:{}", user_id, feature),
+            Self::Ip(ip) => format!("rate_limit:ip:{}", ip),
         }
-    };
+    }
+}
 
-    // 3. Validate JWT
-    let claims = match validate_jwt(&state, token).await {
-        Ok(c) => c,
-        Err(e) => {
-            tracing::warn!("JWT validation failed: {:?}", e);
-            return Err(StatusCode::UNAUTHORIZED);
+impl std::fmt::Display for RateLimitSubject<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::User(user_id) => write!(f, "user {}", user_id),
+            Self::Ip(ip) => write!(f, "IP {}", ip),
         }
-    };
+    }
+}
 
-    let user_id = &claims.sub;
+/// The limiter itself failed, as opposed to the limit being exceeded
+#[derive(Debug)]
+pub struct RateLimiterUnavailable(pub redis::RedisError);
 
-    // 4. Rate Limiting (Redis-based, 100 req/min per user)
-    if let Err(e) = check_rate_limit(&state, user_id).await {
-        tracing::warn!("Rate limit exceeded for user {}: {:?}", user_id, e);
-        return Err(StatusCode::TOO_MANY_REQUESTS);
+impl std::fmt::Display for RateLimiterUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Rate limiter unavailable: {}", self.0)
     }
+}
 
-    // 5. Caching & OpenFGA Check
-    let cache_key = (user_id.clone(), route_config.feature.clone());
-    let cached_result = state.cache.get(&cache_key).await;
-
-    let authorized = match cached_result {
-        Some(result) => {
-            tracing::debug!("Cache hit for {:?}", cache_key);
-            result
-        }
-        None => {
-            tracing::debug!("Cache miss for {:?}, checking OpenFGA", cache_key);
-            let allowed = check_openfga_permission(
-                &state.http_client,
-                &state.fga_client,
-                user_id,
-                &route_config.feature,
-                route_config.action.as_deref(), // NEW: Pass action
-            )
-            .await
-            .unwrap_or(false);
+impl std::error::Error for RateLimiterUnavailable {}
 
-            state.cache.insert(cache_key, allowed).await;
-            allowed
-        }
-    };
+/// Sliding-window log limiter, run atomically inside Redis.
+///
+/// Each allowed request is a member of a sorted set scored by its timestamp
+/// (ms). Entries older than the window are trimmed before counting, so a
+/// client can never exceed `limit` requests in any `window`-long interval.
+///
+/// KEYS[1] = counter key
+/// ARGV = now_ms, window_ms, limit, unique member
+/// Returns {allowed (0/1), count, ms until the oldest entry expires}
+const SLIDING_WINDOW_SCRIPT: &str = r#"
+local key = KEYS[1]
+local now = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
 
-    if !authorized {
-        tracing::warn!(
-            "User {} not authorized for feature {}",
-            user_id,
-            route_config.feature
-        );
-        return Err(StatusCode::FORBIDDEN);
-    }
+redis.call('ZREMRANGEBYSCORE', key, '-inf', now - window)
+local count = redis.call('ZCARD', key)
+local allowed = 0
+if count < limit then
+    redis.call('ZADD', key, now, ARGV[4])
+    count = count + 1
+    allowed = 1
+end
+redis.call('PEXPIRE', key, window)
 
-    // 6. Inject User ID in header for upstream
-    req.headers_mut()
-        .insert("X-User-ID", user_id.parse().unwrap());
+local reset = window
+local oldest = redis.call('ZRANGE', key, 0, 0, 'WITHSCORES')
+if oldest[2] then
+    reset = tonumber(oldest[2]) + window - now
+end
+return {allowed, count, reset}
+"#;
 
-    Ok(next.run(req).await)
-}
+static SLIDING_WINDOW: LazyLock<redis::Script> =
+    LazyLock::new(|| redis::Script::new(SLIDING_WINDOW_SCRIPT));
 
-async fn validate_jwt(
+/// Apply the rate limit for `route_config`, honoring the fail mode when Redis
+/// is unreachable. Returns the status to advertise, or the rejection to send.
+async fn enforce_rate_limit(
     state: &AppState,
-    token: &str,
-) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let header = jsonwebtoken::decode_header(token)?;
-    let kid = header
-        .kid
-        .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?;
-
-    let decoding_key = match state.jwks_cache.get(&kid).await {
-        Some(key) => key,
-        None => {
-            let jwks: Jwks = state
-                .http_client
-                .get(&state.jwks_url)
-                .send()
-                .await
-                .map_err(|_| jsonwebtoken::errors::ErrorKind::InvalidToken)?
-                .json()
-                .await
-                .map_err(|_| jsonwebtoken::errors::ErrorKind::InvalidToken)?;
-
-            let jwk = jwks
-                .keys
-                .into_iter()
-                .find(|k| k.kid == kid)
-                .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?;
-
-            let key = DecodingKey::from_rsa_components(&jwk.n, &jwk.e)?;
-            state.jwks_cache.insert(kid.clone(), key.clone()).await;
-            key
+    subject: &RateLimitSubject<'_>,
+    route_config: &RouteConfig,
+) -> Result<Option<RateLimitStatus>, Response> {
+    match check_rate_limit_for(state, subject, route_config).await {
+        Ok(status) if status.allowed => Ok(Some(status)),
+        Ok(status) => {
+            tracing::warn!(
+                "Rate limit exceeded for {} on feature {}",
+                subject,
+                route_config.feature
+            );
+            metrics::counter!(telemetry::RATE_LIMIT_REJECTIONS_TOTAL).increment(1);
+            Err(status.into_response())
         }
-    };
-
-    let mut validation = Validation::new(Algorithm::RS256);
-    validation.validate_exp = true;
+        Err(e) => match state.rate_limit_fail_mode {
+            RateLimitFailMode::Open => {
+                tracing::warn!("{}, allowing request for {} (fail open)", e, subject);
+                Ok(None)
+            }
+            RateLimitFailMode::Closed => {
+                tracing::error!("{}, rejecting request for {} (fail closed)", e, subject);
+                Err(StatusCode::SERVICE_UNAVAILABLE.into_response())
+            }
+        },
+    }
+}
 
-    jsonwebtoken::decode::<Claims>(token, &decoding_key, &validation).map(|data| data.claims)
+/// Decision label for a rejection from `enforce_rate_limit`
+fn rate_limit_result(response: &Response) -> &'static str {
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        "rate_limited"
+    } else {
+        "unavailable"
+    }
 }
 
-async fn check_rate_limit(
+pub async fn check_rate_limit(
     state: &AppState,
     user_id: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+    route_config: &RouteConfig,
+) -> Result<RateLimitStatus, RateLimiterUnavailable> {
+    check_rate_limit_for(state, &RateLimitSubject::User(user_id), route_config).await
+}
+
+pub async fn check_rate_limit_for(
+    state: &AppState,
+    subject: &RateLimitSubject<'_>,
+    route_config: &RouteConfig,
+) -> Result<RateLimitStatus, RateLimiterUnavailable> {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
     let mut conn = state
         .redis_client
         .get_multiplexed_async_connection()
-        .await?;
-    let key = format!("rate_limit:{}", user_id);
-
-    let current: i32 = conn.get(&key).await.unwrap_or(0);
+        .await
+        .map_err(RateLimiterUnavailable)?;
+    // Users get a budget per feature so cheap and expensive endpoints don't share one
+    let key = subject.key(&route_config.feature);
+    let (limit, window_secs) = route_config.effective_rate_limit();
 
-    if current >= 100 {
-        return Err("Rate limit exceeded".into());
-    }
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    // Members must be unique or concurrent requests in the same ms collapse into one
+    let member = format!(
+        "{}-{}-{}",
+        now_ms,
+        std::process::id(),
+        SEQUENCE.fetch_add(1, Ordering::Relaxed)
+    );
 
-    redis::pipe()
-        .atomic()
-        .incr(&key, 1)
-        .expire(&key, 60)
-        .query_async::<()>(&mut conn)
-        .await?;
+    let (allowed, count, reset_ms): (u8, u32, u64) = SLIDING_WINDOW
+        .key(&key)
+        .arg(now_ms)
+        .arg(window_secs * 1000)
+        .arg(limit)
+        .arg(member)
+        .invoke_async(&mut conn)
+        .await
+        .map_err(RateLimiterUnavailable)?;
 
-    Ok(())
+    Ok(RateLimitStatus {
+        allowed: allowed == 1,
+        limit,
+        remaining: limit.saturating_sub(count),
+        reset_secs: reset_ms.div_ceil(1000),
+    })
 }
 
-async fn check_openfga_permission(
+pub async fn check_openfga_permission(
     client: &HttpClient,
     fga_client: &OpenFgaClient,
     user_id: &str,
-    feature: &str,
+    object: &str,         // Full OpenFGA object, e.g. feature:reports or document:42
     action: Option<&str>, // NEW: action parameter
+    context: Option<&CheckContext>,
+    consistency: Option<Consistency>, // None = the client's default
 ) -> Result<bool, Box<dyn std::error::Error>> {
-    let check_url = format!("{}/stores/{}/check", fga_client.url, fga_client.store_id);
-
-    // Use action as relation if provided, default to "viewer" for backward compatibility
-    let relation = action.unwrap_or("viewer");
+    let allowed = try_check_openfga_permission(
+        client,
+        fga_client,
+        user_id,
+        object,
+        action,
+        context,
+        consistency,
+    )
+    .await?;
+    Ok(allowed.unwrap_or(false))
+}
 
-    let request_body = serde_json::json!({
-        "tuple_key": {
-            "user": format!("user:{}", user_id),
-            "relation": relation,  // Use action/relation
-            "object": format!("feature:{}", feature),
-        }
-    });
+/// Like `check_openfga_permission`, but `None` when OpenFGA gave no
+/// answer: the check failed, or the breaker is open and isn't configured
+/// to allow. Model errors (see `OpenFgaError::is_model_error`) are returned
+/// as errors, since retrying or a stale grant won't fix them.
+pub async fn try_check_openfga_permission(
+    client: &HttpClient,
+    fga_client: &OpenFgaClient,
+    user_id: &str,
+    object: &str,
+    action: Option<&str>,
+    context: Option<&CheckContext>,
+    consistency: Option<Consistency>,
+) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+    let relation = fga_client.relation_for(action)?;
+    let tuple = TupleKey::new(format!("user:{}", user_id), relation, object);
 
-    // Send request and handle errors gracefully
-    match client.post(&check_url).json(&request_body).send().await {
-        Ok(response) if response.status().is_success() => {
-            let result: serde_json::Value = response.json().await?;
-            Ok(result["allowed"].as_bool().unwrap_or(false))
+    let result = fga_client.check(client, &tuple, context, consistency).await;
+    if let Err(e) = &result {
+        metrics::counter!(telemetry::OPENFGA_CHECK_ERRORS_TOTAL, "kind" => e.kind()).increment(1);
+    }
+    match result {
+        Ok(allowed) => Ok(Some(allowed)),
+        Err(OpenFgaError::CircuitOpen) => {
+            tracing::debug!("OpenFGA circuit open, skipping check for {}", object);
+            Ok(fga_client.breaker_open_decision.then_some(true))
         }
-        Ok(response) => {
-            let status = response.status(); // Capture before consuming
-            let error = response.text().await.unwrap_or_default();
-            tracing::warn!("OpenFGA check failed with status {}: {}", status, error);
-            Ok(false)
+        Err(e) if e.is_model_error() => {
+            tracing::error!(
+                "OpenFGA rejected check for {} (do the model and access rules match?): {}",
+                object,
+                e
+            );
+            Err(e.into())
         }
         Err(e) => {
-            tracing::warn!("OpenFGA request failed: {}", e);
-            Ok(false)
+            tracing::warn!("OpenFGA check failed: {}", e);
+            Ok(None)
         }
     }
 }
 
-pub fn create_router(state: AppState, allowed_origins: Vec<header::HeaderValue>) -> axum::Router {
-    let cors = CorsLayer::new()
-        .allow_origin(AllowOrigin::list(allowed_origins))
-        .allow_methods([
+/// Whether a last-known grant for `key` may be served during an outage
+fn has_stale_grant(state: &AppState, key: &AuthzCacheKey) -> bool {
+    state
+        .stale_grants
+        .as_ref()
+        .is_some_and(|stale_grants| stale_grants.contains_key(key))
+}
+
+/// A `(feature, action)` pair as used by the batch check
+pub type FeatureAction = (String, Option<String>);
+
+/// Check several `(feature, action)` pairs for one user in a single
+/// `/batch-check` round-trip.
+///
+/// Pairs whose individual check errored are left out of the result, so
+/// callers don't mistake an OpenFGA error for a deny.
+pub async fn check_openfga_permissions_batch(
+    client: &HttpClient,
+    fga_client: &OpenFgaClient,
+    user_id: &str,
+    checks: &[FeatureAction],
+) -> Result<HashMap<FeatureAction, bool>, Box<dyn std::error::Error>> {
+    if checks.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // Unmappable actions are left out, like errored checks
+    let (checked, tuples): (Vec<&FeatureAction>, Vec<TupleKey>) = checks
+        .iter()
+        .filter_map(|check @ (feature, action)| {
+            let relation = fga_client
+                .relation_for(action.as_deref())
+                .map_err(|e| tracing::warn!("Skipping batch check for {}: {}", feature, e))
+                .ok()?;
+            let tuple = TupleKey::new(
+                format!("user:{}", user_id),
+                relation,
+                format!("feature:{}", feature),
+            );
+            Some((check, tuple))
+        })
+        .unzip();
+    if tuples.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let outcomes = fga_client.batch_check(client, &tuples).await?;
+    Ok(checked
+        .into_iter()
+        .zip(outcomes)
+        .filter_map(|(check, allowed)| Some((check.clone(), allowed?)))
+        .collect())
+}
+
+/// Warm the authz cache for a user ahead of requests they're about to make,
+/// given as `(method, path)`, in one `/batch-check` round-trip.
+///
+/// Entries are keyed exactly as `auth_middleware` keys them (the rule's
+/// object, the method's action, `tenant`), for requests without check
+/// context headers. Requests without a governing rule, public routes and
+/// routes that read their action from the body are skipped.
+pub async fn prefetch_permissions(
+    state: &AppState,
+    user_id: &str,
+    tenant: Option<&str>,
+    requests: &[(Method, String)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let router = state.router.load_full();
+    let mut keys = Vec::new();
+    let mut tuples = Vec::new();
+    for (method, path) in requests {
+        let Some((route_config, params)) = router
+            .at(path)
+            .ok()
+            .and_then(|matched| Some((matched.value.get(method)?, matched.params)))
+        else {
+            tracing::debug!("No access rule for {} {}, not prefetching", method, path);
+            continue;
+        };
+        if route_config.feature == "public_access" || route_config.action_from_body.is_some() {
+            continue;
+        }
+
+        let object = match route_config.resolve_object(&params) {
+            Ok(object) => scoped_object(object, tenant),
+            Err(e) => {
+                tracing::warn!("Skipping prefetch for {}: {}", path, e);
+                continue;
+            }
+        };
+        let action = route_config.action_for(method, &state.method_actions);
+        let relation = match state.fga_client.relation_for(action) {
+            Ok(relation) => relation,
+            Err(e) => {
+                tracing::warn!("Skipping prefetch for {}: {}", path, e);
+                continue;
+            }
+        };
+        tuples.push(TupleKey::new(
+            format!("user:{}", user_id),
+            relation,
+            object.as_str(),
+        ));
+        let grant_ttl = route_config.cache_ttl_secs.map(Duration::from_secs);
+        let key = decision_key(user_id, route_config, &object, action, tenant, None);
+        keys.push((key, grant_ttl));
+    }
+    if tuples.is_empty() {
+        return Ok(());
+    }
+
+    // Errored checks aren't decisions, so they're left uncached
+    let outcomes = state
+        .fga_client
+        .batch_check(&state.http_client, &tuples)
+        .await?;
+    for ((key, grant_ttl), allowed) in keys.into_iter().zip(outcomes) {
+        if let Some(allowed) = allowed {
+            cache_decision(state, key, allowed, grant_ttl).await;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct MeFeaturesResponse {
+    pub user_id: String,
+    pub features: Vec<String>, // Names of accessible `feature` objects
+    pub objects: Vec<String>,  // Every accessible object across `listable_objects`
+}
+
+/// GET /me/features - what the caller can access, so frontends can render
+/// navigation without a check per feature
+pub async fn me_features(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let token = bearer_token(&headers).ok_or_else(|| StatusCode::UNAUTHORIZED.into_response())?;
+    let claims = validate_jwt(&state, token).await.map_err(|e| {
+        tracing::warn!("JWT validation failed: {:?}", e);
+        StatusCode::UNAUTHORIZED.into_response()
+    })?;
+    let user_id = claims.sub;
+
+    // Budgeted like a feature of its own
+    let limit_scope = RouteConfig {
+        feature: "me_features".into(),
+        ..Default::default()
+    };
+    let subject = RateLimitSubject::User(&user_id);
+    let rate_limit = enforce_rate_limit(&state, &subject, &limit_scope).await?;
+
+    let mut objects = Vec::new();
+    for (object_type, relation) in &state.listable_objects {
+        let key = (user_id.clone(), object_type.clone(), relation.clone());
+        let listed = match state.list_objects_cache.get(&key).await {
+            Some(listed) => listed,
+            None => {
+                let listed = state
+                    .fga_client
+                    .list_objects(
+                        &state.http_client,
+                        &format!("user:{}", user_id),
+                        relation,
+                        object_type,
+                    )
+                    .await
+                    .map_err(|e| {
+                        tracing::error!(
+                            "Listing {} objects for {} failed: {}",
+                            object_type,
+                            user_id,
+                            e
+                        );
+                        StatusCode::SERVICE_UNAVAILABLE.into_response()
+                    })?;
+                let listed = Arc::new(listed);
+                state.list_objects_cache.insert(key, listed.clone()).await;
+                listed
+            }
+        };
+        objects.extend(listed.iter().cloned());
+    }
+    objects.sort();
+    objects.dedup();
+
+    let features = objects
+        .iter()
+        .filter_map(|object| object.strip_prefix("feature:"))
+        .map(str::to_owned)
+        .collect();
+
+    let mut response = axum::Json(MeFeaturesResponse {
+        user_id,
+        features,
+        objects,
+    })
+    .into_response();
+    if let Some(rate_limit) = rate_limit {
+        rate_limit.apply_headers(response.headers_mut());
+    }
+    Ok(response)
+}
+
+/// Origins, methods and request headers the CORS layer allows
+#[derive(Clone, Debug)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<header::HeaderValue>,
+    /// Glob patterns such as `https://*.preview.example.com`; empty keeps the
+    /// strict exact-match list. A `*` stands for one DNS label.
+    pub allowed_origin_patterns: Vec<String>,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<header::HeaderName>,
+}
+
+impl CorsConfig {
+    /// Default method and header sets for the given origins
+    pub fn new(allowed_origins: Vec<header::HeaderValue>) -> Self {
+        Self {
+            allowed_origins,
+            allowed_origin_patterns: Vec::new(),
+            allowed_methods: Self::default_methods(),
+            allowed_headers: Self::default_headers(),
+        }
+    }
+
+    pub fn default_methods() -> Vec<Method> {
+        vec![
             Method::GET,
             Method::POST,
             Method::PUT,
             Method::DELETE,
             Method::OPTIONS,
-        ])
-        .allow_headers([
+        ]
+    }
+
+    pub fn default_headers() -> Vec<header::HeaderName> {
+        vec![
             header::AUTHORIZATION,
             header::CONTENT_TYPE,
-            header::HeaderName::from_static("x-user-id"),
-            header::HeaderName::from_static("x-gateway-secret"),
-        ])
+            header::HeaderName::from_static(USER_ID_HEADER),
+            header::HeaderName::from_static(GATEWAY_SECRET_HEADER),
+            header::HeaderName::from_static(REQUEST_ID_HEADER),
+        ]
+    }
+}
+
+/// Whether `origin` matches a CORS glob pattern. `*` matches a non-empty run
+/// of characters without `.` or `/`, so it can't span into another domain.
+pub fn origin_matches(pattern: &str, origin: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == origin,
+        Some((prefix, rest)) => {
+            let Some(origin) = origin.strip_prefix(prefix) else {
+                return false;
+            };
+            origin
+                .char_indices()
+                .skip(1)
+                .map(|(i, _)| i)
+                .chain(std::iter::once(origin.len()))
+                .take_while(|&i| !origin[..i].contains(['.', '/']))
+                .any(|i| origin_matches(rest, &origin[i..]))
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    /// No cross-origin access at all
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+fn allow_origin(exact: Vec<HeaderValue>, patterns: Vec<String>) -> AllowOrigin {
+    if patterns.is_empty() {
+        return AllowOrigin::list(exact);
+    }
+    AllowOrigin::predicate(move |origin, _| {
+        exact.contains(origin)
+            || origin
+                .to_str()
+                .is_ok_and(|origin| patterns.iter().any(|p| origin_matches(p, origin)))
+    })
+}
+
+pub fn create_router(state: AppState, cors: CorsConfig) -> axum::Router {
+    let response_compression = state.response_compression;
+    let cors = CorsLayer::new()
+        .allow_origin(allow_origin(
+            cors.allowed_origins,
+            cors.allowed_origin_patterns,
+        ))
+        .allow_methods(cors.allowed_methods)
+        .allow_headers(cors.allowed_headers)
         .allow_credentials(true);
 
-    // Create separate router for webhooks (no auth middleware)
+    // Create separate router for webhooks (signature-checked, no JWT auth)
     let webhook_routes = axum::Router::new()
         .route(
             "/webhooks/user-created",
@@ -334,6 +2880,66 @@ pub fn create_router(state: AppState, allowed_origins: Vec<header::HeaderValue>)
             "/webhooks/user-deleted",
             axum::routing::post(crate::webhooks::handle_user_deleted),
         )
+        .route(
+            "/webhooks/users-bulk",
+            axum::routing::post(crate::webhooks::handle_users_bulk),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::webhooks::dedupe_event,
+        ))
+        // Outermost, so unsigned requests never reach dedupe
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::webhooks::verify_signature,
+        ))
+        .with_state(state.clone());
+
+    // Health probes and metrics also bypass the auth middleware
+    let health_routes = axum::Router::new()
+        .route("/healthz", axum::routing::get(crate::health::healthz))
+        .route("/readyz", axum::routing::get(crate::health::readyz))
+        .route(
+            "/metrics",
+            axum::routing::get(crate::telemetry::metrics_handler),
+        )
+        .with_state(state.clone());
+
+    // Operator endpoints, gated by the admin secret instead of JWT auth
+    let admin_routes = axum::Router::new()
+        .route(
+            "/admin/reload-rules",
+            axum::routing::post(crate::admin::reload_rules),
+        )
+        .route(
+            "/admin/cache/invalidate",
+            axum::routing::post(crate::admin::invalidate_cache),
+        )
+        .route(
+            "/admin/tokens/revoke",
+            axum::routing::post(crate::admin::revoke_token),
+        )
+        .route(
+            "/admin/grants",
+            axum::routing::post(crate::admin::create_grant),
+        )
+        .route(
+            "/admin/maintenance",
+            axum::routing::post(crate::admin::set_maintenance),
+        )
+        .route(
+            "/debug/authz",
+            axum::routing::post(crate::admin::debug_authz),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::admin::require_admin,
+        ))
+        .with_state(state.clone());
+
+    // Gateway-served endpoints for the caller (JWT checked by the handler)
+    let me_routes = axum::Router::new()
+        .route("/me/features", axum::routing::get(me_features))
         .with_state(state.clone());
 
     // Main router with auth middleware
@@ -346,84 +2952,352 @@ pub fn create_router(state: AppState, allowed_origins: Vec<header::HeaderValue>)
         .with_state(state);
 
     // Merge routers
-    axum::Router::new()
+    let mut router = axum::Router::new()
         .merge(webhook_routes)
-        .merge(protected_routes)
+        .merge(health_routes)
+        .merge(admin_routes)
+        .merge(me_routes)
+        .merge(protected_routes);
+    if response_compression {
+        router = router.layer(compression_layer());
+    }
+    router
         .layer(TraceLayer::new_for_http())
+        .layer(middleware::from_fn(request_id_middleware))
         .layer(cors)
 }
 
+/// Compresses responses per the client's `Accept-Encoding`. Responses that
+/// are tiny, already encoded, streamed as events, or in an already
+/// compressed format are passed through untouched.
+fn compression_layer() -> CompressionLayer<impl Predicate> {
+    let predicate = DefaultPredicate::new()
+        .and(NotForContentType::const_new("application/zip"))
+        .and(NotForContentType::const_new("application/gzip"))
+        .and(NotForContentType::const_new("application/x-gzip"))
+        .and(NotForContentType::const_new("font/woff2"))
+        .and(NotForContentType::const_new("video/"))
+        .and(NotForContentType::const_new("audio/"));
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(predicate)
+}
+
 pub async fn proxy_handler(
     State(state): State<AppState>,
     req: Request<Body>,
 ) -> Result<Response<Body>, StatusCode> {
-    let path = req.uri().path();
-    let query = req.uri().query().unwrap_or("");
+    // Auth has already run; WebSockets are bridged instead of forwarded
+    if crate::ws_proxy::is_websocket_upgrade(req.headers()) {
+        return Ok(crate::ws_proxy::proxy_websocket(&state, req).await);
+    }
 
-    // Get the route config to determine target
-    let match_result = state.router.at(path);
-    let target_url = if let Ok(matched) = match_result {
-        let route_config = matched.value;
-        match &route_config.target {
-            Some(target) if target == "zitadel" => {
-                format!("{}{}", state.zitadel_api_url, path)
-            }
-            Some(target) if target == "openfga" => {
-                format!("{}{}", state.openfga_url, path)
-            }
-            _ => {
-                format!("{}{}", state.upstream_url, path)
-            }
-        }
-    } else {
-        format!("{}{}", state.upstream_url, path)
-    };
+    let started = Instant::now();
+    let result = forward_request(&state, req).await;
 
-    let final_url = if query.is_empty() {
-        target_url
-    } else {
-        format!("{}?{}", target_url, query)
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(status) => *status,
     };
+    metrics::histogram!(
+        telemetry::PROXY_REQUEST_DURATION_SECONDS,
+        "status" => status.as_u16().to_string()
+    )
+    .record(started.elapsed().as_secs_f64());
+
+    result
+}
+
+async fn forward_request(
+    state: &AppState,
+    req: Request<Body>,
+) -> Result<Response<Body>, StatusCode> {
+    let final_url = upstream_url(state, req.method(), req.uri()).map_err(|e| {
+        tracing::error!("Cannot build upstream path: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
 
     tracing::debug!("Proxying to: {}", final_url);
 
     let method = req.method().clone();
-    let headers = req.headers().clone();
-    let body_bytes = axum::body::to_bytes(req.into_body(), usize::MAX)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut headers = req.headers().clone();
+    strip_hop_by_hop(&mut headers);
+    // Trailers are relayed, so a caller that accepts them (gRPC) still says so
+    if accepts_trailers(req.headers()) {
+        headers.insert(header::TE, HeaderValue::from_static("trailers"));
+    }
+    apply_forward_allowlist(state, &mut headers);
+    apply_upstream_auth(state, req.method(), req.uri().path(), &mut headers);
+    apply_gateway_secret(state, &mut headers);
+    Forwarded::new(state, req.headers(), req.extensions()).apply(&mut headers);
+    crate::otel::inject_trace_context(&mut headers);
+    let request_id = req.extensions().get::<RequestId>().cloned();
+    let client = upstream_client(state, &method, req.uri().path());
 
-    let mut proxy_req = state.http_client.request(method, &final_url);
+    // Reject declared oversize bodies up front; the rest are capped mid-stream
+    let declared_len = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.parse::<usize>().ok());
+    if declared_len.is_some_and(|len| len > state.max_body_bytes) {
+        tracing::warn!("Request body exceeds {} bytes", state.max_body_bytes);
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let body = Body::new(Limited::new(req.into_body(), state.max_body_bytes));
+
+    let mut proxy_req = client
+        .request(method.clone(), &final_url)
+        .timeout(state.upstream_timeout);
 
     for (name, value) in headers.iter() {
-        if name != header::HOST {
+        if name != header::HOST && name != REQUEST_ID_HEADER {
             proxy_req = proxy_req.header(name, value);
         }
     }
 
-    if !body_bytes.is_empty() {
-        proxy_req = proxy_req.body(body_bytes.to_vec());
+    // Same correlation ID upstream, whether supplied or generated here
+    if let Some(RequestId(request_id)) = request_id {
+        proxy_req = proxy_req.header(REQUEST_ID_HEADER, request_id);
+    }
+
+    // Stream the body through; a known-empty body stays empty (no chunked GETs)
+    let has_body = body.size_hint().exact() != Some(0);
+    if has_body {
+        proxy_req = proxy_req.body(reqwest::Body::wrap_stream(body.into_data_stream()));
     }
 
-    let proxy_response = proxy_req.send().await.map_err(|e| {
+    // Only a bodiless idempotent request can safely be sent twice: anything
+    // else may have side effects, and a streamed body can't be replayed
+    let max_attempts = if is_idempotent(&method) && !has_body {
+        state.upstream_retry_attempts.max(1)
+    } else {
+        1
+    };
+    let mut attempt = 1;
+    let proxy_response = loop {
+        let request = match proxy_req.try_clone() {
+            Some(request) if attempt < max_attempts => request,
+            _ => break proxy_req.send().await,
+        };
+        match request.send().await {
+            Err(e) if is_connection_failure(&e) => {
+                tracing::warn!(
+                    "Upstream connection failed (attempt {}/{}), retrying: {}",
+                    attempt,
+                    max_attempts,
+                    e
+                );
+                attempt += 1;
+            }
+            result => break result,
+        }
+    };
+
+    let proxy_response = proxy_response.map_err(|e| {
+        if exceeded_body_limit(&e) {
+            tracing::warn!("Request body exceeded {} bytes", state.max_body_bytes);
+            return StatusCode::PAYLOAD_TOO_LARGE;
+        }
+        if e.is_timeout() {
+            tracing::error!("Upstream timed out after {:?}", state.upstream_timeout);
+            return StatusCode::GATEWAY_TIMEOUT;
+        }
         tracing::error!("Proxy request failed: {}", e);
         StatusCode::BAD_GATEWAY
     })?;
 
     let status = proxy_response.status();
-    let headers = proxy_response.headers().clone();
-    let response_bytes = proxy_response
-        .bytes()
-        .await
-        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+    let mut headers = proxy_response.headers().clone();
+    strip_hop_by_hop(&mut headers);
 
+    // Content-Length passes through; framing like Transfer-Encoding is per hop
     let mut response = Response::builder().status(status);
-
     for (name, value) in headers.iter() {
         response = response.header(name, value);
     }
 
+    // HEAD gets the upstream's headers, Content-Length included, but never
+    // a body, whatever upstream sent. Otherwise relayed frame by frame, so
+    // trailers (gRPC status) survive.
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        Body::new(reqwest::Body::from(proxy_response))
+    };
     response
-        .body(Body::from(response_bytes))
+        .body(body)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
+
+/// The HTTP/2 client for rules targeting an `http2_services` entry,
+/// otherwise the regular upstream client
+fn upstream_client<'a>(state: &'a AppState, method: &Method, path: &str) -> &'a HttpClient {
+    let router = state.router.load();
+    let http2 = router
+        .at(path)
+        .ok()
+        .and_then(|matched| matched.value.get(method))
+        .and_then(|config| config.target.as_deref())
+        .is_some_and(|target| state.http2_services.contains(target));
+    if http2 {
+        &state.upstream_h2_client
+    } else {
+        &state.upstream_client
+    }
+}
+
+/// Whether the caller sent `TE: trailers`, which gRPC requires
+fn accepts_trailers(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::TE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case("trailers"))
+}
+
+/// Upstream URL for a request: the rule's `target` service (or the default
+/// upstream) plus the (possibly rewritten) path and the original query
+pub(crate) fn upstream_url(
+    state: &AppState,
+    method: &Method,
+    uri: &axum::http::Uri,
+) -> Result<String, String> {
+    let path = uri.path();
+
+    // Get the route config to determine target
+    let router = state.router.load_full();
+    let matched = router.at(path).ok();
+    let route_config = matched
+        .as_ref()
+        .and_then(|matched| matched.value.get(method));
+
+    let base_url = match route_config.and_then(|config| config.target.as_deref()) {
+        Some(target) => state.service_url(target).unwrap_or_else(|| {
+            tracing::warn!("Unknown target service {}, using default upstream", target);
+            &state.upstream_url
+        }),
+        None => &state.upstream_url,
+    };
+    let upstream_path = match (route_config, &matched) {
+        (Some(config), Some(matched)) => config.upstream_path(path, &matched.params)?,
+        _ => path.to_string(),
+    };
+    let target_url = format!("{}{}", base_url, upstream_path);
+
+    // A rewrite template may carry its own query string
+    match uri.query() {
+        Some(query) if !query.is_empty() => {
+            let separator = if upstream_path.contains('?') {
+                '&'
+            } else {
+                '?'
+            };
+            Ok(format!("{}{}{}", target_url, separator, query))
+        }
+        _ => Ok(target_url),
+    }
+}
+
+/// Methods that are safe to resend (RFC 9110 section 9.2.2), restricted to
+/// those proxied requests normally send without a body
+fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// The upstream couldn't be reached or dropped the connection before
+/// answering. Timeouts aren't retried, so they can't multiply latency.
+fn is_connection_failure(error: &reqwest::Error) -> bool {
+    (error.is_connect() || error.is_request()) && !error.is_timeout()
+}
+
+/// Whether a proxy failure was caused by the client body hitting the cap
+fn exceeded_body_limit(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(e) = source {
+        if e.is::<LengthLimitError>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// Connection-level headers that must not be forwarded (RFC 7230 section 6.1)
+const HOP_BY_HOP_HEADERS: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Replace any client-supplied gateway secret with the configured one, so
+/// upstream can tell proxied traffic from direct calls
+pub(crate) fn apply_gateway_secret(state: &AppState, headers: &mut HeaderMap) {
+    headers.remove(GATEWAY_SECRET_HEADER);
+    if let Some(secret) = &state.upstream_secret {
+        headers.insert(GATEWAY_SECRET_HEADER, secret.clone());
+    }
+}
+
+/// Drop the caller's bearer token, which the gateway has already verified,
+/// unless the rule targets a built-in service that checks it itself
+pub(crate) fn apply_upstream_auth(
+    state: &AppState,
+    method: &Method,
+    path: &str,
+    headers: &mut HeaderMap,
+) {
+    if !state.strip_upstream_auth {
+        return;
+    }
+    let router = state.router.load();
+    let target = router
+        .at(path)
+        .ok()
+        .and_then(|matched| matched.value.get(method))
+        .and_then(|config| config.target.as_deref());
+    if !target.is_some_and(|target| BUILTIN_TARGETS.contains(&target)) {
+        headers.remove(header::AUTHORIZATION);
+    }
+}
+
+/// In allowlist mode, drop every request header that isn't listed in
+/// `forward_headers` or set by the gateway itself (identity headers)
+pub(crate) fn apply_forward_allowlist(state: &AppState, headers: &mut HeaderMap) {
+    if state.forward_header_mode != ForwardHeaderMode::Allowlist {
+        return;
+    }
+    let dropped: Vec<header::HeaderName> = headers
+        .keys()
+        .filter(|name| {
+            !state.forward_headers.contains(name) && !IDENTITY_HEADERS.contains(&name.as_str())
+        })
+        .cloned()
+        .collect();
+    for name in dropped {
+        headers.remove(name);
+    }
+}
+
+/// Remove hop-by-hop headers, including any the sender named in `Connection`
+pub(crate) fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<String> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    for name in listed.iter().map(String::as_str).chain(HOP_BY_HOP_HEADERS) {
+        headers.remove(name);
+    }
+}
@@ -0,0 +1,153 @@
+// Circuit Breaker
+// Short-circuits calls to a failing dependency instead of paying a timeout
+// (and its retries) on every request during an outage
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::telemetry;
+
+#[derive(Clone, Debug)]
+pub struct BreakerConfig {
+    /// Consecutive failures that open the breaker
+    pub failure_threshold: u32,
+    /// How long the breaker stays open before a trial call is let through
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl BreakerState {
+    /// Value exported on the state gauge: 0 closed, 1 half-open, 2 open
+    pub fn as_gauge(self) -> f64 {
+        match self {
+            Self::Closed => 0.0,
+            Self::HalfOpen => 1.0,
+            Self::Open => 2.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Inner {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { trial_in_flight: bool },
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    config: BreakerConfig,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, config: BreakerConfig) -> Self {
+        let breaker = Self {
+            name,
+            config,
+            inner: Mutex::new(Inner::Closed { failures: 0 }),
+        };
+        breaker.publish(BreakerState::Closed);
+        breaker
+    }
+
+    pub fn state(&self) -> BreakerState {
+        match *self.inner.lock().unwrap() {
+            Inner::Closed { .. } => BreakerState::Closed,
+            Inner::Open { .. } => BreakerState::Open,
+            Inner::HalfOpen { .. } => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Whether a call may go out now. Once the cool-down has elapsed exactly
+    /// one trial call is allowed; its outcome closes or re-opens the breaker.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match &mut *inner {
+            Inner::Closed { .. } => true,
+            Inner::Open { until } if Instant::now() >= *until => {
+                *inner = Inner::HalfOpen {
+                    trial_in_flight: true,
+                };
+                self.transition(BreakerState::HalfOpen);
+                true
+            }
+            Inner::Open { .. } => false,
+            Inner::HalfOpen { trial_in_flight } => !std::mem::replace(trial_in_flight, true),
+        }
+    }
+
+    /// A call that ended without an outcome (its future was dropped). If it
+    /// was the half-open trial, the next call becomes the trial instead.
+    pub fn record_abandoned(&self) {
+        if let Inner::HalfOpen { trial_in_flight } = &mut *self.inner.lock().unwrap() {
+            *trial_in_flight = false;
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if !matches!(*inner, Inner::Closed { .. }) {
+            self.transition(BreakerState::Closed);
+        }
+        *inner = Inner::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let trip = match &mut *inner {
+            Inner::Closed { failures } => {
+                *failures += 1;
+                *failures >= self.config.failure_threshold
+            }
+            Inner::HalfOpen { .. } => true,
+            Inner::Open { .. } => false,
+        };
+
+        if trip {
+            *inner = Inner::Open {
+                until: Instant::now() + self.config.cooldown,
+            };
+            self.transition(BreakerState::Open);
+        }
+    }
+
+    fn transition(&self, state: BreakerState) {
+        match state {
+            BreakerState::Open => tracing::warn!(
+                "{} circuit breaker open, short-circuiting calls for {:?}",
+                self.name,
+                self.config.cooldown
+            ),
+            BreakerState::HalfOpen => {
+                tracing::info!(
+                    "{} circuit breaker half-open, sending trial call",
+                    self.name
+                )
+            }
+            BreakerState::Closed => tracing::info!("{} circuit breaker closed", self.name),
+        }
+        self.publish(state);
+    }
+
+    fn publish(&self, state: BreakerState) {
+        metrics::gauge!(telemetry::CIRCUIT_BREAKER_STATE, "dependency" => self.name)
+            .set(state.as_gauge());
+    }
+}
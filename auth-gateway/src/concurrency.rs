@@ -0,0 +1,66 @@
+// Per-User Concurrency Limit
+// Caps how many requests one user can have in flight at once, so a burst of
+// slow requests can't tie up every upstream connection
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    limit: usize,
+    in_flight: Mutex<HashMap<String, usize>>, // user -> requests in flight (absent = 0)
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one of `user`'s slots, or None if all are in use. The slot is
+    /// held until the returned guard is dropped.
+    pub fn try_acquire(self: &Arc<Self>, user: &str) -> Option<InFlightGuard> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(user.to_string()).or_default();
+        if *count >= self.limit {
+            return None;
+        }
+        *count += 1;
+        Some(InFlightGuard {
+            limiter: self.clone(),
+            user: user.to_string(),
+        })
+    }
+
+    /// Requests `user` currently has in flight
+    pub fn in_flight(&self, user: &str) -> usize {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .get(user)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// A held slot, released on drop however the request ends
+#[derive(Debug)]
+pub struct InFlightGuard {
+    limiter: Arc<ConcurrencyLimiter>,
+    user: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut in_flight = self.limiter.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.user) {
+            *count -= 1;
+            // Idle users don't keep an entry
+            if *count == 0 {
+                in_flight.remove(&self.user);
+            }
+        }
+    }
+}
@@ -0,0 +1,659 @@
+// Gateway Configuration
+// Every setting, read from the environment and validated together once at startup
+
+use axum::http::{header::HeaderName, HeaderValue, Method};
+use jsonwebtoken::Algorithm;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::audit::{AuditSink, DEFAULT_AUDIT_QUEUE_CAPACITY};
+use crate::auth::{
+    default_method_actions, Consistency, CorsConfig, DefaultPolicy, ForwardHeaderMode,
+    HttpClientConfig, ModelErrorStatus, RateLimitFailMode, RetryPolicy, TenantSource,
+    UnmatchedStatus, DEFAULT_AUTHZ_CACHE_MAX_CAPACITY, DEFAULT_JWKS_CACHE_MAX_CAPACITY,
+    DEFAULT_MAINTENANCE_RETRY_AFTER_SECS, DEFAULT_MAX_BODY_BYTES, DEFAULT_MAX_URL_LEN,
+    DEFAULT_UPSTREAM_RETRY_ATTEMPTS, DEFAULT_UPSTREAM_TIMEOUT_SECS,
+};
+use crate::circuit_breaker::BreakerConfig;
+use crate::introspection::{
+    IntrospectionConfig, DEFAULT_INTROSPECTION_CACHE_TTL_SECS, DEFAULT_INTROSPECTION_MAX_IN_FLIGHT,
+    DEFAULT_INTROSPECTION_NEGATIVE_CACHE_TTL_SECS,
+};
+use crate::webhooks::UserRegistration;
+
+/// Every setting the gateway reads from the environment. Each one must be
+/// present or must parse; every problem is collected before failing, so a
+/// misconfigured deployment is fixed in one go.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub openfga_url: String,
+    pub openfga_store_id: String,
+    pub zitadel_issuer_url: String,
+    pub zitadel_api_url: String,
+    pub redis_url: String,
+    pub jwt_algorithms: Vec<Algorithm>, // Defaults to RS256 alone
+    pub tenant_source: Option<TenantSource>, // Namespace OpenFGA objects by "org_id" or "header:<name>"
+    pub upstream_secret: Option<HeaderValue>, // Sent upstream so services can reject direct calls
+    pub forward_header_mode: ForwardHeaderMode,
+    pub forward_headers: Vec<HeaderName>, // Only these (plus identity headers) in allowlist mode
+    pub audit_sink: Option<AuditSink>,    // Record of denials and sampled allows
+    pub trusted_proxies: Vec<IpAddr>,     // Peers whose X-Forwarded-For is believed
+    pub trusted_proxy_hops: usize, // Load balancers in front, each appending to X-Forwarded-For
+    pub rate_limit_fail_mode: RateLimitFailMode,
+    pub default_policy: DefaultPolicy,
+    pub unmatched_status: UnmatchedStatus, // 404 hides which paths the gateway governs
+    pub model_error_status: ModelErrorStatus, // 502 tells clients a check failed on model drift
+    pub openfga_consistency: Option<Consistency>, // Default for middleware checks
+    pub webhook_timestamp_tolerance_secs: Option<u64>, // Max age of a signed webhook (None = no timestamp)
+    pub strip_upstream_auth: bool, // Upstreams get X-User-ID instead of the bearer token
+    pub http: HttpClientConfig,    // Pools of the outbound clients
+    pub openfga_retry: RetryPolicy,
+    pub openfga_breaker: BreakerConfig,
+    pub openfga_breaker_open_decision: bool, // Checks allowed while the breaker is open
+    pub openfga_relation_map: HashMap<String, String>, // Rule action -> OpenFGA relation
+    pub openfga_model_id: Option<String>,    // Pins checks to one authorization model
+    pub openfga_context_headers: Vec<String>, // Request headers passed as check context
+    pub jwks_file: Option<String>,           // Static JWKS replacing every issuer's endpoint
+    pub jwks_url: Option<String>,            // JWKS for issuers listed without one
+    pub jwt_issuers: Vec<String>, // `iss` or `iss=jwks_url` entries (defaults to ZITADEL_ISSUER_URL)
+    pub jwt_audience: Option<Vec<String>>, // Accepted `aud` values (None = skip check)
+    pub jwt_leeway_secs: u64,
+    pub jwks_retry: RetryPolicy,
+    pub jwks_cache_max_capacity: u64,
+    pub introspection: Option<IntrospectionConfig>, // Set when client credentials are
+    pub introspection_cache_ttl: Duration,
+    pub introspection_negative_cache_ttl: Duration,
+    pub token_cache_ttl: Option<Duration>, // None = verify every request
+    pub token_denylist: bool,
+    pub upstream_url: String,
+    pub upstream_services: HashMap<String, String>, // Rule `target` name -> base URL
+    pub upstream_http2_services: HashSet<String>,   // Targets spoken to over cleartext HTTP/2
+    pub method_actions: HashMap<Method, String>,    // Action for rules without one
+    pub max_body_bytes: usize,
+    pub max_url_len: usize,
+    pub upstream_timeout: Duration,
+    pub upstream_retry_attempts: u32,
+    pub maintenance_mode: bool, // Start with proxied routes returning 503
+    pub maintenance_retry_after_secs: u64,
+    pub webhook_signing_secret: Option<String>,
+    pub user_registration: UserRegistration,
+    pub admin_secret: Option<String>,
+    pub debug_headers: bool,
+    pub access_log: bool,
+    pub response_compression: bool,
+    pub user_max_in_flight: Option<usize>, // None = unlimited
+    pub authz_cache_max_capacity: u64,
+    pub authz_cache_ttl: Duration,
+    pub authz_negative_cache_ttl: Duration,
+    pub authz_cache_verify_rate: f64,
+    pub stale_grant_ttl: Option<Duration>, // Stale-while-error (None = disabled)
+    pub audit_allow_sample_rate: f64,
+    pub audit_queue_capacity: usize,
+    pub listable_objects: Vec<(String, String)>, // (type, relation) pairs for GET /me/features
+    pub feature_sync_dry_run: bool,
+    pub startup_healthcheck: bool,
+    pub startup_healthcheck_abort: bool,
+    pub cors: CorsConfig,
+}
+
+/// Every missing or invalid setting found by `Config::from_vars`
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid configuration: {}", self.0.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_os_vars(std::env::vars_os())
+    }
+
+    /// Build from `(name, value)` pairs; empty values count as unset
+    pub fn from_vars<I, K, V>(vars: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Self::read(EnvReader::new(vars))
+    }
+
+    /// Like `from_vars`, for the raw process environment. Names that aren't
+    /// Unicode can't be settings and are skipped; a setting whose value isn't
+    /// Unicode is reported with the other problems.
+    pub fn from_os_vars<I>(vars: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = (OsString, OsString)>,
+    {
+        Self::read(EnvReader::from_os(vars))
+    }
+
+    fn read(mut env: EnvReader) -> Result<Self, ConfigError> {
+        let openfga_url = env.required("OPENFGA_URL");
+        let openfga_store_id = env.required("OPENFGA_STORE_ID");
+        let zitadel_issuer_url = env.required("ZITADEL_ISSUER_URL");
+        let zitadel_api_url = env.required("ZITADEL_API_URL");
+        let redis_url = env.required("REDIS_URL");
+
+        let mut jwt_algorithms = env.list("JWT_ALGORITHMS", "signing algorithms such as RS256");
+        if jwt_algorithms.is_empty() {
+            jwt_algorithms.push(Algorithm::RS256);
+        }
+
+        let mut jwt_issuers = env.list("JWT_ISSUERS", "issuer URLs");
+        if jwt_issuers.is_empty() {
+            jwt_issuers.push(zitadel_issuer_url.clone());
+        }
+        let jwt_audience: Vec<String> = env.list("JWT_AUDIENCE", "audience names");
+
+        let introspection_url = env.get("INTROSPECTION_URL").map(str::to_string);
+        let introspection_client_id = env.get("INTROSPECTION_CLIENT_ID").map(str::to_string);
+        let introspection_client_secret =
+            env.get("INTROSPECTION_CLIENT_SECRET").map(str::to_string);
+        let introspection_max_in_flight = env
+            .parse("INTROSPECTION_MAX_IN_FLIGHT", "a positive integer")
+            .unwrap_or(DEFAULT_INTROSPECTION_MAX_IN_FLIGHT);
+        let introspection = match (introspection_client_id, introspection_client_secret) {
+            (Some(client_id), Some(client_secret)) => Some(IntrospectionConfig::new(
+                introspection_url
+                    .unwrap_or_else(|| format!("{}/oauth/v2/introspect", zitadel_api_url)),
+                client_id,
+                client_secret,
+                introspection_max_in_flight,
+            )),
+            (None, None) => None,
+            _ => {
+                env.errors.push(
+                    "INTROSPECTION_CLIENT_ID and INTROSPECTION_CLIENT_SECRET must be set together"
+                        .to_string(),
+                );
+                None
+            }
+        };
+
+        let defaults = HttpClientConfig::default();
+        let http = HttpClientConfig {
+            pool_max_idle_per_host: env
+                .parse("HTTP_POOL_MAX_IDLE", "a non-negative integer")
+                .unwrap_or(defaults.pool_max_idle_per_host),
+            pool_idle_timeout: env
+                .secs("HTTP_POOL_IDLE_TIMEOUT_SECS")
+                .unwrap_or(defaults.pool_idle_timeout),
+            connect_timeout: env
+                .secs("HTTP_CONNECT_TIMEOUT_SECS")
+                .unwrap_or(defaults.connect_timeout),
+            ..defaults
+        };
+
+        let mut user_registration = UserRegistration::default();
+        if let Some(object) = env.get("ORG_OBJECT") {
+            user_registration.object = object.to_string();
+        }
+        if let Some(relation) = env.get("ORG_MEMBER_RELATION") {
+            user_registration.relation = relation.to_string();
+        }
+        user_registration.machine_object = env.get("ORG_MACHINE_OBJECT").map(str::to_string);
+
+        let stale_while_error = env.flag("AUTHZ_STALE_WHILE_ERROR", false);
+        let stale_grant_ttl = env
+            .secs("AUTHZ_STALE_GRANT_TTL_SECS")
+            .unwrap_or(Duration::from_secs(3600));
+
+        let mut cors = CorsConfig::new(env.list("ALLOWED_ORIGINS", "origin header values"));
+        if cors.allowed_origins.is_empty() {
+            cors.allowed_origins = vec![
+                HeaderValue::from_static("http://localhost:3000"),
+                HeaderValue::from_static("http://localhost:8080"),
+            ];
+        }
+        cors.allowed_origin_patterns = env.list("CORS_ALLOW_PATTERNS", "origin patterns");
+        if let Some(methods) = env.methods("ALLOWED_METHODS") {
+            cors.allowed_methods = methods;
+        }
+        let allowed_headers = env.list("ALLOWED_HEADERS", "header names");
+        if !allowed_headers.is_empty() {
+            cors.allowed_headers = allowed_headers;
+        }
+
+        let config = Config {
+            openfga_url,
+            openfga_store_id,
+            zitadel_issuer_url,
+            zitadel_api_url,
+            redis_url,
+            jwt_algorithms,
+            tenant_source: env.parse("AUTHZ_TENANT_SOURCE", "'org_id' or 'header:<name>'"),
+            upstream_secret: env.parse("UPSTREAM_GATEWAY_SECRET", "a valid header value"),
+            forward_header_mode: env
+                .parse("FORWARD_HEADER_MODE", "'all' or 'allowlist'")
+                .unwrap_or_default(),
+            forward_headers: env.list("FORWARD_HEADERS", "header names"),
+            audit_sink: env.parse(
+                "AUDIT_SINK",
+                "'tracing', 'redis:<stream>' or an http(s) URL",
+            ),
+            trusted_proxies: env.list("TRUSTED_PROXIES", "IP addresses"),
+            trusted_proxy_hops: env
+                .parse("TRUSTED_PROXY_HOPS", "a non-negative integer")
+                .unwrap_or(0),
+            rate_limit_fail_mode: env
+                .parse("RATE_LIMIT_FAIL_MODE", "'open' or 'closed'")
+                .unwrap_or_default(),
+            default_policy: env
+                .parse("DEFAULT_POLICY", "'deny' or 'proxy'")
+                .unwrap_or_default(),
+            unmatched_status: env
+                .parse("UNMATCHED_STATUS", "403 or 404")
+                .unwrap_or_default(),
+            model_error_status: env
+                .parse("OPENFGA_MODEL_ERROR_STATUS", "403 or 502")
+                .unwrap_or_default(),
+            openfga_consistency: env.parse(
+                "OPENFGA_CONSISTENCY",
+                "'minimize_latency' or 'higher_consistency'",
+            ),
+            webhook_timestamp_tolerance_secs: env.parse(
+                "WEBHOOK_TIMESTAMP_TOLERANCE_SECS",
+                "a whole number of seconds",
+            ),
+            strip_upstream_auth: env.flag("STRIP_UPSTREAM_AUTH", true),
+            http,
+            openfga_retry: RetryPolicy {
+                max_attempts: env
+                    .parse("OPENFGA_RETRY_ATTEMPTS", "a non-negative integer")
+                    .unwrap_or(3),
+                base_delay: env
+                    .millis("OPENFGA_RETRY_BASE_DELAY_MS")
+                    .unwrap_or(Duration::from_millis(50)),
+            },
+            openfga_breaker: BreakerConfig {
+                failure_threshold: env
+                    .parse("OPENFGA_BREAKER_THRESHOLD", "a non-negative integer")
+                    .unwrap_or(5),
+                cooldown: env
+                    .secs("OPENFGA_BREAKER_COOLDOWN_SECS")
+                    .unwrap_or(Duration::from_secs(30)),
+            },
+            openfga_breaker_open_decision: env
+                .parse::<OpenDecision>("OPENFGA_BREAKER_OPEN_DECISION", "'allow' or 'deny'")
+                .is_some_and(|OpenDecision(allow)| allow),
+            openfga_relation_map: env
+                .json(
+                    "OPENFGA_RELATION_MAP",
+                    "a JSON object of action to relation",
+                )
+                .unwrap_or_default(),
+            openfga_model_id: env.get("OPENFGA_MODEL_ID").map(str::to_string),
+            openfga_context_headers: env.list("OPENFGA_CONTEXT_HEADERS", "header names"),
+            jwks_file: env.get("JWKS_FILE").map(str::to_string),
+            jwks_url: env.get("JWKS_URL").map(str::to_string),
+            jwt_issuers,
+            jwt_audience: (!jwt_audience.is_empty()).then_some(jwt_audience),
+            jwt_leeway_secs: env
+                .parse("JWT_LEEWAY_SECS", "a whole number of seconds")
+                .unwrap_or(60),
+            jwks_retry: RetryPolicy {
+                max_attempts: env
+                    .parse("JWKS_RETRY_ATTEMPTS", "a non-negative integer")
+                    .unwrap_or(3),
+                base_delay: env
+                    .millis("JWKS_RETRY_BASE_DELAY_MS")
+                    .unwrap_or(Duration::from_millis(100)),
+            },
+            jwks_cache_max_capacity: env
+                .parse("JWKS_CACHE_MAX_CAPACITY", "a non-negative integer")
+                .unwrap_or(DEFAULT_JWKS_CACHE_MAX_CAPACITY),
+            introspection,
+            introspection_cache_ttl: env
+                .secs("INTROSPECTION_CACHE_TTL_SECS")
+                .unwrap_or(Duration::from_secs(DEFAULT_INTROSPECTION_CACHE_TTL_SECS)),
+            introspection_negative_cache_ttl: env
+                .secs("INTROSPECTION_NEGATIVE_CACHE_TTL_SECS")
+                .unwrap_or(Duration::from_secs(
+                    DEFAULT_INTROSPECTION_NEGATIVE_CACHE_TTL_SECS,
+                )),
+            token_cache_ttl: env.secs("JWT_CACHE_TTL_SECS").filter(|ttl| !ttl.is_zero()),
+            token_denylist: env.flag("TOKEN_DENYLIST", false),
+            upstream_url: env
+                .get("UPSTREAM_URL")
+                .unwrap_or("http://localhost:8000")
+                .to_string(),
+            upstream_services: env
+                .json("UPSTREAM_SERVICES", "a JSON object of service name to URL")
+                .unwrap_or_default(),
+            upstream_http2_services: env
+                .list::<String>("UPSTREAM_HTTP2_SERVICES", "service names")
+                .into_iter()
+                .collect(),
+            method_actions: env
+                .method_actions("METHOD_DEFAULT_ACTIONS")
+                .unwrap_or_else(default_method_actions),
+            max_body_bytes: env
+                .parse("MAX_BODY_BYTES", "a number of bytes")
+                .unwrap_or(DEFAULT_MAX_BODY_BYTES),
+            max_url_len: env
+                .parse("MAX_URL_LEN", "a number of bytes")
+                .unwrap_or(DEFAULT_MAX_URL_LEN),
+            upstream_timeout: env
+                .secs("UPSTREAM_TIMEOUT_SECS")
+                .unwrap_or(Duration::from_secs(DEFAULT_UPSTREAM_TIMEOUT_SECS)),
+            upstream_retry_attempts: env
+                .parse("UPSTREAM_RETRY_ATTEMPTS", "a non-negative integer")
+                .unwrap_or(DEFAULT_UPSTREAM_RETRY_ATTEMPTS),
+            maintenance_mode: env.flag("MAINTENANCE_MODE", false),
+            maintenance_retry_after_secs: env
+                .parse("MAINTENANCE_RETRY_AFTER_SECS", "a whole number of seconds")
+                .unwrap_or(DEFAULT_MAINTENANCE_RETRY_AFTER_SECS),
+            webhook_signing_secret: env.get("WEBHOOK_SIGNING_SECRET").map(str::to_string),
+            user_registration,
+            admin_secret: env.get("ADMIN_SECRET").map(str::to_string),
+            debug_headers: env.flag("DEBUG_HEADERS", false),
+            access_log: env.flag("ACCESS_LOG", false),
+            response_compression: env.flag("RESPONSE_COMPRESSION", false),
+            user_max_in_flight: env
+                .parse("USER_MAX_IN_FLIGHT", "a non-negative integer")
+                .filter(|&limit| limit > 0),
+            authz_cache_max_capacity: env
+                .parse("AUTHZ_CACHE_MAX_CAPACITY", "a non-negative integer")
+                .unwrap_or(DEFAULT_AUTHZ_CACHE_MAX_CAPACITY),
+            authz_cache_ttl: env
+                .secs("AUTHZ_CACHE_TTL_SECS")
+                .unwrap_or(Duration::from_secs(30)),
+            authz_negative_cache_ttl: env
+                .secs("AUTHZ_NEGATIVE_CACHE_TTL_SECS")
+                .unwrap_or(Duration::from_secs(5)),
+            authz_cache_verify_rate: env.rate("AUTHZ_CACHE_VERIFY_RATE"),
+            stale_grant_ttl: stale_while_error.then_some(stale_grant_ttl),
+            audit_allow_sample_rate: env.rate("AUDIT_ALLOW_SAMPLE_RATE"),
+            audit_queue_capacity: env
+                .parse("AUDIT_QUEUE_CAPACITY", "a positive integer")
+                .unwrap_or(DEFAULT_AUDIT_QUEUE_CAPACITY),
+            listable_objects: env.object_relations("LIST_OBJECT_TYPES"),
+            feature_sync_dry_run: env.flag("FEATURE_SYNC_DRY_RUN", false),
+            startup_healthcheck: env.flag("STARTUP_HEALTHCHECK", false),
+            startup_healthcheck_abort: env.flag("STARTUP_HEALTHCHECK_ABORT", false),
+            cors,
+        };
+        env.finish(config)
+    }
+}
+
+/// What `--check-rules` validates rule actions and targets against, read
+/// without requiring the rest of the configuration
+pub struct RuleTargets {
+    pub relations: HashMap<String, String>, // OPENFGA_RELATION_MAP
+    pub services: HashMap<String, String>,  // UPSTREAM_SERVICES
+}
+
+impl RuleTargets {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let mut env = EnvReader::from_os(std::env::vars_os());
+        let targets = RuleTargets {
+            relations: env
+                .json(
+                    "OPENFGA_RELATION_MAP",
+                    "a JSON object of action to relation",
+                )
+                .unwrap_or_default(),
+            services: env
+                .json("UPSTREAM_SERVICES", "a JSON object of service name to URL")
+                .unwrap_or_default(),
+        };
+        env.finish(targets)
+    }
+}
+
+/// OPENFGA_BREAKER_OPEN_DECISION: whether checks pass while the breaker is open
+struct OpenDecision(bool);
+
+impl FromStr for OpenDecision {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "allow" => Ok(Self(true)),
+            "deny" => Ok(Self(false)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Reads variables, noting each problem instead of stopping at the first
+struct EnvReader {
+    vars: HashMap<String, String>,
+    not_unicode: HashSet<String>, // Names whose values couldn't be read
+    errors: Vec<String>,
+}
+
+impl EnvReader {
+    fn new<I, K, V>(vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Self {
+            vars: vars
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+            not_unicode: HashSet::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn from_os<I: IntoIterator<Item = (OsString, OsString)>>(vars: I) -> Self {
+        let mut reader = Self::new(Vec::<(String, String)>::new());
+        for (name, value) in vars {
+            let Ok(name) = name.into_string() else {
+                continue;
+            };
+            match value.into_string() {
+                Ok(value) => reader.vars.insert(name, value),
+                Err(_) => {
+                    reader.not_unicode.insert(name);
+                    None
+                }
+            };
+        }
+        reader
+    }
+
+    /// The collected value, or every problem found while reading it
+    fn finish<T>(self, value: T) -> Result<T, ConfigError> {
+        if self.errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(ConfigError(self.errors))
+        }
+    }
+
+    fn get(&mut self, name: &str) -> Option<&str> {
+        if self.not_unicode.remove(name) {
+            self.errors.push(format!("{} must be valid Unicode", name));
+        }
+        self.vars
+            .get(name)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn required(&mut self, name: &str) -> String {
+        match self.get(name) {
+            Some(value) => value.to_string(),
+            None => {
+                self.errors.push(format!("{} must be set", name));
+                String::new()
+            }
+        }
+    }
+
+    /// `None` when unset, or when invalid (which is recorded)
+    fn parse<T: FromStr>(&mut self, name: &str, expected: &str) -> Option<T> {
+        let value = self.get(name)?;
+        match value.parse() {
+            Ok(parsed) => Some(parsed),
+            Err(_) => {
+                let error = format!("{} must be {} (got {:?})", name, expected, value);
+                self.errors.push(error);
+                None
+            }
+        }
+    }
+
+    /// `true`/`false` (also `1`/`0`, `yes`/`no`, `on`/`off`, any case), or
+    /// `default` when unset. Anything else is recorded, never guessed at.
+    fn flag(&mut self, name: &str, default: bool) -> bool {
+        let Some(value) = self.get(name) else {
+            return default;
+        };
+        match value.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => true,
+            "false" | "0" | "no" | "off" => false,
+            _ => {
+                let error = format!("{} must be true or false (got {:?})", name, value);
+                self.errors.push(error);
+                default
+            }
+        }
+    }
+
+    /// Comma-separated values; each invalid entry is recorded
+    fn list<T: FromStr>(&mut self, name: &str, expected: &str) -> Vec<T> {
+        let Some(value) = self.get(name) else {
+            return Vec::new();
+        };
+        let mut parsed = Vec::new();
+        let mut invalid = Vec::new();
+        for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.parse() {
+                Ok(item) => parsed.push(item),
+                Err(_) => invalid.push(entry.to_string()),
+            }
+        }
+        if !invalid.is_empty() {
+            let error = format!("{} must be {} (got {:?})", name, expected, invalid);
+            self.errors.push(error);
+        }
+        parsed
+    }
+
+    fn secs(&mut self, name: &str) -> Option<Duration> {
+        self.parse(name, "a whole number of seconds")
+            .map(Duration::from_secs)
+    }
+
+    fn millis(&mut self, name: &str) -> Option<Duration> {
+        self.parse(name, "a whole number of milliseconds")
+            .map(Duration::from_millis)
+    }
+
+    /// A fraction from 0 to 1, or 0 when unset
+    fn rate(&mut self, name: &str) -> f64 {
+        let Some(value) = self.get(name) else {
+            return 0.0;
+        };
+        match value.parse() {
+            Ok(rate) if (0.0..=1.0).contains(&rate) => rate,
+            _ => {
+                let error = format!("{} must be between 0 and 1 (got {:?})", name, value);
+                self.errors.push(error);
+                0.0
+            }
+        }
+    }
+
+    /// A JSON value such as an object of names to URLs
+    fn json<T: DeserializeOwned>(&mut self, name: &str, expected: &str) -> Option<T> {
+        let value = self.get(name)?;
+        match serde_json::from_str(value) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                let error = format!("{} must be {} ({})", name, expected, e);
+                self.errors.push(error);
+                None
+            }
+        }
+    }
+
+    /// Comma-separated HTTP methods, in any case; `None` when unset
+    fn methods(&mut self, name: &str) -> Option<Vec<Method>> {
+        let names: Vec<String> = self.list(name, "HTTP methods");
+        if names.is_empty() {
+            return None;
+        }
+        let mut methods = Vec::new();
+        let mut invalid = Vec::new();
+        for method in names {
+            match parse_method(&method) {
+                Some(parsed) => methods.push(parsed),
+                None => invalid.push(method),
+            }
+        }
+        if !invalid.is_empty() {
+            let error = format!("{} must be HTTP methods (got {:?})", name, invalid);
+            self.errors.push(error);
+        }
+        Some(methods)
+    }
+
+    /// JSON object of HTTP method to rule action, e.g. {"GET": "view"}
+    fn method_actions(&mut self, name: &str) -> Option<HashMap<Method, String>> {
+        let actions: HashMap<String, String> =
+            self.json(name, "a JSON object of HTTP method to action")?;
+        let mut parsed = HashMap::new();
+        let mut invalid = Vec::new();
+        for (method, action) in actions {
+            match parse_method(&method) {
+                Some(method) => {
+                    parsed.insert(method, action);
+                }
+                None => invalid.push(method),
+            }
+        }
+        if !invalid.is_empty() {
+            let error = format!("{} keys must be HTTP methods (got {:?})", name, invalid);
+            self.errors.push(error);
+        }
+        Some(parsed)
+    }
+
+    /// Comma-separated `type#relation` pairs, defaulting to `feature#viewer`
+    fn object_relations(&mut self, name: &str) -> Vec<(String, String)> {
+        let entries: Vec<String> = self.list(name, "type#relation pairs");
+        if entries.is_empty() {
+            return vec![("feature".to_string(), "viewer".to_string())];
+        }
+        let mut pairs = Vec::new();
+        let mut invalid = Vec::new();
+        for entry in entries {
+            match entry.split_once('#') {
+                Some((object_type, relation))
+                    if !object_type.is_empty() && !relation.is_empty() =>
+                {
+                    pairs.push((object_type.to_string(), relation.to_string()))
+                }
+                _ => invalid.push(entry),
+            }
+        }
+        if !invalid.is_empty() {
+            let error = format!("{} must be type#relation pairs (got {:?})", name, invalid);
+            self.errors.push(error);
+        }
+        pairs
+    }
+}
+
+/// A method name in any case; extension methods are accepted as given
+fn parse_method(method: &str) -> Option<Method> {
+    method.trim().to_ascii_uppercase().parse().ok()
+}
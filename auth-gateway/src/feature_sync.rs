@@ -4,9 +4,11 @@
 use anyhow::Result;
 use reqwest::Client as HttpClient;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 
+use crate::openfga::{OpenFgaClient, Tuple, TupleFilter, TupleKey, OPENFGA_MAX_TUPLES_PER_WRITE};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AccessRule {
     pub path: String,
@@ -15,27 +17,47 @@ pub struct AccessRule {
     pub target: Option<String>,
 }
 
-/// Migrate features based on changes between two access_rules files
+/// What a migration run changed (or, in dry-run mode, would change)
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct MigrationSummary {
+    pub dry_run: bool,
+    pub renamed: Vec<(String, String)>,
+    pub deleted: Vec<String>,
+    pub added: Vec<String>,
+    pub tuples_migrated: usize,
+    pub tuples_deleted: usize,
+}
+
+/// Migrate features based on changes between two access_rules files.
+///
+/// With `dry_run` set, tuples are still read from OpenFGA but nothing is
+/// written; the tuples that would change are logged and counted instead.
 pub async fn migrate_features(
     http_client: &HttpClient,
-    openfga_url: &str,
-    store_id: &str,
+    fga_client: &OpenFgaClient,
     latest_path: &str,
     prev_path: &str,
-) -> Result<()> {
+    dry_run: bool,
+) -> Result<MigrationSummary> {
     tracing::info!(
-        "Checking for feature changes between {} and {}",
+        "Checking for feature changes between {} and {}{}",
         latest_path,
-        prev_path
+        prev_path,
+        if dry_run { " (dry run)" } else { "" }
     );
 
+    let mut summary = MigrationSummary {
+        dry_run,
+        ..Default::default()
+    };
+
     // Load both versions
     let latest_rules = load_rules(latest_path)?;
     let prev_rules = match load_rules(prev_path) {
         Ok(rules) => rules,
         Err(e) => {
             tracing::warn!("Could not load previous rules ({}), skipping migration", e);
-            return Ok(());
+            return Ok(summary);
         }
     };
 
@@ -45,13 +67,17 @@ pub async fn migrate_features(
 
     // Detect changes
     let renamed = detect_renames(&prev_rules, &latest_rules);
-    let deleted = detect_deletions(&prev_features, &latest_features);
-    let added = detect_additions(&prev_features, &latest_features);
+    let mut deleted = detect_deletions(&prev_features, &latest_features);
+    // A renamed feature's tuples are migrated, so don't also delete them
+    deleted.retain(|feature| !renamed.iter().any(|(old, _)| old == feature));
+    let mut added = detect_additions(&prev_features, &latest_features);
+    deleted.sort();
+    added.sort();
 
     // Log summary
     if renamed.is_empty() && deleted.is_empty() && added.is_empty() {
         tracing::info!("No feature changes detected");
-        return Ok(());
+        return Ok(summary);
     }
 
     tracing::info!("Feature changes detected:");
@@ -78,37 +104,94 @@ pub async fn migrate_features(
 
     // Fetch tuples ONLY for features being migrated/deleted (not all millions of tuples!)
     let relevant_tuples = if !features_to_fetch.is_empty() {
-        fetch_tuples_for_features(http_client, openfga_url, store_id, &features_to_fetch).await?
+        fetch_tuples_for_features(http_client, fga_client, &features_to_fetch).await
     } else {
         vec![]
     };
 
-    // Apply ALL migrations in a SINGLE batched call
+    // Writes that succeeded, undone if a later step fails
+    let mut applied = Vec::new();
+
+    // Apply ALL migrations, chunked to the per-write tuple limit
     if !renamed.is_empty() {
-        migrate_all_feature_tuples(
+        match migrate_all_feature_tuples(
             http_client,
-            openfga_url,
-            store_id,
+            fga_client,
             &renamed,
             &relevant_tuples,
+            dry_run,
+            &mut applied,
         )
-        .await?;
+        .await
+        {
+            Ok(count) => summary.tuples_migrated = count,
+            Err(e) => {
+                roll_back(http_client, fga_client, &applied).await;
+                return Err(e);
+            }
+        }
     }
 
-    // Apply ALL deletions in a SINGLE batched call
+    // Apply ALL deletions, chunked to the per-write tuple limit
     if !deleted.is_empty() {
-        cleanup_all_feature_tuples(
+        match cleanup_all_feature_tuples(
             http_client,
-            openfga_url,
-            store_id,
+            fga_client,
             &deleted,
             &relevant_tuples,
+            dry_run,
+            &mut applied,
         )
-        .await?;
+        .await
+        {
+            Ok(count) => summary.tuples_deleted = count,
+            Err(e) => {
+                roll_back(http_client, fga_client, &applied).await;
+                return Err(e);
+            }
+        }
     }
 
-    tracing::info!("Feature migration completed successfully");
-    Ok(())
+    if dry_run {
+        tracing::info!("Feature migration dry run completed, no tuples were written");
+    } else {
+        tracing::info!("Feature migration completed successfully");
+    }
+
+    summary.renamed = renamed;
+    summary.deleted = deleted;
+    summary.added = added;
+    Ok(summary)
+}
+
+/// One successful `/write` made by a migration
+struct AppliedWrite {
+    writes: Vec<TupleKey>,
+    deletes: Vec<TupleKey>,
+}
+
+/// Best-effort undo of `applied`, newest first. OpenFGA writes aren't
+/// transactional across calls, so without this a failed step would leave
+/// the store half-migrated.
+async fn roll_back(client: &HttpClient, fga_client: &OpenFgaClient, applied: &[AppliedWrite]) {
+    for write in applied.iter().rev() {
+        match fga_client
+            .write(client, &write.deletes, &write.writes)
+            .await
+        {
+            Ok(()) => tracing::warn!(
+                "Rolled back migration write: removed {} tuples, restored {}",
+                write.writes.len(),
+                write.deletes.len()
+            ),
+            Err(e) => tracing::error!(
+                "Rollback failed, OpenFGA may be left half-migrated ({} tuples written, {} deleted): {}",
+                write.writes.len(),
+                write.deletes.len(),
+                e
+            ),
+        }
+    }
 }
 
 fn load_rules(path: &str) -> Result<Vec<AccessRule>> {
@@ -125,36 +208,65 @@ fn extract_features(rules: &[AccessRule]) -> HashSet<String> {
         .collect()
 }
 
+/// Renamed features, as `(old, new)` pairs.
+///
+/// A rename is reported when an endpoint (path + method) moved from a feature
+/// that no longer exists to one that didn't exist before. An old feature that
+/// spread across several new ones is ambiguous and skipped.
 fn detect_renames(prev: &[AccessRule], latest: &[AccessRule]) -> Vec<(String, String)> {
-    let mut renames = Vec::new();
-
-    // Heuristic: same path+method but different feature = likely a rename
-    for prev_rule in prev {
-        if prev_rule.feature == "public_access" {
+    let prev_by_endpoint = features_by_endpoint(prev);
+    let latest_by_endpoint = features_by_endpoint(latest);
+    let prev_features = extract_features(prev);
+    let latest_features = extract_features(latest);
+
+    // Old feature -> every new feature its endpoints moved to
+    let mut candidates: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for (endpoint, old_feature) in &prev_by_endpoint {
+        let Some(new_feature) = latest_by_endpoint.get(endpoint) else {
             continue;
+        };
+        if old_feature != new_feature
+            && !latest_features.contains(*old_feature)
+            && !prev_features.contains(*new_feature)
+        {
+            candidates
+                .entry(old_feature)
+                .or_default()
+                .insert(new_feature);
         }
+    }
 
-        for latest_rule in latest {
-            if latest_rule.feature == "public_access" {
-                continue;
-            }
-
-            // If same endpoint but different feature, it's likely renamed
-            if prev_rule.path == latest_rule.path
-                && prev_rule.method == latest_rule.method
-                && prev_rule.feature != latest_rule.feature
-            {
-                renames.push((prev_rule.feature.clone(), latest_rule.feature.clone()));
-            }
+    let mut renames = Vec::new();
+    for (old_feature, new_features) in candidates {
+        if new_features.len() > 1 {
+            tracing::warn!(
+                "Feature {} maps to several new features {:?}, skipping ambiguous rename",
+                old_feature,
+                new_features
+            );
+            continue;
+        }
+        if let Some(new_feature) = new_features.first() {
+            renames.push((old_feature.to_string(), new_feature.to_string()));
         }
     }
-
-    // Deduplicate
-    renames.sort();
-    renames.dedup();
     renames
 }
 
+/// `(path, method) -> feature`, ignoring public_access rules
+fn features_by_endpoint(rules: &[AccessRule]) -> HashMap<(&str, &str), &str> {
+    rules
+        .iter()
+        .filter(|r| r.feature != "public_access")
+        .map(|r| ((r.path.as_str(), r.method.as_str()), r.feature.as_str()))
+        .collect()
+}
+
+/// OpenFGA object for a feature name
+fn feature_object(feature: &str) -> String {
+    format!("feature:{}", feature)
+}
+
 fn detect_deletions(
     prev_features: &HashSet<String>,
     latest_features: &HashSet<String>,
@@ -172,12 +284,11 @@ fn detect_additions(
 /// Fetch tuples for specific features only (much more efficient than fetching all!)
 async fn fetch_tuples_for_features(
     client: &HttpClient,
-    openfga_url: &str,
-    store_id: &str,
+    fga_client: &OpenFgaClient,
     features: &[String],
-) -> Result<Vec<serde_json::Value>> {
+) -> Vec<Tuple> {
     if features.is_empty() {
-        return Ok(vec![]);
+        return vec![];
     }
 
     tracing::debug!(
@@ -185,35 +296,17 @@ async fn fetch_tuples_for_features(
         features.len()
     );
 
-    let read_url = format!("{}/stores/{}/read", openfga_url, store_id);
-
-    #[derive(serde::Deserialize)]
-    struct ReadResponse {
-        tuples: Vec<serde_json::Value>,
-    }
-
     let mut all_tuples = Vec::new();
 
-    // Fetch tuples for each feature
+    // Fetch tuples for each feature (the client follows pagination)
     for feature in features {
-        let read_request = serde_json::json!({
-            "tuple_key": {
-                "object": format!("feature:{}", feature)
-            }
-        });
-
-        match client.post(&read_url).json(&read_request).send().await {
-            Ok(response) if response.status().is_success() => {
-                let result: ReadResponse = response.json().await?;
-                all_tuples.extend(result.tuples);
-            }
-            Ok(response) => {
-                let error = response.text().await.unwrap_or_default();
-                tracing::warn!("Failed to fetch tuples for feature {}: {}", feature, error);
-            }
-            Err(e) => {
-                tracing::warn!("Request failed for feature {}: {}", feature, e);
-            }
+        let filter = TupleFilter {
+            object: Some(feature_object(feature)),
+            ..Default::default()
+        };
+        match fga_client.read(client, &filter).await {
+            Ok(tuples) => all_tuples.extend(tuples),
+            Err(e) => tracing::warn!("Failed to fetch tuples for feature {}: {}", feature, e),
         }
     }
 
@@ -222,34 +315,43 @@ async fn fetch_tuples_for_features(
         all_tuples.len(),
         features.len()
     );
-    Ok(all_tuples)
+    all_tuples
 }
 
-/// Migrate ALL feature renames in a single batched API call
+/// Migrate ALL feature renames in as few writes as the per-write tuple limit
+/// allows, returning the number of tuples migrated (or that would be, when
+/// `dry_run` is set). Each migrated tuple is one delete plus one write, so a
+/// write carries at most half the limit in tuples.
+///
+/// Every chunk that lands is recorded in `applied`, so a later failure only
+/// rolls back what was actually written.
 async fn migrate_all_feature_tuples(
     client: &HttpClient,
-    openfga_url: &str,
-    store_id: &str,
+    fga_client: &OpenFgaClient,
     renames: &[(String, String)],
-    all_tuples: &[serde_json::Value],
-) -> Result<()> {
-    tracing::info!(
-        "Migrating {} feature renames in single batch",
-        renames.len()
-    );
-
-    let mut all_deletes = Vec::new();
-    let mut all_writes = Vec::new();
+    all_tuples: &[Tuple],
+    dry_run: bool,
+    applied: &mut Vec<AppliedWrite>,
+) -> Result<usize> {
+    tracing::info!("Migrating {} feature renames", renames.len());
+
+    // (old tuple, renamed tuple) pairs
+    let mut migrations = Vec::new();
     let mut total_tuples = 0;
 
     // Process ALL renames and build combined deletes/writes
     for (old_feature, new_feature) in renames {
         tracing::debug!("Processing rename: {} → {}", old_feature, new_feature);
 
+        // OpenFGA returns full objects, e.g. feature:reports
+        let old_object = feature_object(old_feature);
+        let new_object = feature_object(new_feature);
+
         // Filter tuples for this specific rename
-        let tuples_to_migrate: Vec<&serde_json::Value> = all_tuples
+        let tuples_to_migrate: Vec<&TupleKey> = all_tuples
             .iter()
-            .filter(|t| t["key"]["object"].as_str() == Some(old_feature.as_str()))
+            .map(|t| &t.key)
+            .filter(|key| key.object == old_object)
             .collect();
 
         if tuples_to_migrate.is_empty() {
@@ -266,77 +368,77 @@ async fn migrate_all_feature_tuples(
         total_tuples += tuples_to_migrate.len();
 
         // Add to combined batch
-        for tuple in tuples_to_migrate {
-            let user = tuple["key"]["user"].as_str().unwrap();
-            let relation = tuple["key"]["relation"].as_str().unwrap();
-
-            all_deletes.push(serde_json::json!({
-                "user": user,
-                "relation": relation,
-                "object": old_feature
-            }));
-
-            all_writes.push(serde_json::json!({
-                "user": user,
-                "relation": relation,
-                "object": new_feature
-            }));
+        for key in tuples_to_migrate {
+            if dry_run {
+                tracing::info!(
+                    "[dry run] would migrate {} {} {} → {}",
+                    key.user,
+                    key.relation,
+                    old_feature,
+                    new_feature
+                );
+            }
+
+            migrations.push((
+                key.clone(),
+                TupleKey::new(
+                    key.user.as_str(),
+                    key.relation.as_str(),
+                    new_object.as_str(),
+                ),
+            ));
         }
     }
 
-    if all_deletes.is_empty() {
+    if migrations.is_empty() {
         tracing::info!("No tuples to migrate across all renames");
-        return Ok(());
+        return Ok(0);
+    }
+
+    if dry_run {
+        tracing::info!(
+            "[dry run] would migrate {} tuples across {} renames",
+            total_tuples,
+            renames.len()
+        );
+        return Ok(total_tuples);
     }
 
-    // Send ONE MASSIVE batched request for ALL renames
     tracing::info!(
         "Sending batch migration: {} tuples across {} renames",
         total_tuples,
         renames.len()
     );
 
-    let write_url = format!("{}/stores/{}/write", openfga_url, store_id);
-    let result = client
-        .post(&write_url)
-        .json(&serde_json::json!({
-            "deletes": {
-                "tuple_keys": all_deletes
-            },
-            "writes": {
-                "tuple_keys": all_writes
-            }
-        }))
-        .send()
-        .await?;
-
-    if result.status().is_success() {
-        tracing::info!(
-            "✅ Successfully migrated {} tuples across {} renames in single batch!",
-            total_tuples,
-            renames.len()
-        );
-    } else {
-        let error_text = result.text().await?;
-        tracing::error!("Failed to migrate tuples: {}", error_text);
-        return Err(anyhow::anyhow!("Batch migration failed: {}", error_text));
+    for chunk in migrations.chunks(OPENFGA_MAX_TUPLES_PER_WRITE / 2) {
+        let (deletes, writes): (Vec<TupleKey>, Vec<TupleKey>) = chunk.iter().cloned().unzip();
+        if let Err(e) = fga_client.write(client, &writes, &deletes).await {
+            tracing::error!("Failed to migrate tuples: {}", e);
+            return Err(anyhow::anyhow!("Batch migration failed: {}", e));
+        }
+        applied.push(AppliedWrite { writes, deletes });
     }
+    tracing::info!(
+        "✅ Successfully migrated {} tuples across {} renames",
+        total_tuples,
+        renames.len()
+    );
 
-    Ok(())
+    Ok(total_tuples)
 }
 
-/// Cleanup ALL deleted features in a single batched API call
+/// Cleanup ALL deleted features in as few writes as the per-write tuple
+/// limit allows, returning the number of tuples deleted (or that would be,
+/// when `dry_run` is set). Each chunk that lands is recorded in `applied`.
 async fn cleanup_all_feature_tuples(
     client: &HttpClient,
-    openfga_url: &str,
-    store_id: &str,
+    fga_client: &OpenFgaClient,
     deleted_features: &[String],
-    all_tuples: &[serde_json::Value],
-) -> Result<()> {
-    tracing::info!(
-        "Cleaning up {} deleted features in single batch",
-        deleted_features.len()
-    );
+    all_tuples: &[Tuple],
+    dry_run: bool,
+    applied: &mut Vec<AppliedWrite>,
+) -> Result<usize> {
+    tracing::info!("Cleaning up {} deleted features", deleted_features.len());
 
     let mut all_delete_keys = Vec::new();
     let mut total_tuples = 0;
@@ -346,9 +448,11 @@ async fn cleanup_all_feature_tuples(
         tracing::debug!("Processing deletion: {}", feature);
 
         // Filter tuples for this feature
-        let tuples_to_delete: Vec<&serde_json::Value> = all_tuples
+        let object = feature_object(feature);
+        let tuples_to_delete: Vec<&TupleKey> = all_tuples
             .iter()
-            .filter(|t| t["key"]["object"].as_str() == Some(feature.as_str()))
+            .map(|t| &t.key)
+            .filter(|key| key.object == object)
             .collect();
 
         if tuples_to_delete.is_empty() {
@@ -364,45 +468,54 @@ async fn cleanup_all_feature_tuples(
         total_tuples += tuples_to_delete.len();
 
         // Add to combined delete batch
-        for tuple in tuples_to_delete {
-            all_delete_keys.push(&tuple["key"]);
+        for key in tuples_to_delete {
+            if dry_run {
+                tracing::info!(
+                    "[dry run] would delete {} {} {}",
+                    key.user,
+                    key.relation,
+                    key.object
+                );
+            }
+            all_delete_keys.push(key.clone());
         }
     }
 
     if all_delete_keys.is_empty() {
         tracing::info!("No tuples to delete across all deleted features");
-        return Ok(());
+        return Ok(0);
+    }
+
+    if dry_run {
+        tracing::info!(
+            "[dry run] would delete {} tuples across {} deleted features",
+            total_tuples,
+            deleted_features.len()
+        );
+        return Ok(total_tuples);
     }
 
-    // Send ONE MASSIVE batched delete for ALL deleted features
     tracing::info!(
         "Sending batch cleanup: {} tuples across {} deleted features",
         total_tuples,
         deleted_features.len()
     );
 
-    let write_url = format!("{}/stores/{}/write", openfga_url, store_id);
-    let result = client
-        .post(&write_url)
-        .json(&serde_json::json!({
-            "deletes": {
-                "tuple_keys": all_delete_keys
-            }
-        }))
-        .send()
-        .await?;
-
-    if result.status().is_success() {
-        tracing::info!(
-            "✅ Successfully cleaned up {} tuples across {} deleted features in single batch!",
-            total_tuples,
-            deleted_features.len()
-        );
-    } else {
-        let error_text = result.text().await?;
-        tracing::error!("Failed to cleanup tuples: {}", error_text);
-        return Err(anyhow::anyhow!("Batch cleanup failed: {}", error_text));
+    for chunk in all_delete_keys.chunks(OPENFGA_MAX_TUPLES_PER_WRITE) {
+        if let Err(e) = fga_client.write(client, &[], chunk).await {
+            tracing::error!("Failed to cleanup tuples: {}", e);
+            return Err(anyhow::anyhow!("Batch cleanup failed: {}", e));
+        }
+        applied.push(AppliedWrite {
+            writes: Vec::new(),
+            deletes: chunk.to_vec(),
+        });
     }
+    tracing::info!(
+        "✅ Successfully cleaned up {} tuples across {} deleted features",
+        total_tuples,
+        deleted_features.len()
+    );
 
-    Ok(())
+    Ok(total_tuples)
 }
@@ -0,0 +1,176 @@
+// Health Endpoints
+// Liveness and readiness probes for orchestrators (no auth middleware)
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::auth::{self, AppState};
+
+/// Upper bound for each dependency probe so /readyz stays fast
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub status: String,
+    pub checks: BTreeMap<&'static str, String>, // dependency -> "ok" or error
+}
+
+/// Liveness: the process is up and serving requests
+pub async fn healthz() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Readiness: Redis, OpenFGA and the JWKS endpoint are all reachable
+///
+/// Returns 503 listing the failing dependencies so the pod is taken out of
+/// rotation instead of serving requests that would fail.
+pub async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let (redis, openfga, jwks) = tokio::join!(
+        probe(ping_redis(&state)),
+        probe(http_get(
+            &state,
+            format!("{}/healthz", state.fga_client.url)
+        )),
+        probe(all_jwks_reachable(&state)),
+    );
+
+    let mut checks = BTreeMap::new();
+    let mut ready = true;
+    for (name, result) in [("redis", redis), ("openfga", openfga), ("jwks", jwks)] {
+        let outcome = match result {
+            Ok(()) => "ok".to_string(),
+            Err(e) => {
+                tracing::warn!("Readiness check failed for {}: {}", name, e);
+                ready = false;
+                e
+            }
+        };
+        checks.insert(name, outcome);
+    }
+
+    let (status, label) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+    };
+
+    (
+        status,
+        Json(ReadinessResponse {
+            status: label.to_string(),
+            checks,
+        }),
+    )
+}
+
+/// Outcome of the startup self-check, per dependency
+#[derive(Debug)]
+pub struct SelfCheckReport {
+    pub checks: BTreeMap<&'static str, Result<(), String>>,
+}
+
+impl SelfCheckReport {
+    pub fn passed(&self) -> bool {
+        self.checks.values().all(Result::is_ok)
+    }
+}
+
+/// Check once at startup, with the clients already built, that Redis
+/// answers, the OpenFGA store exists and every issuer's JWKS yields keys.
+/// Each outcome is logged; failures are reported, never panicked on.
+pub async fn startup_self_check(state: &AppState) -> SelfCheckReport {
+    let (redis, openfga, jwks) = tokio::join!(
+        probe(ping_redis(state)),
+        probe(http_get(
+            state,
+            format!(
+                "{}/stores/{}",
+                state.fga_client.url, state.fga_client.store_id
+            )
+        )),
+        probe(all_jwks_have_keys(state)),
+    );
+
+    let checks = BTreeMap::from([("redis", redis), ("openfga", openfga), ("jwks", jwks)]);
+    for (name, result) in &checks {
+        match result {
+            Ok(()) => tracing::info!("Startup check passed for {}", name),
+            Err(e) => tracing::error!("Startup check failed for {}: {}", name, e),
+        }
+    }
+    SelfCheckReport { checks }
+}
+
+async fn probe(check: impl std::future::Future<Output = Result<(), String>>) -> Result<(), String> {
+    tokio::time::timeout(PROBE_TIMEOUT, check)
+        .await
+        .map_err(|_| "timed out".to_string())?
+}
+
+async fn ping_redis(state: &AppState) -> Result<(), String> {
+    let mut conn = state
+        .redis_client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    redis::cmd("PING")
+        .query_async::<String>(&mut conn)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Every configured issuer's JWKS must answer; errors name the failing URLs
+async fn all_jwks_reachable(state: &AppState) -> Result<(), String> {
+    let mut failures = Vec::new();
+    // Issuers with a static JWKS have no endpoint to depend on
+    for issuer in state.jwt_issuers.values().filter(|i| !i.has_static_keys()) {
+        if let Err(e) = http_get(state, issuer.jwks_url.clone()).await {
+            failures.push(format!("{}: {}", issuer.jwks_url, e));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+/// Fetch and decode every issuer's JWKS, which must contain a usable key
+async fn all_jwks_have_keys(state: &AppState) -> Result<(), String> {
+    let mut failures = Vec::new();
+    for issuer in state.jwt_issuers.values().filter(|i| !i.has_static_keys()) {
+        match auth::fetch_jwks(&state.http_client, &issuer.jwks_url).await {
+            Ok(keys) if keys.is_empty() => {
+                failures.push(format!("{}: no usable keys", issuer.jwks_url))
+            }
+            Ok(_) => {}
+            Err(e) => failures.push(format!("{}: {}", issuer.jwks_url, e)),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+async fn http_get(state: &AppState, url: String) -> Result<(), String> {
+    let response = state
+        .http_client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("status {}", response.status()))
+    }
+}
@@ -0,0 +1,188 @@
+// Token Introspection
+// Validates opaque (non-JWT) access tokens against the IdP's OAuth 2.0
+// introspection endpoint (RFC 7662)
+
+use jsonwebtoken::errors::{Error, ErrorKind};
+use moka::future::Cache;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Semaphore;
+
+use crate::auth::{AppState, Audience, Claims};
+
+/// How long an introspection result is reused by default
+pub const DEFAULT_INTROSPECTION_CACHE_TTL_SECS: u64 = 30;
+
+/// How long an inactive or invalid token is remembered by default
+pub const DEFAULT_INTROSPECTION_NEGATIVE_CACHE_TTL_SECS: u64 = 5;
+
+/// Calls to the introspection endpoint allowed in flight at once by default
+pub const DEFAULT_INTROSPECTION_MAX_IN_FLIGHT: usize = 32;
+
+/// Introspection endpoint and the client credentials it requires
+#[derive(Clone, Debug)]
+pub struct IntrospectionConfig {
+    pub url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    permits: Arc<Semaphore>, // Bounds concurrent calls, so unknown tokens can't flood the IdP
+}
+
+impl IntrospectionConfig {
+    pub fn new(
+        url: String,
+        client_id: String,
+        client_secret: String,
+        max_in_flight: usize,
+    ) -> Self {
+        Self {
+            url,
+            client_id,
+            client_secret,
+            permits: Arc::new(Semaphore::new(max_in_flight.max(1))),
+        }
+    }
+}
+
+/// Introspection results keyed by SHA-256 of the token, so raw tokens are
+/// never held in memory longer than the request. Rejections are kept too
+/// (briefly), so repeating a bad token doesn't reach the IdP each time.
+#[derive(Clone)]
+pub struct IntrospectionCache {
+    active: Cache<String, Claims>,
+    rejected: Cache<String, ErrorKind>,
+}
+
+pub fn build_introspection_cache(ttl: Duration, negative_ttl: Duration) -> IntrospectionCache {
+    IntrospectionCache {
+        active: Cache::builder().time_to_live(ttl).build(),
+        rejected: Cache::builder().time_to_live(negative_ttl).build(),
+    }
+}
+
+#[derive(Deserialize)]
+struct IntrospectionResponse {
+    active: bool,
+    sub: Option<String>,
+    iss: Option<String>,
+    exp: Option<i64>,
+    nbf: Option<i64>,
+    aud: Option<Audience>,
+    scope: Option<String>,
+}
+
+/// Validate an opaque token through the introspection endpoint. Inactive
+/// tokens, transport failures and issuer or audience mismatches are all
+/// invalid.
+pub async fn introspect(
+    state: &AppState,
+    config: &IntrospectionConfig,
+    token: &str,
+) -> Result<Claims, Error> {
+    let cache = &state.introspection_cache;
+    let key = hex::encode(Sha256::digest(token.as_bytes()));
+    if let Some(claims) = cache.active.get(&key).await {
+        if claims.exp > now() {
+            return Ok(claims);
+        }
+        cache.active.invalidate(&key).await;
+    }
+    if let Some(kind) = cache.rejected.get(&key).await {
+        return Err(kind.into());
+    }
+
+    let introspected = {
+        let _permit = config
+            .permits
+            .acquire()
+            .await
+            .expect("introspection semaphore is never closed");
+        request(state, config, token).await?
+    };
+    // Transport failures above aren't cached; they say nothing about the token
+    match claims_from(state, introspected) {
+        Ok(claims) => {
+            cache.active.insert(key, claims.clone()).await;
+            Ok(claims)
+        }
+        Err(e) => {
+            cache.rejected.insert(key, e.kind().clone()).await;
+            Err(e)
+        }
+    }
+}
+
+async fn request(
+    state: &AppState,
+    config: &IntrospectionConfig,
+    token: &str,
+) -> Result<IntrospectionResponse, Error> {
+    let response = state
+        .http_client
+        .post(&config.url)
+        .basic_auth(&config.client_id, Some(&config.client_secret))
+        .form(&[("token", token), ("token_type_hint", "access_token")])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            tracing::error!("Token introspection failed: {}", e);
+            Error::from(ErrorKind::InvalidToken)
+        })?;
+    response.json().await.map_err(|e| {
+        tracing::error!("Invalid introspection response: {}", e);
+        Error::from(ErrorKind::InvalidToken)
+    })
+}
+
+/// Claims of an active token, checked like a JWT's would be
+fn claims_from(state: &AppState, introspected: IntrospectionResponse) -> Result<Claims, Error> {
+    if !introspected.active {
+        return Err(ErrorKind::InvalidToken.into());
+    }
+    let (Some(sub), Some(exp)) = (introspected.sub, introspected.exp) else {
+        tracing::warn!("Active introspection result without sub/exp");
+        return Err(ErrorKind::InvalidToken.into());
+    };
+    if exp <= now() {
+        return Err(ErrorKind::ExpiredSignature.into());
+    }
+    let Some(iss) = introspected
+        .iss
+        .filter(|iss| state.jwt_issuers.contains_key(iss))
+    else {
+        tracing::warn!("Introspected token from an unaccepted issuer");
+        return Err(ErrorKind::InvalidIssuer.into());
+    };
+    if let Some(accepted) = &state.jwt_audience {
+        let matches = match &introspected.aud {
+            Some(Audience::Single(aud)) => accepted.contains(aud),
+            Some(Audience::Multiple(auds)) => auds.iter().any(|aud| accepted.contains(aud)),
+            None => false,
+        };
+        if !matches {
+            return Err(ErrorKind::InvalidAudience.into());
+        }
+    }
+
+    Ok(Claims {
+        sub,
+        iss,
+        exp,
+        nbf: introspected.nbf,
+        aud: introspected.aud,
+        scope: introspected.scope,
+        roles: None,
+        org_id: None,
+        jti: None,
+    })
+}
+
+pub(crate) fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
@@ -1,3 +1,15 @@
+pub mod admin;
+pub mod audit;
 pub mod auth;
+pub mod circuit_breaker;
+pub mod concurrency;
+pub mod config;
 pub mod feature_sync;
+pub mod health;
+pub mod introspection;
+pub mod openfga;
+pub mod otel;
+pub mod rules_watcher;
+pub mod telemetry;
 pub mod webhooks;
+pub mod ws_proxy;
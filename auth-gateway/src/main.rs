@@ -1,10 +1,15 @@
-use auth_gateway::auth;
+use auth_gateway::audit::AuditQueue;
+use auth_gateway::concurrency::ConcurrencyLimiter;
+use auth_gateway::config::{Config, RuleTargets};
+use auth_gateway::introspection::build_introspection_cache;
+use auth_gateway::{auth, health, otel, rules_watcher};
 
-use auth::{AppState, OpenFgaClient};
-use axum::http::header;
-use moka::future::Cache;
-use reqwest::Client as HttpClient;
+use arc_swap::ArcSwap;
+use auth::{AppState, JwtIssuer, OpenFgaClient};
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -13,95 +18,312 @@ async fn main() {
     // Initialize dotenv
     dotenv::dotenv().ok();
 
+    // `--check-rules <path>`: validate a rules file offline (for CI) and exit
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--check-rules") {
+        let Some(path) = args.get(i + 1) else {
+            eprintln!("Usage: auth-gateway --check-rules <path>");
+            std::process::exit(2);
+        };
+        std::process::exit(check_rules(path).await);
+    }
+
     // Initialize tracing
+    // Spans are also exported over OTLP when a collector is configured
+    let otel_provider = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(|_| otel::otlp_provider().expect("Failed to build OTLP exporter"));
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "auth_gateway=debug,tower_http=debug".into()),
         )
         .with(tracing_subscriber::fmt::layer())
+        .with(otel_provider.as_ref().map(otel::layer))
         .init();
 
+    // Register the Prometheus recorder before any metrics are emitted
+    auth_gateway::telemetry::install_recorder();
+
+    // Required and validated settings; report every problem before exiting
+    let config = Config::from_env().unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    });
+
     // Initialize clients
-    let http_client = HttpClient::new();
-    let fga_url = std::env::var("OPENFGA_URL").expect("OPENFGA_URL must be set");
-    let fga_store_id = std::env::var("OPENFGA_STORE_ID").expect("OPENFGA_STORE_ID must be set");
-    let fga_client = OpenFgaClient::new(fga_url.clone(), fga_store_id.clone());
-    let issuer_url = std::env::var("ZITADEL_ISSUER_URL").expect("ZITADEL_ISSUER_URL must be set");
-    let jwks_url = format!("{}/oauth/v2/keys", issuer_url);
-    let zitadel_api_url = std::env::var("ZITADEL_API_URL").expect("ZITADEL_API_URL must be set");
+    // Same settings, separate pools: upstream load can't exhaust authz connections
+    let http_client = config.http.build().expect("Failed to build HTTP client");
+    let upstream_client = config
+        .http
+        .build_upstream()
+        .expect("Failed to build upstream HTTP client");
+    let upstream_h2_client = config
+        .http
+        .build_upstream_h2()
+        .expect("Failed to build upstream HTTP/2 client");
+    // Stop calling OpenFGA after repeated failures; while open, checks
+    // resolve to OPENFGA_BREAKER_OPEN_DECISION ("deny" unless set to "allow")
+    let fga_client =
+        OpenFgaClient::new(config.openfga_url.clone(), config.openfga_store_id.clone())
+            .with_retry(config.openfga_retry)
+            .with_breaker(config.openfga_breaker, config.openfga_breaker_open_decision)
+            .with_relations(config.openfga_relation_map)
+            .with_model_id(config.openfga_model_id)
+            .with_consistency(config.openfga_consistency);
 
-    let upstream_url =
-        std::env::var("UPSTREAM_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+    // A static JWKS replaces every issuer's endpoint (local and offline runs)
+    let static_jwks = config.jwks_file.as_ref().map(|path| {
+        let keys = auth::load_jwks_file(path).expect("JWKS_FILE must be a readable JWKS");
+        tracing::info!("Using {} static JWKS keys from {}", keys.len(), path);
+        keys
+    });
+
+    // Trusted issuers: `iss=jwks_url` pairs, or a bare `iss` whose JWKS is
+    // JWKS_URL, else discovered from its OpenID configuration, else Zitadel's
+    // standard path. Defaults to ZITADEL_ISSUER_URL alone.
+    let mut jwt_issuers: HashMap<String, JwtIssuer> = HashMap::new();
+    for entry in &config.jwt_issuers {
+        let (iss, jwks_url) = match entry.split_once('=') {
+            Some((iss, jwks_url)) => (iss.to_string(), jwks_url.to_string()),
+            // Never fetched, so not worth discovering
+            None if static_jwks.is_some() => (
+                entry.to_string(),
+                format!("{}{}", entry, auth::ZITADEL_JWKS_PATH),
+            ),
+            None => {
+                let jwks_url =
+                    auth::resolve_jwks_url(&http_client, entry, config.jwks_url.as_deref()).await;
+                (entry.to_string(), jwks_url)
+            }
+        };
+        tracing::info!("Trusting issuer {} with JWKS at {}", iss, jwks_url);
+        let issuer = JwtIssuer::new(jwks_url, auth::DEFAULT_JWKS_MIN_REFRESH_INTERVAL);
+        let issuer = match &static_jwks {
+            Some(keys) => issuer.with_static_keys(keys.clone()),
+            None => issuer,
+        };
+        jwt_issuers.insert(iss, issuer);
+    }
+    // Introspection results for opaque tokens, rejections included
+    let introspection_cache = build_introspection_cache(
+        config.introspection_cache_ttl,
+        config.introspection_negative_cache_ttl,
+    );
+
+    // Skip re-verifying a recently seen token (unset or 0 = verify every request)
+    let token_cache = config.token_cache_ttl.map(auth::build_token_cache);
+
+    for action in config.method_actions.values() {
+        if let Err(e) = fga_client.relation_for(Some(action)) {
+            tracing::warn!("Default action for rules without one is unusable: {}", e);
+        }
+    }
+    // Start in maintenance (503 for proxied routes); toggled via /admin/maintenance
+    if config.maintenance_mode {
+        tracing::warn!("MAINTENANCE_MODE enabled, proxied routes return 503");
+    }
+
+    // Fail fast on malformed rules
+    let access_rules_path = "access_rules.json".to_string();
+    let known = auth::RuleValidation::new(&fga_client.relations, &config.upstream_services);
+    if let Err(e) = auth::validate_rules(&access_rules_path, &known).await {
+        tracing::error!("Invalid {}: {}", access_rules_path, e);
+        std::process::exit(1);
+    }
 
     // Initialize Redis (Valkey)
-    let redis_url = std::env::var("REDIS_URL").expect("REDIS_URL must be set");
-    let redis_client = redis::Client::open(redis_url).expect("Invalid Redis URL");
+    let redis_client = redis::Client::open(config.redis_url.as_str()).expect("Invalid Redis URL");
 
-    // Initialize Cache (30s TTL)
-    let cache = Cache::builder()
-        .time_to_live(Duration::from_secs(30))
-        .build();
+    if config.webhook_signing_secret.is_none() {
+        tracing::warn!("WEBHOOK_SIGNING_SECRET not set, all webhook calls will be rejected");
+    }
+    // Require signed, recent X-Zitadel-Timestamp headers so webhooks can't be replayed
+    let webhook_timestamp_tolerance = config
+        .webhook_timestamp_tolerance_secs
+        .map(Duration::from_secs);
+    if config.upstream_secret.is_none() {
+        tracing::warn!("UPSTREAM_GATEWAY_SECRET not set, upstream can't verify proxied traffic");
+    }
+    // Never enable in production: tells clients which rule matched
+    if config.debug_headers {
+        tracing::warn!("DEBUG_HEADERS enabled, responses reveal matched access rules");
+    }
+    // Requests one user may have in flight at once (unset = unlimited)
+    let concurrency_limiter = config
+        .user_max_in_flight
+        .map(|limit| Arc::new(ConcurrencyLimiter::new(limit)));
 
-    let jwks_cache = Cache::builder()
-        .time_to_live(Duration::from_secs(24 * 60 * 60))
-        .build();
+    // Initialize authz cache (grants 30s, denials 5s by default)
+    let cache = auth::build_authz_cache(config.authz_cache_max_capacity);
+    // Stale-while-error: serve last-known grants (1h by default) while OpenFGA fails
+    let stale_grants = config.stale_grant_ttl.map(auth::build_stale_grant_cache);
+    let list_objects_cache = auth::build_list_objects_cache(config.authz_cache_ttl);
+    let jwks_cache = auth::build_jwks_cache(config.jwks_cache_max_capacity);
 
     // Run feature migration BEFORE loading new rules
     // This ensures OpenFGA tuples are updated when features are renamed/deleted
+    // FEATURE_SYNC_DRY_RUN=true only logs what would change
     tracing::info!("Running feature migration check...");
-    if let Err(e) = auth_gateway::feature_sync::migrate_features(
+    match auth_gateway::feature_sync::migrate_features(
         &http_client,
-        &fga_url,
-        &fga_store_id.clone(),
+        &fga_client,
         "access_rules.json",      // Latest rules
         "access_rules_prev.json", // Previous rules (from CI/CD)
+        config.feature_sync_dry_run,
     )
     .await
     {
-        tracing::error!("Feature migration failed: {}", e);
-        // Continue anyway - migration failure shouldn't block startup
+        Ok(summary) => tracing::info!("Feature migration summary: {:?}", summary),
+        Err(e) => {
+            tracing::error!("Feature migration failed: {}", e);
+            // Continue anyway - migration failure shouldn't block startup
+        }
     }
 
     // Load access rules (from latest version)
-    let router = auth::load_access_rules("access_rules.json")
+    let router = auth::load_access_rules(&access_rules_path)
         .await
         .expect("Failed to load access rules");
 
+    let audit_queue = config.audit_sink.map(|sink| {
+        AuditQueue::spawn(
+            sink.build(&redis_client, &http_client),
+            config.audit_queue_capacity,
+        )
+    });
+
     let state = AppState {
         http_client,
+        upstream_client,
+        upstream_h2_client,
         fga_client,
-        router,
+        router: Arc::new(ArcSwap::new(router)),
+        access_rules_path,
+        default_policy: config.default_policy,
+        unmatched_status: config.unmatched_status,
         cache,
+        authz_cache_ttl: config.authz_cache_ttl,
+        authz_negative_cache_ttl: config.authz_negative_cache_ttl,
+        authz_cache_verify_rate: config.authz_cache_verify_rate,
+        stale_grants,
+        model_error_status: config.model_error_status,
         jwks_cache,
-        jwks_url,
-        zitadel_api_url,
-        openfga_url: fga_url,
+        jwt_issuers: Arc::new(jwt_issuers),
+        jwt_audience: config.jwt_audience,
+        jwt_algorithms: config.jwt_algorithms,
+        jwt_leeway_secs: config.jwt_leeway_secs,
+        jwks_retry: config.jwks_retry,
+        introspection: config.introspection,
+        introspection_cache,
+        token_cache,
+        token_denylist: config.token_denylist,
+        zitadel_api_url: config.zitadel_api_url,
+        openfga_url: config.openfga_url,
         redis_client,
-        upstream_url,
+        rate_limit_fail_mode: config.rate_limit_fail_mode,
+        trusted_proxies: config.trusted_proxies,
+        trusted_proxy_hops: config.trusted_proxy_hops,
+        concurrency_limiter,
+        openfga_context_headers: config.openfga_context_headers,
+        method_actions: Arc::new(config.method_actions),
+        tenant_source: config.tenant_source,
+        upstream_url: config.upstream_url,
+        services: Arc::new(config.upstream_services),
+        http2_services: Arc::new(config.upstream_http2_services),
+        max_body_bytes: config.max_body_bytes,
+        max_url_len: config.max_url_len,
+        upstream_timeout: config.upstream_timeout,
+        upstream_retry_attempts: config.upstream_retry_attempts,
+        maintenance_mode: Arc::new(AtomicBool::new(config.maintenance_mode)),
+        maintenance_retry_after_secs: config.maintenance_retry_after_secs,
+        webhook_signing_secret: config.webhook_signing_secret,
+        webhook_timestamp_tolerance,
+        user_registration: config.user_registration,
+        admin_secret: config.admin_secret,
+        upstream_secret: config.upstream_secret,
+        strip_upstream_auth: config.strip_upstream_auth,
+        forward_header_mode: config.forward_header_mode,
+        forward_headers: config.forward_headers,
+        response_compression: config.response_compression,
+        access_log: config.access_log,
+        debug_headers: config.debug_headers,
+        audit_queue,
+        audit_allow_sample_rate: config.audit_allow_sample_rate,
+        listable_objects: config.listable_objects,
+        list_objects_cache,
     };
 
-    // Configure CORS
-    let allowed_origins_str = std::env::var("ALLOWED_ORIGINS")
-        .unwrap_or_else(|_| "http://localhost:3000,http://localhost:8080".to_string());
+    // Surface misconfigured dependencies now rather than on the first request
+    if config.startup_healthcheck {
+        let report = health::startup_self_check(&state).await;
+        if !report.passed() && config.startup_healthcheck_abort {
+            tracing::error!("Startup self-check failed, exiting");
+            std::process::exit(1);
+        }
+    }
 
-    let allowed_origins: Vec<header::HeaderValue> = allowed_origins_str
-        .split(',')
-        .map(|s| {
-            s.trim()
-                .parse::<header::HeaderValue>()
-                .expect("Invalid origin header value")
-        })
-        .collect();
+    // Pick up access rule edits without a restart (POST /admin/reload-rules also works)
+    let _rules_watcher = match rules_watcher::spawn_rules_watcher(state.clone()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            tracing::warn!("Access rules hot reload disabled: {}", e);
+            None
+        }
+    };
+
+    // Pattern origins for preview environments; leave unset in production
+    let cors = config.cors;
+    if !cors.allowed_origin_patterns.is_empty() {
+        tracing::warn!(
+            "CORS pattern matching enabled: {:?}",
+            cors.allowed_origin_patterns
+        );
+    }
 
     // Build app with routes using helper function (for testability)
-    let app = auth::create_router(state, allowed_origins);
+    let app = auth::create_router(state, cors);
 
     // Run the server
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     tracing::info!("listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Peer addresses feed the IP rate limit on public routes
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 // function content moved to auth.rs
+
+/// Validate and load a rules file without touching the network, printing a
+/// report. Returns the process exit code.
+async fn check_rules(path: &str) -> i32 {
+    let targets = match RuleTargets::from_env() {
+        Ok(targets) => targets,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let known = auth::RuleValidation::new(&targets.relations, &targets.services);
+    let result = match auth::validate_rules(path, &known).await {
+        Ok(()) => auth::load_access_rules(path).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(()) => {
+            println!("{}: OK", path);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: INVALID\n{}", path, e);
+            1
+        }
+    }
+}
@@ -0,0 +1,560 @@
+// OpenFGA Client
+// Typed calls to the OpenFGA HTTP API, shared by auth, webhooks and feature sync
+
+use reqwest::header::HeaderMap;
+use reqwest::Client as HttpClient;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::auth::{CheckContext, DEFAULT_RELATION};
+use crate::circuit_breaker::{BreakerConfig, CircuitBreaker};
+
+/// Tuples requested per OpenFGA `/read` page (the server's maximum)
+pub const OPENFGA_READ_PAGE_SIZE: u32 = 100;
+
+/// Most tuples OpenFGA accepts in one `/write` (its default
+/// `maxTuplesPerWrite`), writes and deletes combined
+pub const OPENFGA_MAX_TUPLES_PER_WRITE: usize = 100;
+
+/// A relationship tuple, e.g. `user:1` is `viewer` of `feature:reports`
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TupleKey {
+    pub user: String,
+    pub relation: String,
+    pub object: String,
+}
+
+impl TupleKey {
+    pub fn new(
+        user: impl Into<String>,
+        relation: impl Into<String>,
+        object: impl Into<String>,
+    ) -> Self {
+        Self {
+            user: user.into(),
+            relation: relation.into(),
+            object: object.into(),
+        }
+    }
+}
+
+/// Partial tuple key for `/read`. Unset fields match anything, and an
+/// object of just `type:` matches every object of that type.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TupleFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object: Option<String>,
+}
+
+/// A stored tuple as returned by `/read`
+#[derive(Clone, Debug, Deserialize)]
+pub struct Tuple {
+    pub key: TupleKey,
+    #[serde(default)]
+    pub timestamp: Option<String>,
+}
+
+/// How fresh a `/check` answer must be. Latency-optimised checks may be
+/// served from OpenFGA's cache, so can briefly miss a tuple just written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Consistency {
+    MinimizeLatency,
+    HigherConsistency,
+}
+
+impl std::str::FromStr for Consistency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "minimize_latency" => Ok(Self::MinimizeLatency),
+            "higher_consistency" => Ok(Self::HigherConsistency),
+            other => Err(format!("Invalid consistency: {}", other)),
+        }
+    }
+}
+
+/// A single `/check` exchange, as sent and as answered
+#[derive(Debug)]
+pub struct CheckExplanation {
+    pub request: serde_json::Value,
+    pub status: reqwest::StatusCode,
+    /// Parsed JSON, or the raw text if OpenFGA didn't answer with JSON
+    pub response: serde_json::Value,
+}
+
+#[derive(Debug)]
+pub enum OpenFgaError {
+    /// The circuit breaker is open, so OpenFGA wasn't called
+    CircuitOpen,
+    /// OpenFGA couldn't be reached, or its response couldn't be read
+    Request(reqwest::Error),
+    /// OpenFGA answered with an error status
+    Status {
+        status: reqwest::StatusCode,
+        body: String,
+    },
+}
+
+impl OpenFgaError {
+    /// Connection failures and 5xx are worth retrying; 4xx are final
+    pub fn is_transient(&self) -> bool {
+        match self {
+            OpenFgaError::Request(_) => true,
+            OpenFgaError::Status { status, .. } => status.is_server_error(),
+            OpenFgaError::CircuitOpen => false,
+        }
+    }
+
+    /// A 4xx other than 404: OpenFGA rejected the request itself, usually an
+    /// unknown type or relation because the model and the rules drifted
+    pub fn is_model_error(&self) -> bool {
+        matches!(self, OpenFgaError::Status { status, .. }
+            if status.is_client_error() && *status != reqwest::StatusCode::NOT_FOUND)
+    }
+
+    /// Metric label for this failure
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OpenFgaError::CircuitOpen => "circuit_open",
+            OpenFgaError::Request(_) => "request",
+            _ if self.is_model_error() => "model",
+            OpenFgaError::Status { .. } => "status",
+        }
+    }
+}
+
+impl std::fmt::Display for OpenFgaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenFgaError::CircuitOpen => write!(f, "OpenFGA circuit breaker is open"),
+            OpenFgaError::Request(e) => write!(f, "OpenFGA request failed: {}", e),
+            OpenFgaError::Status { status, body } => {
+                write!(f, "OpenFGA returned {}: {}", status, body)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OpenFgaError {}
+
+impl From<reqwest::Error> for OpenFgaError {
+    fn from(e: reqwest::Error) -> Self {
+        OpenFgaError::Request(e)
+    }
+}
+
+#[derive(Clone)]
+pub struct OpenFgaClient {
+    pub url: String,
+    pub store_id: String,
+    pub retry: RetryPolicy,
+    /// Shared by every clone, so all requests see the same outage
+    pub breaker: Arc<CircuitBreaker>,
+    /// Decision returned without calling OpenFGA while the breaker is open
+    pub breaker_open_decision: bool,
+    /// Rule action -> OpenFGA relation (e.g. view -> can_view). Empty means
+    /// actions are used as relations verbatim.
+    pub relations: Arc<HashMap<String, String>>,
+    /// Authorization model every request is pinned to (None = the store's
+    /// latest model)
+    pub model_id: Option<String>,
+    /// Consistency for checks that don't ask for one (None = OpenFGA's
+    /// default, which minimises latency)
+    pub consistency: Option<Consistency>,
+}
+
+impl OpenFgaClient {
+    pub fn new(url: String, store_id: String) -> Self {
+        Self {
+            url,
+            store_id,
+            retry: RetryPolicy::default(),
+            breaker: Arc::new(CircuitBreaker::new("openfga", BreakerConfig::default())),
+            breaker_open_decision: false,
+            relations: Arc::new(HashMap::new()),
+            model_id: None,
+            consistency: None,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_breaker(mut self, config: BreakerConfig, open_decision: bool) -> Self {
+        self.breaker = Arc::new(CircuitBreaker::new("openfga", config));
+        self.breaker_open_decision = open_decision;
+        self
+    }
+
+    pub fn with_relations(mut self, relations: HashMap<String, String>) -> Self {
+        self.relations = Arc::new(relations);
+        self
+    }
+
+    pub fn with_model_id(mut self, model_id: Option<String>) -> Self {
+        self.model_id = model_id;
+        self
+    }
+
+    pub fn with_consistency(mut self, consistency: Option<Consistency>) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// OpenFGA relation for a rule action. Rules without an action check
+    /// `DEFAULT_RELATION`; with a mapping configured, an unmapped action is
+    /// an error rather than being passed through.
+    pub fn relation_for<'a>(&'a self, action: Option<&'a str>) -> Result<&'a str, String> {
+        match action {
+            None => Ok(DEFAULT_RELATION),
+            Some(action) if self.relations.is_empty() => Ok(action),
+            Some(action) => self
+                .relations
+                .get(action)
+                .map(String::as_str)
+                .ok_or_else(|| format!("No OpenFGA relation mapped for action '{}'", action)),
+        }
+    }
+
+    /// `/check` one tuple, retrying transient failures (a clean deny is final).
+    /// `consistency` overrides the client's default.
+    pub async fn check(
+        &self,
+        http: &HttpClient,
+        tuple: &TupleKey,
+        context: Option<&CheckContext>,
+        consistency: Option<Consistency>,
+    ) -> Result<bool, OpenFgaError> {
+        let body = self.check_body(tuple, context, consistency);
+
+        #[derive(Deserialize)]
+        struct CheckResponse {
+            #[serde(default)]
+            allowed: bool,
+        }
+
+        let response: CheckResponse = self
+            .guarded(self.post_with_retry(http, "check", &body))
+            .await?;
+        Ok(response.allowed)
+    }
+
+    /// `/check` once for diagnostics: no retries, no circuit breaker, and
+    /// OpenFGA's answer is returned as-is whatever its status
+    pub async fn explain_check(
+        &self,
+        http: &HttpClient,
+        tuple: &TupleKey,
+        context: Option<&CheckContext>,
+        consistency: Option<Consistency>,
+    ) -> Result<CheckExplanation, OpenFgaError> {
+        let request = self.check_body(tuple, context, consistency);
+        let mut headers = HeaderMap::new();
+        crate::otel::inject_trace_context(&mut headers);
+        let response = http
+            .post(self.endpoint("check"))
+            .headers(headers)
+            .json(&request)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        let response = serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text));
+        Ok(CheckExplanation {
+            request,
+            status,
+            response,
+        })
+    }
+
+    /// `/batch-check` several tuples in one round-trip. Outcomes line up with
+    /// `tuples`; a check OpenFGA reported an error for is `None`.
+    pub async fn batch_check(
+        &self,
+        http: &HttpClient,
+        tuples: &[TupleKey],
+    ) -> Result<Vec<Option<bool>>, OpenFgaError> {
+        // Correlation IDs are the index into `tuples`
+        let checks: Vec<serde_json::Value> = tuples
+            .iter()
+            .enumerate()
+            .map(|(i, tuple)| {
+                serde_json::json!({ "tuple_key": tuple, "correlation_id": i.to_string() })
+            })
+            .collect();
+
+        #[derive(Deserialize)]
+        struct BatchCheckResponse {
+            result: HashMap<String, serde_json::Value>,
+        }
+
+        let mut body = serde_json::json!({ "checks": checks });
+        self.pin_model(&mut body);
+        let batch: BatchCheckResponse = self.guarded(self.post(http, "batch-check", &body)).await?;
+
+        let mut outcomes = vec![None; tuples.len()];
+        for (correlation_id, outcome) in batch.result {
+            let Some(slot) = correlation_id
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| outcomes.get_mut(i))
+            else {
+                continue;
+            };
+            match outcome["allowed"].as_bool() {
+                Some(allowed) if outcome.get("error").is_none() => *slot = Some(allowed),
+                _ => tracing::warn!(
+                    "OpenFGA batch check {} errored: {}",
+                    correlation_id,
+                    outcome
+                ),
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// `/read` every tuple matching `filter`, following continuation tokens
+    /// to the last page
+    pub async fn read(
+        &self,
+        http: &HttpClient,
+        filter: &TupleFilter,
+    ) -> Result<Vec<Tuple>, OpenFgaError> {
+        #[derive(Deserialize)]
+        struct ReadResponse {
+            tuples: Vec<Tuple>,
+            #[serde(default)]
+            continuation_token: String,
+        }
+
+        let mut tuples = Vec::new();
+        let mut continuation_token = String::new();
+        loop {
+            let mut body = serde_json::json!({
+                "tuple_key": filter,
+                "page_size": OPENFGA_READ_PAGE_SIZE,
+            });
+            if !continuation_token.is_empty() {
+                body["continuation_token"] = continuation_token.into();
+            }
+            self.pin_model(&mut body);
+
+            let page: ReadResponse = self.post_with_retry(http, "read", &body).await?;
+            tuples.extend(page.tuples);
+            if page.continuation_token.is_empty() {
+                return Ok(tuples);
+            }
+            continuation_token = page.continuation_token;
+        }
+    }
+
+    /// `/write` tuple additions and deletions as one transaction
+    pub async fn write(
+        &self,
+        http: &HttpClient,
+        writes: &[TupleKey],
+        deletes: &[TupleKey],
+    ) -> Result<(), OpenFgaError> {
+        // OpenFGA rejects empty tuple_keys, so only send the non-empty halves
+        let mut body = serde_json::json!({});
+        if !writes.is_empty() {
+            body["writes"] = serde_json::json!({ "tuple_keys": writes });
+        }
+        if !deletes.is_empty() {
+            body["deletes"] = serde_json::json!({ "tuple_keys": deletes });
+        }
+        if body.as_object().is_some_and(|body| body.is_empty()) {
+            return Ok(());
+        }
+        self.pin_model(&mut body);
+
+        let _: serde_json::Value = self.post_with_retry(http, "write", &body).await?;
+        Ok(())
+    }
+
+    /// `/list-objects`: full IDs of every `object_type` object `user` has
+    /// `relation` on, e.g. `feature:reports`
+    pub async fn list_objects(
+        &self,
+        http: &HttpClient,
+        user: &str,
+        relation: &str,
+        object_type: &str,
+    ) -> Result<Vec<String>, OpenFgaError> {
+        #[derive(Deserialize)]
+        struct ListObjectsResponse {
+            objects: Vec<String>,
+        }
+
+        let mut body = serde_json::json!({
+            "type": object_type,
+            "relation": relation,
+            "user": user,
+        });
+        self.pin_model(&mut body);
+        let listed: ListObjectsResponse =
+            self.guarded(self.post(http, "list-objects", &body)).await?;
+        Ok(listed.objects)
+    }
+
+    /// Run a request-path call through the circuit breaker
+    async fn guarded<T>(
+        &self,
+        call: impl Future<Output = Result<T, OpenFgaError>>,
+    ) -> Result<T, OpenFgaError> {
+        if !self.breaker.allow() {
+            return Err(OpenFgaError::CircuitOpen);
+        }
+        // Frees the half-open trial if this future is dropped mid-call
+        let mut pending = AbandonGuard {
+            breaker: &self.breaker,
+            armed: true,
+        };
+        let result = call.await;
+        pending.armed = false;
+        // A 4xx still means OpenFGA is up and answering
+        match &result {
+            Err(e) if e.is_transient() => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
+        }
+        result
+    }
+
+    /// POST to a store endpoint, retrying transient failures with backoff
+    async fn post_with_retry<T: DeserializeOwned>(
+        &self,
+        http: &HttpClient,
+        endpoint: &str,
+        body: &serde_json::Value,
+    ) -> Result<T, OpenFgaError> {
+        let max_attempts = self.retry.max_attempts.max(1);
+        let mut attempt = 0;
+        loop {
+            match self.post(http, endpoint, body).await {
+                Err(e) if e.is_transient() && attempt + 1 < max_attempts => {
+                    tracing::warn!(
+                        "OpenFGA {} failed (attempt {}/{}), retrying: {}",
+                        endpoint,
+                        attempt + 1,
+                        max_attempts,
+                        e
+                    );
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// `/check` request body, with ABAC condition context, request-time
+    /// tuples and consistency when provided
+    fn check_body(
+        &self,
+        tuple: &TupleKey,
+        context: Option<&CheckContext>,
+        consistency: Option<Consistency>,
+    ) -> serde_json::Value {
+        let mut body = serde_json::json!({ "tuple_key": tuple });
+        if let Some(consistency) = consistency.or(self.consistency) {
+            body["consistency"] = serde_json::json!(consistency);
+        }
+        if let Some(context) = context {
+            if let Some(values) = &context.context {
+                body["context"] = values.clone();
+            }
+            if !context.contextual_tuples.is_empty() {
+                body["contextual_tuples"] =
+                    serde_json::json!({ "tuple_keys": context.contextual_tuples });
+            }
+        }
+        self.pin_model(&mut body);
+        body
+    }
+
+    /// Pin a request to the configured authorization model, if any, so a
+    /// model migration can't change how in-flight requests are evaluated
+    fn pin_model(&self, body: &mut serde_json::Value) {
+        if let Some(model_id) = &self.model_id {
+            body["authorization_model_id"] = model_id.as_str().into();
+        }
+    }
+
+    fn endpoint(&self, endpoint: &str) -> String {
+        format!("{}/stores/{}/{}", self.url, self.store_id, endpoint)
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        http: &HttpClient,
+        endpoint: &str,
+        body: &serde_json::Value,
+    ) -> Result<T, OpenFgaError> {
+        let mut headers = HeaderMap::new();
+        crate::otel::inject_trace_context(&mut headers);
+        let response = http
+            .post(self.endpoint(endpoint))
+            .headers(headers)
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(OpenFgaError::Status { status, body });
+        }
+        Ok(response.json().await?)
+    }
+}
+
+/// Reports a guarded call to the breaker as abandoned unless disarmed first
+struct AbandonGuard<'a> {
+    breaker: &'a CircuitBreaker,
+    armed: bool,
+}
+
+impl Drop for AbandonGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.breaker.record_abandoned();
+        }
+    }
+}
+
+/// Bounded exponential backoff for transient (connection/5xx) failures
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(50),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retrying after the given (0-based) attempt, with jitter
+    /// so concurrent callers don't retry in lockstep
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        delay.mul_f64(rand::random_range(0.5..1.0))
+    }
+}
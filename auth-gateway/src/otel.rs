@@ -0,0 +1,47 @@
+// OpenTelemetry Tracing
+// Optional OTLP export of the gateway's spans, and W3C trace context on
+// outbound calls so upstream and OpenFGA spans join the same trace
+
+use axum::http::HeaderMap;
+use opentelemetry::global;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_http::HeaderInjector;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Tracer provider batching spans over OTLP/HTTP to the collector named by
+/// the standard `OTEL_EXPORTER_OTLP_*` variables
+pub fn otlp_provider() -> Result<SdkTracerProvider, ExporterBuildError> {
+    let exporter = SpanExporter::builder().with_http().build()?;
+    let mut resource = Resource::builder();
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name("auth-gateway");
+    }
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build())
+}
+
+/// `tracing` layer recording spans through `provider`. Installing it also
+/// turns on `traceparent` propagation (see `inject_trace_context`).
+pub fn layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, SdkTracer>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("auth-gateway"))
+}
+
+/// Add the current span's trace context to an outbound request's headers.
+/// A no-op until `layer` has been installed.
+pub fn inject_trace_context(headers: &mut HeaderMap) {
+    let context = tracing::Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
@@ -0,0 +1,52 @@
+// Access Rules Watcher
+// Reloads access rules when the rules file changes on disk
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::auth::{reload_access_rules, AppState};
+
+/// Editors often write a file in several steps; wait for them to settle
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watch `state.access_rules_path` and hot-reload the router on change
+///
+/// The parent directory is watched rather than the file itself so that
+/// atomic replace-by-rename (editors, ConfigMap updates) is picked up.
+/// The returned watcher must be kept alive for watching to continue.
+pub fn spawn_rules_watcher(state: AppState) -> notify::Result<RecommendedWatcher> {
+    let path = Path::new(&state.access_rules_path).to_path_buf();
+    let file_name = path.file_name().map(|name| name.to_os_string());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    };
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+        let Ok(event) = result else { return };
+        let touches_rules = event
+            .paths
+            .iter()
+            .any(|p| p.file_name().map(|n| n.to_os_string()) == file_name);
+        if touches_rules && (event.kind.is_create() || event.kind.is_modify()) {
+            let _ = tx.send(());
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+    tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            tokio::time::sleep(DEBOUNCE).await;
+            while rx.try_recv().is_ok() {}
+
+            if let Err(e) = reload_access_rules(&state).await {
+                tracing::error!("Keeping current access rules, reload failed: {}", e);
+            }
+        }
+    });
+
+    tracing::info!("Watching {} for access rule changes", path.display());
+    Ok(watcher)
+}
@@ -0,0 +1,120 @@
+// Prometheus Metrics
+// Counters and histograms for auth decisions, caching and proxying
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+
+use crate::auth::AppState;
+
+pub const AUTH_REQUESTS_TOTAL: &str = "auth_requests_total";
+pub const AUTHZ_CACHE_HITS_TOTAL: &str = "authz_cache_hits_total";
+pub const AUTHZ_CACHE_MISSES_TOTAL: &str = "authz_cache_misses_total";
+pub const OPENFGA_CHECK_DURATION_SECONDS: &str = "openfga_check_duration_seconds";
+pub const RATE_LIMIT_REJECTIONS_TOTAL: &str = "rate_limit_rejections_total";
+pub const PROXY_REQUEST_DURATION_SECONDS: &str = "proxy_request_duration_seconds";
+pub const CIRCUIT_BREAKER_STATE: &str = "circuit_breaker_state";
+pub const UPSTREAM_THROTTLED_TOTAL: &str = "upstream_throttled_total";
+pub const CACHE_ENTRIES: &str = "cache_entries";
+pub const OPENFGA_CHECK_ERRORS_TOTAL: &str = "openfga_check_errors_total";
+pub const AUTHZ_CACHE_STALENESS_TOTAL: &str = "authz_cache_staleness_total";
+pub const AUDIT_EVENTS_DROPPED_TOTAL: &str = "audit_events_dropped_total";
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Install the global Prometheus recorder (idempotent)
+pub fn install_recorder() -> PrometheusHandle {
+    HANDLE
+        .get_or_init(|| {
+            let handle = PrometheusBuilder::new()
+                .install_recorder()
+                .expect("failed to install Prometheus recorder");
+            describe_metrics();
+            handle
+        })
+        .clone()
+}
+
+fn describe_metrics() {
+    metrics::describe_counter!(AUTH_REQUESTS_TOTAL, "Auth middleware outcomes by result");
+    metrics::describe_counter!(
+        AUTHZ_CACHE_HITS_TOTAL,
+        "Authorization decisions served from cache"
+    );
+    metrics::describe_counter!(
+        AUTHZ_CACHE_MISSES_TOTAL,
+        "Authorization decisions sent to OpenFGA"
+    );
+    metrics::describe_histogram!(
+        OPENFGA_CHECK_DURATION_SECONDS,
+        metrics::Unit::Seconds,
+        "OpenFGA check latency, including retries"
+    );
+    metrics::describe_counter!(
+        RATE_LIMIT_REJECTIONS_TOTAL,
+        "Requests rejected by the rate limiter"
+    );
+    metrics::describe_histogram!(
+        PROXY_REQUEST_DURATION_SECONDS,
+        metrics::Unit::Seconds,
+        "Upstream proxy latency by response status"
+    );
+    metrics::describe_gauge!(
+        CIRCUIT_BREAKER_STATE,
+        "Circuit breaker state by dependency (0 closed, 1 half-open, 2 open)"
+    );
+    metrics::describe_counter!(
+        UPSTREAM_THROTTLED_TOTAL,
+        "Upstream 429s and 503s with Retry-After, by feature"
+    );
+    metrics::describe_gauge!(CACHE_ENTRIES, "Entries held by each in-memory cache");
+    metrics::describe_counter!(
+        OPENFGA_CHECK_ERRORS_TOTAL,
+        "OpenFGA checks that got no decision, by kind (model, request, status, circuit_open)"
+    );
+    metrics::describe_counter!(
+        AUTHZ_CACHE_STALENESS_TOTAL,
+        "Sampled cache hits OpenFGA disagreed with, by kind (stale_allow, stale_deny)"
+    );
+    metrics::describe_counter!(
+        AUDIT_EVENTS_DROPPED_TOTAL,
+        "Audit events dropped because the sink fell behind"
+    );
+
+    // Register the unlabelled counters so they are scraped before first use
+    metrics::counter!(AUTHZ_CACHE_HITS_TOTAL).increment(0);
+    metrics::counter!(AUTHZ_CACHE_MISSES_TOTAL).increment(0);
+    metrics::counter!(RATE_LIMIT_REJECTIONS_TOTAL).increment(0);
+    metrics::counter!(AUDIT_EVENTS_DROPPED_TOTAL).increment(0);
+}
+
+/// Count one auth middleware outcome (allowed, public, unauthorized, ...)
+pub fn record_auth_result(result: &'static str) {
+    metrics::counter!(AUTH_REQUESTS_TOTAL, "result" => result).increment(1);
+}
+
+/// GET /metrics - Prometheus text exposition
+pub async fn metrics_handler(State(state): State<AppState>) -> Response {
+    record_cache_sizes(&state).await;
+    match HANDLE.get() {
+        Some(handle) => (
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            handle.render(),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Set the cache size gauges. Pending evictions are applied first so the
+/// counts reflect the configured capacities.
+async fn record_cache_sizes(state: &AppState) {
+    state.cache.run_pending_tasks().await;
+    state.jwks_cache.run_pending_tasks().await;
+    metrics::gauge!(CACHE_ENTRIES, "cache" => "authz").set(state.cache.entry_count() as f64);
+    metrics::gauge!(CACHE_ENTRIES, "cache" => "jwks").set(state.jwks_cache.entry_count() as f64);
+}
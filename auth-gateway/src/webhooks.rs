@@ -1,10 +1,166 @@
 // Webhook Handlers for Zitadel User Sync
 // Handles incoming webhooks from Zitadel Actions to sync users to OpenFGA
 
-use axum::{extract::State, http::StatusCode, Json};
-use serde::{Deserialize, Serialize};
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{FromRequest, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashSet;
 
-use crate::auth::AppState;
+use crate::auth::{invalidate_user, AppState};
+use crate::openfga::{OpenFgaError, Tuple, TupleFilter, TupleKey, OPENFGA_MAX_TUPLES_PER_WRITE};
+
+/// Header carrying the hex HMAC-SHA256 of the raw body (optionally `sha256=` prefixed)
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+/// Unix seconds at which the sender signed the request. When timestamps are
+/// enforced the signature covers `{timestamp}.{body}`, so it can't be moved
+/// to make a captured request look fresh.
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "x-zitadel-timestamp";
+
+/// OpenFGA type and relation used for Zitadel roles: `user:{id}` is `member` of `role:{name}`
+const ROLE_TYPE: &str = "role";
+const ROLE_RELATION: &str = "member";
+
+/// Where newly created users are registered: `user:{id}` is `relation` of
+/// `object`, with machine users optionally kept apart in `machine_object`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserRegistration {
+    pub object: String,
+    pub relation: String,
+    pub machine_object: Option<String>, // None = machines share `object`
+}
+
+impl Default for UserRegistration {
+    fn default() -> Self {
+        Self {
+            object: "organization:users".to_string(),
+            relation: "member".to_string(),
+            machine_object: None,
+        }
+    }
+}
+
+impl UserRegistration {
+    /// The tuple registering the user entity. This doesn't grant any
+    /// permissions - it just makes the user visible to admin tools.
+    pub fn tuple(&self, user_id: &str, user_type: Option<&str>) -> TupleKey {
+        let object = match (&self.machine_object, user_type) {
+            (Some(machine_object), Some("machine")) => machine_object,
+            _ => &self.object,
+        };
+        TupleKey::new(format!("user:{}", user_id), &self.relation, object)
+    }
+}
+
+/// Optional delivery ID used to drop redelivered events
+pub const WEBHOOK_EVENT_ID_HEADER: &str = "x-event-id";
+
+/// How long a processed event ID is remembered
+const EVENT_DEDUPE_TTL_SECS: u64 = 600;
+
+/// Webhook payloads are small; anything larger is rejected before hashing
+const MAX_WEBHOOK_BODY_BYTES: usize = 1024 * 1024;
+
+// ============================================================================
+// Signature Verification
+// ============================================================================
+
+/// Reject webhook calls whose body isn't signed with `WEBHOOK_SIGNING_SECRET`
+///
+/// Runs before JSON extraction so the HMAC covers the exact bytes sent;
+/// the body is handed on to the handler unchanged. With
+/// `webhook_timestamp_tolerance` set, requests must also carry a signed
+/// timestamp within the tolerance of now, so captured requests can't be
+/// replayed later.
+pub async fn verify_signature(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(secret) = state.webhook_signing_secret.as_deref() else {
+        tracing::warn!("Webhook rejected: no signing secret configured");
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    let signature = req
+        .headers()
+        .get(WEBHOOK_SIGNATURE_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.strip_prefix("sha256=").unwrap_or(h))
+        .and_then(|h| hex::decode(h).ok())
+        .ok_or_else(|| {
+            tracing::warn!("Webhook rejected: missing or malformed signature");
+            StatusCode::UNAUTHORIZED
+        })?;
+
+    let timestamp = match state.webhook_timestamp_tolerance {
+        None => None,
+        Some(tolerance) => Some(fresh_timestamp(req.headers(), tolerance)?.to_string()),
+    };
+
+    let (parts, body) = req.into_parts();
+    let bytes = axum::body::to_bytes(body, MAX_WEBHOOK_BODY_BYTES)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE)?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Some(timestamp) = timestamp {
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+    }
+    mac.update(&bytes);
+    if mac.verify_slice(&signature).is_err() {
+        tracing::warn!("Webhook rejected: signature mismatch");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await)
+}
+
+/// The request's timestamp header, if it's within `tolerance` of now in
+/// either direction (allowing for clock skew)
+fn fresh_timestamp(
+    headers: &axum::http::HeaderMap,
+    tolerance: std::time::Duration,
+) -> Result<&str, StatusCode> {
+    let header = headers
+        .get(WEBHOOK_TIMESTAMP_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| {
+            tracing::warn!("Webhook rejected: missing timestamp");
+            StatusCode::UNAUTHORIZED
+        })?;
+    let timestamp: u64 = header.trim().parse().map_err(|_| {
+        tracing::warn!("Webhook rejected: malformed timestamp {:?}", header);
+        StatusCode::UNAUTHORIZED
+    })?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    if now.abs_diff(timestamp) > tolerance.as_secs() {
+        tracing::warn!(
+            "Webhook rejected: timestamp {} is {}s from now",
+            timestamp,
+            now.abs_diff(timestamp)
+        );
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(header)
+}
 
 // ============================================================================
 // Event Types
@@ -26,6 +182,7 @@ pub struct UserUpdatedEvent {
     pub user_id: String,
     #[serde(rename = "userName")]
     pub user_name: String,
+    pub roles: Option<Vec<String>>, // Full current role set (None = roles unchanged)
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,6 +197,155 @@ pub struct WebhookResponse {
     pub message: String,
 }
 
+/// Outcome for one user of a bulk sync
+#[derive(Debug, Serialize)]
+pub struct BulkUserResult {
+    #[serde(rename = "userId")]
+    pub user_id: String,
+    pub status: &'static str, // registered, already_registered or failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkSyncResponse {
+    pub status: String, // success, or partial if any user failed
+    pub registered: usize,
+    pub failed: usize,
+    pub results: Vec<BulkUserResult>,
+}
+
+/// 400 body for a payload that doesn't fit its event type
+#[derive(Debug, Serialize)]
+pub struct PayloadError {
+    pub status: String,
+    pub message: String,
+    pub field: Option<String>, // e.g. "userId" or "roles[1]" (None = not valid JSON)
+}
+
+// ============================================================================
+// Payload Extraction
+// ============================================================================
+
+/// JSON extractor for webhook events. Unlike `Json`, a payload that doesn't
+/// fit is a 400 naming the missing or invalid field, and the raw body is
+/// logged at debug level to help troubleshoot Zitadel Action scripts.
+pub struct WebhookPayload<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for WebhookPayload<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        tracing::debug!("Webhook payload: {}", String::from_utf8_lossy(&bytes));
+
+        let deserializer = &mut serde_json::Deserializer::from_slice(&bytes);
+        serde_path_to_error::deserialize(deserializer)
+            .map(WebhookPayload)
+            .map_err(|e| {
+                let field = invalid_field(&e);
+                tracing::warn!("Webhook payload rejected ({:?}): {}", field, e.inner());
+                let error = PayloadError {
+                    status: "error".to_string(),
+                    message: format!("Invalid webhook payload: {}", e.inner()),
+                    field,
+                };
+                (StatusCode::BAD_REQUEST, Json(error)).into_response()
+            })
+    }
+}
+
+/// Path of the field a payload failed on, including a missing field's name
+fn invalid_field(e: &serde_path_to_error::Error<serde_json::Error>) -> Option<String> {
+    let path = e.path().to_string();
+    let inner = e.inner().to_string();
+    let missing = inner
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split_once('`'))
+        .map(|(field, _)| field);
+
+    // "." is the top level; "?" a syntax error with no position in the payload
+    match (path.as_str(), missing) {
+        ("." | "?", None) => None,
+        ("." | "?", Some(field)) => Some(field.to_string()),
+        (path, Some(field)) => Some(format!("{}.{}", path, field)),
+        (path, None) => Some(path.to_string()),
+    }
+}
+
+// ============================================================================
+// Duplicate Delivery
+// ============================================================================
+
+/// Skip events whose `X-Event-ID` was already processed
+///
+/// Zitadel delivers at least once. The ID is claimed in Redis before the
+/// handler runs and released again if the handler fails, so a retry after
+/// an error is still processed. Without Redis, events are processed anyway;
+/// the handlers themselves tolerate repeats.
+pub async fn dedupe_event(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(event_id) = req
+        .headers()
+        .get(WEBHOOK_EVENT_ID_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_owned)
+    else {
+        return next.run(req).await;
+    };
+    let key = format!("webhook_event:{}", event_id);
+
+    match claim_event(&state.redis_client, &key).await {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::info!("Webhook event {} already processed, skipping", event_id);
+            return Json(WebhookResponse {
+                status: "duplicate".to_string(),
+                message: format!("Event {} already processed", event_id),
+            })
+            .into_response();
+        }
+        Err(e) => {
+            tracing::warn!("Webhook dedupe unavailable, processing anyway: {}", e);
+            return next.run(req).await;
+        }
+    }
+
+    let response = next.run(req).await;
+    if !response.status().is_success() {
+        // Let the sender's retry through
+        if let Err(e) = release_event(&state.redis_client, &key).await {
+            tracing::warn!("Failed to release webhook event {}: {}", event_id, e);
+        }
+    }
+    response
+}
+
+/// Record the event ID; false if it was already recorded
+async fn claim_event(client: &redis::Client, key: &str) -> redis::RedisResult<bool> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    let claimed: Option<String> = redis::cmd("SET")
+        .arg(key)
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(EVENT_DEDUPE_TTL_SECS)
+        .query_async(&mut conn)
+        .await?;
+    Ok(claimed.is_some())
+}
+
+async fn release_event(client: &redis::Client, key: &str) -> redis::RedisResult<()> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    redis::cmd("DEL").arg(key).query_async(&mut conn).await
+}
+
 // ============================================================================
 // Webhook Handlers
 // ============================================================================
@@ -50,13 +356,14 @@ pub struct WebhookResponse {
 /// and assign permissions via the admin UI.
 ///
 /// This creates a tuple: `user:{userId}` is `member` of `organization:users`
-/// This allows admin tools to query all users from OpenFGA.
+/// (or as configured in `user_registration`). This allows admin tools to
+/// query all users from OpenFGA.
 ///
 /// **No permissions are assigned** - only the user entity is registered.
 /// Admin assigns permissions separately via the admin interface.
 pub async fn handle_user_created(
     State(state): State<AppState>,
-    Json(event): Json<UserCreatedEvent>,
+    WebhookPayload(event): WebhookPayload<UserCreatedEvent>,
 ) -> Result<Json<WebhookResponse>, StatusCode> {
     tracing::info!(
         "Webhook: User created - ID: {}, Name: {}, Type: {:?}",
@@ -65,31 +372,15 @@ pub async fn handle_user_created(
         event.user_type
     );
 
-    let store_id =
-        std::env::var("OPENFGA_STORE_ID").map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Create a tuple to register the user entity in OpenFGA
-    // This doesn't grant any permissions - it just makes the user visible to admin tools
-    let tuple = serde_json::json!({
-        "user": format!("user:{}", event.user_id),
-        "relation": "member",
-        "object": "organization:users"
-    });
-
-    let write_request = serde_json::json!({
-        "writes": {
-            "tuple_keys": [tuple]
-        }
-    });
-
+    let tuple = state
+        .user_registration
+        .tuple(&event.user_id, event.user_type.as_deref());
     match state
-        .http_client
-        .post(format!("{}/stores/{}/write", state.openfga_url, store_id))
-        .json(&write_request)
-        .send()
+        .fga_client
+        .write(&state.http_client, &[tuple], &[])
         .await
     {
-        Ok(resp) if resp.status().is_success() => {
+        Ok(()) => {
             tracing::info!("Registered user {} in OpenFGA", event.user_id);
             Ok(Json(WebhookResponse {
                 status: "success".to_string(),
@@ -99,95 +390,252 @@ pub async fn handle_user_created(
                 ),
             }))
         }
-        Ok(resp) => {
-            let error: String = resp.text().await.unwrap_or_default();
-            tracing::error!("Failed to register user in OpenFGA: {}", error);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        // Redelivered event: the user is already registered
+        Err(OpenFgaError::Status { status, body })
+            if status.is_client_error() && is_duplicate_tuple_error(&body) =>
+        {
+            tracing::info!("User {} already registered in OpenFGA", event.user_id);
+            Ok(Json(WebhookResponse {
+                status: "success".to_string(),
+                message: format!("User {} already registered in OpenFGA", event.user_id),
+            }))
         }
         Err(e) => {
-            tracing::error!("OpenFGA request failed: {}", e);
+            tracing::error!("Failed to register user in OpenFGA: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
-/// Handle user update event from Zitadel
+/// Handle a batch of user creation events, e.g. the initial migration of
+/// an existing directory
 ///
-/// Currently logs the event (extend as needed for role changes, etc.)
-pub async fn handle_user_updated(
-    State(_state): State<AppState>,
-    Json(event): Json<UserUpdatedEvent>,
-) -> Result<Json<WebhookResponse>, StatusCode> {
+/// Registers every user like `handle_user_created`, but in as few OpenFGA
+/// writes as the per-write tuple limit allows. A chunk OpenFGA rejects (4xx)
+/// is retried one user at a time, so a single bad or already-registered user
+/// doesn't fail its neighbours. A chunk that fails because OpenFGA is down
+/// fails as a whole, after the client's own backoff, rather than multiplying
+/// the load on it. Responds 500 (so the sender retries) if any user couldn't
+/// be registered, with the per-user outcome either way.
+pub async fn handle_users_bulk(
+    State(state): State<AppState>,
+    WebhookPayload(events): WebhookPayload<Vec<UserCreatedEvent>>,
+) -> (StatusCode, Json<BulkSyncResponse>) {
+    // A tuple may only appear once per write
+    let mut seen = HashSet::new();
+    let users: Vec<(&str, TupleKey)> = events
+        .iter()
+        .filter(|e| seen.insert(e.user_id.as_str()))
+        .map(|e| {
+            let tuple = state
+                .user_registration
+                .tuple(&e.user_id, e.user_type.as_deref());
+            (e.user_id.as_str(), tuple)
+        })
+        .collect();
+    tracing::info!("Webhook: Bulk user sync - {} users", users.len());
+
+    let mut results = Vec::with_capacity(users.len());
+    for chunk in users.chunks(OPENFGA_MAX_TUPLES_PER_WRITE) {
+        let tuples: Vec<TupleKey> = chunk.iter().map(|(_, tuple)| tuple.clone()).collect();
+        match state
+            .fga_client
+            .write(&state.http_client, &tuples, &[])
+            .await
+        {
+            Ok(()) => results.extend(chunk.iter().map(|(id, _)| BulkUserResult {
+                user_id: id.to_string(),
+                status: "registered",
+                error: None,
+            })),
+            Err(OpenFgaError::Status { status, body }) if status.is_client_error() => {
+                tracing::warn!(
+                    "Bulk write of {} users rejected ({}), retrying individually: {}",
+                    chunk.len(),
+                    status,
+                    body
+                );
+                for (id, tuple) in chunk {
+                    results.push(register_user(&state, id, tuple.clone()).await);
+                }
+            }
+            Err(e) => {
+                tracing::error!("Bulk write of {} users failed: {}", chunk.len(), e);
+                results.extend(chunk.iter().map(|(id, _)| BulkUserResult {
+                    user_id: id.to_string(),
+                    status: "failed",
+                    error: Some(e.to_string()),
+                }));
+            }
+        }
+    }
+
+    let failed = results.iter().filter(|r| r.status == "failed").count();
+    let registered = results.len() - failed;
     tracing::info!(
-        "Webhook: User updated - ID: {}, Name: {}",
-        event.user_id,
-        event.user_name
+        "Bulk user sync: {} registered, {} failed",
+        registered,
+        failed
     );
+    let (status, summary) = if failed == 0 {
+        (StatusCode::OK, "success")
+    } else {
+        (StatusCode::INTERNAL_SERVER_ERROR, "partial")
+    };
+    (
+        status,
+        Json(BulkSyncResponse {
+            status: summary.to_string(),
+            registered,
+            failed,
+            results,
+        }),
+    )
+}
 
-    // Future: Handle role/permission updates
-    // For now, just acknowledge the event
-
-    Ok(Json(WebhookResponse {
-        status: "acknowledged".to_string(),
-        message: format!("User {} update acknowledged", event.user_id),
-    }))
+/// Register one user of a bulk sync on its own
+async fn register_user(state: &AppState, user_id: &str, tuple: TupleKey) -> BulkUserResult {
+    let (status, error) = match state
+        .fga_client
+        .write(&state.http_client, &[tuple], &[])
+        .await
+    {
+        Ok(()) => ("registered", None),
+        Err(OpenFgaError::Status { status, body })
+            if status.is_client_error() && is_duplicate_tuple_error(&body) =>
+        {
+            ("already_registered", None)
+        }
+        Err(e) => {
+            tracing::error!("Failed to register user {} in OpenFGA: {}", user_id, e);
+            ("failed", Some(e.to_string()))
+        }
+    };
+    BulkUserResult {
+        user_id: user_id.to_string(),
+        status,
+        error,
+    }
 }
 
-/// Handle user deletion event from Zitadel
+/// Handle user update event from Zitadel
 ///
-/// Removes all OpenFGA tuples associated with the user
-pub async fn handle_user_deleted(
+/// When `roles` is present, syncs the user's `role:*` membership tuples to
+/// match it: missing roles are written and stale ones deleted in a single
+/// batched write. Events without `roles` are only acknowledged.
+pub async fn handle_user_updated(
     State(state): State<AppState>,
-    Json(event): Json<UserDeletedEvent>,
+    WebhookPayload(event): WebhookPayload<UserUpdatedEvent>,
 ) -> Result<Json<WebhookResponse>, StatusCode> {
-    tracing::info!("Webhook: User deleted - ID: {}", event.user_id);
+    tracing::info!(
+        "Webhook: User updated - ID: {}, Name: {}, Roles: {:?}",
+        event.user_id,
+        event.user_name,
+        event.roles
+    );
 
-    let store_id =
-        std::env::var("OPENFGA_STORE_ID").map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some(roles) = event.roles else {
+        return Ok(Json(WebhookResponse {
+            status: "acknowledged".to_string(),
+            message: format!("User {} update acknowledged", event.user_id),
+        }));
+    };
 
-    // Read tuples filtered by user (much more efficient than reading all tuples!)
-    let read_url = format!("{}/stores/{}/read", state.openfga_url, store_id);
     let user_string = format!("user:{}", event.user_id);
+    let role_prefix = format!("{}:", ROLE_TYPE);
 
-    let read_request = serde_json::json!({
-        "tuple_key": {
-            "user": user_string
-        }
-    });
+    // Current role memberships (type-only object filters to role:*)
+    let tuples = read_tuples(
+        &state,
+        TupleFilter {
+            user: Some(user_string.clone()),
+            relation: Some(ROLE_RELATION.to_string()),
+            object: Some(role_prefix.clone()),
+        },
+    )
+    .await?;
 
-    tracing::debug!("Querying OpenFGA for tuples of user: {}", event.user_id);
+    let current: HashSet<&str> = tuples
+        .iter()
+        .filter_map(|t| t.key.object.strip_prefix(&role_prefix))
+        .collect();
+    let desired: HashSet<&str> = roles.iter().map(String::as_str).collect();
+
+    let role_tuple = |role: &str| {
+        TupleKey::new(
+            user_string.as_str(),
+            ROLE_RELATION,
+            format!("{}{}", role_prefix, role),
+        )
+    };
+    let writes: Vec<_> = desired
+        .difference(&current)
+        .map(|r| role_tuple(r))
+        .collect();
+    let deletes: Vec<_> = current
+        .difference(&desired)
+        .map(|r| role_tuple(r))
+        .collect();
+
+    if writes.is_empty() && deletes.is_empty() {
+        return Ok(Json(WebhookResponse {
+            status: "success".to_string(),
+            message: format!("User {} roles already in sync", event.user_id),
+        }));
+    }
 
-    let read_response = match state
-        .http_client
-        .post(&read_url)
-        .json(&read_request)
-        .send()
+    match state
+        .fga_client
+        .write(&state.http_client, &writes, &deletes)
         .await
     {
-        Ok(resp) if resp.status().is_success() => resp,
-        Ok(resp) => {
-            let error: String = resp.text().await.unwrap_or_default();
-            tracing::error!("Failed to read tuples from OpenFGA: {}", error);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        Ok(()) => {
+            invalidate_user(&state, &event.user_id);
+            tracing::info!(
+                "Synced roles for user {}: {} added, {} removed",
+                event.user_id,
+                writes.len(),
+                deletes.len()
+            );
+            Ok(Json(WebhookResponse {
+                status: "success".to_string(),
+                message: format!(
+                    "User {} roles synced: {} added, {} removed",
+                    event.user_id,
+                    writes.len(),
+                    deletes.len()
+                ),
+            }))
         }
         Err(e) => {
-            tracing::error!("OpenFGA read request failed: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            tracing::error!("Failed to sync roles in OpenFGA: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
-    };
-
-    // Use same deserialization pattern as feature_sync (no clone!)
-    #[derive(serde::Deserialize)]
-    struct ReadResponse {
-        tuples: Vec<serde_json::Value>,
     }
+}
 
-    let read_result: ReadResponse = read_response
-        .json()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+/// Handle user deletion event from Zitadel
+///
+/// Removes all OpenFGA tuples associated with the user
+pub async fn handle_user_deleted(
+    State(state): State<AppState>,
+    WebhookPayload(event): WebhookPayload<UserDeletedEvent>,
+) -> Result<Json<WebhookResponse>, StatusCode> {
+    tracing::info!("Webhook: User deleted - ID: {}", event.user_id);
+
+    // Read tuples filtered by user (much more efficient than reading all tuples!)
+    tracing::debug!("Querying OpenFGA for tuples of user: {}", event.user_id);
+    let tuples = read_tuples(
+        &state,
+        TupleFilter {
+            user: Some(format!("user:{}", event.user_id)),
+            ..Default::default()
+        },
+    )
+    .await?;
 
-    if read_result.tuples.is_empty() {
+    if tuples.is_empty() {
+        invalidate_user(&state, &event.user_id);
         tracing::info!("No tuples found for user {}", event.user_id);
         return Ok(Json(WebhookResponse {
             status: "success".to_string(),
@@ -197,51 +645,56 @@ pub async fn handle_user_deleted(
 
     tracing::info!(
         "Found {} tuples to delete for user {}",
-        read_result.tuples.len(),
+        tuples.len(),
         event.user_id
     );
 
-    // Batch delete ALL tuples in a single API call
-    let delete_keys: Vec<&serde_json::Value> =
-        read_result.tuples.iter().map(|t| &t["key"]).collect();
+    // Batch delete in as few calls as the per-write tuple limit allows
+    let delete_keys: Vec<TupleKey> = tuples.into_iter().map(|t| t.key).collect();
 
-    let delete_url = format!("{}/stores/{}/write", state.openfga_url, store_id);
-    let delete_request = serde_json::json!({
-        "deletes": {
-            "tuple_keys": delete_keys
+    for chunk in delete_keys.chunks(OPENFGA_MAX_TUPLES_PER_WRITE) {
+        if let Err(e) = state.fga_client.write(&state.http_client, &[], chunk).await {
+            // Earlier chunks may already be gone; the sender's retry reads
+            // what's left
+            invalidate_user(&state, &event.user_id);
+            tracing::error!("Failed to delete tuples: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
-    });
+    }
 
-    match state
-        .http_client
-        .post(&delete_url)
-        .json(&delete_request)
-        .send()
+    invalidate_user(&state, &event.user_id);
+    tracing::info!(
+        "Cleaned up {} tuples for user {}",
+        delete_keys.len(),
+        event.user_id
+    );
+    Ok(Json(WebhookResponse {
+        status: "success".to_string(),
+        message: format!(
+            "User {} deleted: cleaned up {} permissions",
+            event.user_id,
+            delete_keys.len()
+        ),
+    }))
+}
+
+// ============================================================================
+// OpenFGA Helpers
+// ============================================================================
+
+/// Whether an OpenFGA write error only says the tuple is already there
+pub(crate) fn is_duplicate_tuple_error(error: &str) -> bool {
+    error.contains("already exists") || error.contains("already existed")
+}
+
+/// Read the tuples matching `filter` (e.g. everything for one user)
+async fn read_tuples(state: &AppState, filter: TupleFilter) -> Result<Vec<Tuple>, StatusCode> {
+    state
+        .fga_client
+        .read(&state.http_client, &filter)
         .await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            tracing::info!(
-                "Cleaned up {} tuples for user {} in single batch",
-                read_result.tuples.len(),
-                event.user_id
-            );
-            Ok(Json(WebhookResponse {
-                status: "success".to_string(),
-                message: format!(
-                    "User {} deleted: cleaned up {} permissions",
-                    event.user_id,
-                    read_result.tuples.len()
-                ),
-            }))
-        }
-        Ok(resp) => {
-            let error: String = resp.text().await.unwrap_or_default();
-            tracing::error!("Failed to delete tuples: {}", error);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-        Err(e) => {
-            tracing::error!("OpenFGA delete request failed: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+        .map_err(|e| {
+            tracing::error!("Failed to read tuples from OpenFGA: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
 }
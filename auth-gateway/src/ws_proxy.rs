@@ -0,0 +1,189 @@
+// WebSocket Proxying
+// Bridges authorized `Upgrade: websocket` requests to the upstream service
+
+use axum::{
+    body::Body,
+    extract::{
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        FromRequestParts, Request,
+    },
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{
+    self, client::IntoClientRequest, protocol::frame::coding::CloseCode,
+};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::auth::{
+    apply_forward_allowlist, apply_gateway_secret, apply_upstream_auth, strip_hop_by_hop,
+    upstream_url, AppState, Forwarded, RequestId, REQUEST_ID_HEADER,
+};
+
+type UpstreamSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Handshake headers the upstream connection generates for itself
+const HANDSHAKE_HEADERS: [&str; 4] = [
+    "sec-websocket-key",
+    "sec-websocket-version",
+    "sec-websocket-extensions",
+    "sec-websocket-accept",
+];
+
+/// Whether the client is asking to upgrade to a WebSocket
+pub fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::UPGRADE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.eq_ignore_ascii_case("websocket"))
+}
+
+/// Connect to the upstream WebSocket first, so a dead upstream is a plain
+/// 502 rather than an accepted socket that closes straight away, then
+/// accept the client upgrade and copy messages both ways
+pub async fn proxy_websocket(state: &AppState, req: Request<Body>) -> Response {
+    let (mut parts, _body) = req.into_parts();
+    let upgrade = match WebSocketUpgrade::from_request_parts(&mut parts, state).await {
+        Ok(upgrade) => upgrade,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let url = match upstream_url(state, &parts.method, &parts.uri) {
+        Ok(url) => url
+            .replacen("http://", "ws://", 1)
+            .replacen("https://", "wss://", 1),
+        Err(e) => {
+            tracing::error!("Cannot build upstream path: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    tracing::debug!("Proxying WebSocket to: {}", url);
+
+    let mut upstream_req = match url.as_str().into_client_request() {
+        Ok(upstream_req) => upstream_req,
+        Err(e) => {
+            tracing::error!("Invalid upstream WebSocket URL {}: {}", url, e);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+
+    // Forward end-to-end headers (identity, subprotocols, ...) like the HTTP proxy
+    let forwarded = Forwarded::new(state, &parts.headers, &parts.extensions);
+    let mut headers = parts.headers;
+    strip_hop_by_hop(&mut headers);
+    apply_forward_allowlist(state, &mut headers);
+    apply_upstream_auth(state, &parts.method, parts.uri.path(), &mut headers);
+    apply_gateway_secret(state, &mut headers);
+    forwarded.apply(&mut headers);
+    for name in HANDSHAKE_HEADERS {
+        headers.remove(name);
+    }
+    headers.remove(header::HOST);
+    headers.remove(REQUEST_ID_HEADER);
+    if let Some(RequestId(request_id)) = parts.extensions.get::<RequestId>() {
+        if let Ok(value) = HeaderValue::from_str(request_id) {
+            headers.insert(REQUEST_ID_HEADER, value);
+        }
+    }
+    for (name, value) in headers.iter() {
+        upstream_req.headers_mut().append(name, value.clone());
+    }
+
+    let connect = tokio_tungstenite::connect_async(upstream_req);
+    let (upstream, handshake) = match tokio::time::timeout(state.upstream_timeout, connect).await {
+        Ok(Ok(connected)) => connected,
+        Ok(Err(e)) => {
+            tracing::error!("Upstream WebSocket connection failed: {}", e);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+        Err(_) => {
+            tracing::error!(
+                "Upstream WebSocket timed out after {:?}",
+                state.upstream_timeout
+            );
+            return StatusCode::GATEWAY_TIMEOUT.into_response();
+        }
+    };
+
+    // Echo whichever subprotocol the upstream picked
+    let protocol = handshake
+        .headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_owned);
+    let upgrade = match protocol {
+        Some(protocol) => upgrade.protocols([protocol]),
+        None => upgrade,
+    };
+
+    upgrade.on_upgrade(|client| bridge(client, upstream))
+}
+
+/// Copy messages between the two sockets until either side closes.
+/// Pings are answered by each leg on its own rather than forwarded.
+async fn bridge(client: WebSocket, upstream: UpstreamSocket) {
+    let (mut client_tx, mut client_rx) = client.split();
+    let (mut upstream_tx, mut upstream_rx) = upstream.split();
+
+    let client_to_upstream = async {
+        while let Some(Ok(message)) = client_rx.next().await {
+            let Some(message) = to_upstream(message) else {
+                continue;
+            };
+            let closing = message.is_close();
+            if upstream_tx.send(message).await.is_err() || closing {
+                break;
+            }
+        }
+        let _ = upstream_tx.close().await;
+    };
+
+    let upstream_to_client = async {
+        while let Some(Ok(message)) = upstream_rx.next().await {
+            let Some(message) = to_client(message) else {
+                continue;
+            };
+            let closing = matches!(message, Message::Close(_));
+            if client_tx.send(message).await.is_err() || closing {
+                break;
+            }
+        }
+        let _ = client_tx.close().await;
+    };
+
+    tokio::select! {
+        _ = client_to_upstream => {}
+        _ = upstream_to_client => {}
+    }
+    tracing::debug!("WebSocket proxy session ended");
+}
+
+fn to_upstream(message: Message) -> Option<tungstenite::Message> {
+    match message {
+        Message::Text(text) => Some(tungstenite::Message::Text(text)),
+        Message::Binary(data) => Some(tungstenite::Message::Binary(data)),
+        Message::Close(frame) => Some(tungstenite::Message::Close(frame.map(|frame| {
+            tungstenite::protocol::CloseFrame {
+                code: CloseCode::from(frame.code),
+                reason: frame.reason,
+            }
+        }))),
+        Message::Ping(_) | Message::Pong(_) => None,
+    }
+}
+
+fn to_client(message: tungstenite::Message) -> Option<Message> {
+    match message {
+        tungstenite::Message::Text(text) => Some(Message::Text(text)),
+        tungstenite::Message::Binary(data) => Some(Message::Binary(data)),
+        tungstenite::Message::Close(frame) => Some(Message::Close(frame.map(|frame| CloseFrame {
+            code: frame.code.into(),
+            reason: frame.reason,
+        }))),
+        tungstenite::Message::Ping(_)
+        | tungstenite::Message::Pong(_)
+        | tungstenite::Message::Frame(_) => None,
+    }
+}
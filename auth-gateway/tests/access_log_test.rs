@@ -0,0 +1,100 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, AppState, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+
+const RULES: &str =
+    r#"[{ "path": "/reports", "method": "GET", "feature": "reports", "action": "view" }]"#;
+
+type Fields = HashMap<String, String>;
+
+/// Layer collecting the fields of every `access_log` event
+#[derive(Clone, Default)]
+struct AccessLogCapture(Arc<Mutex<Vec<Fields>>>);
+
+impl<S: tracing::Subscriber> Layer<S> for AccessLogCapture {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() == "access_log" {
+            let mut visitor = FieldVisitor::default();
+            event.record(&mut visitor);
+            self.0.lock().unwrap().push(visitor.0);
+        }
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor(Fields);
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().into(), format!("{:?}", value));
+    }
+}
+
+async fn state(access_log: bool) -> AppState {
+    let path = common::write_temp_file("access_log_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    state.fga_client.url = common::spawn_openfga(true).await;
+    state.access_log = access_log;
+    common::install_test_key(&state).await;
+    state
+}
+
+/// Send an authorized GET /reports and return the access log events
+async fn logged_request(state: AppState) -> Vec<Fields> {
+    let capture = AccessLogCapture::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+
+    let req = Request::builder()
+        .uri("/reports")
+        .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let events = capture.0.lock().unwrap().clone();
+    events
+}
+
+#[tokio::test]
+async fn test_authorized_request_is_logged_with_decision() {
+    let events = logged_request(state(true).await).await;
+
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event["method"], "GET");
+    assert_eq!(event["path"], "/reports");
+    assert_eq!(event["user"], "user-1");
+    assert_eq!(event["feature"], "reports");
+    assert_eq!(event["action"], "view");
+    assert_eq!(event["cache"], "miss");
+    assert_eq!(event["allowed"], "true");
+    assert_eq!(event["result"], "allowed");
+    assert_eq!(event["status"], "200");
+    assert!(event["latency_ms"].parse::<f64>().unwrap() >= 0.0);
+}
+
+#[tokio::test]
+async fn test_access_log_is_off_by_default() {
+    assert!(logged_request(state(false).await).await.is_empty());
+}
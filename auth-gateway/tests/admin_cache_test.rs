@@ -0,0 +1,96 @@
+mod common;
+
+use auth_gateway::admin::ADMIN_SECRET_HEADER;
+use auth_gateway::auth::{cache_decision, create_router, AppState, AuthzCacheKey, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+fn key(user: &str, feature: &str) -> AuthzCacheKey {
+    AuthzCacheKey::new(user, feature, None)
+}
+
+/// State with cached decisions for two users on two features
+async fn cached_state() -> AppState {
+    let state = common::test_state(matchit::Router::new());
+    for user in ["user-1", "user-2"] {
+        for feature in ["reports", "billing"] {
+            cache_decision(&state, key(user, feature), false, None).await;
+        }
+    }
+    state
+}
+
+async fn invalidate(state: &AppState, secret: &str, body: serde_json::Value) -> StatusCode {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/admin/cache/invalidate")
+        .header(ADMIN_SECRET_HEADER, secret)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn cached(state: &AppState, user: &str, feature: &str) -> bool {
+    state.cache.get(&key(user, feature)).await.is_some()
+}
+
+#[tokio::test]
+async fn test_invalidate_whole_user() {
+    let state = cached_state().await;
+
+    let status = invalidate(&state, common::ADMIN_SECRET, json!({ "user": "user-1" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(!cached(&state, "user-1", "reports").await);
+    assert!(!cached(&state, "user-1", "billing").await);
+    assert!(cached(&state, "user-2", "reports").await);
+}
+
+#[tokio::test]
+async fn test_invalidate_single_entry() {
+    let state = cached_state().await;
+
+    let body = json!({ "user": "user-1", "feature": "reports" });
+    let status = invalidate(&state, common::ADMIN_SECRET, body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(!cached(&state, "user-1", "reports").await);
+    assert!(cached(&state, "user-1", "billing").await);
+    assert!(cached(&state, "user-2", "reports").await);
+}
+
+#[tokio::test]
+async fn test_invalidate_feature_for_everyone() {
+    let state = cached_state().await;
+
+    let status = invalidate(
+        &state,
+        common::ADMIN_SECRET,
+        json!({ "feature": "billing" }),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(!cached(&state, "user-1", "billing").await);
+    assert!(!cached(&state, "user-2", "billing").await);
+    assert!(cached(&state, "user-1", "reports").await);
+}
+
+#[tokio::test]
+async fn test_invalidate_requires_admin_secret() {
+    let state = cached_state().await;
+
+    let status = invalidate(&state, "wrong-secret", json!({ "user": "user-1" })).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(cached(&state, "user-1", "reports").await);
+}
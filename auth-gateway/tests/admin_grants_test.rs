@@ -0,0 +1,118 @@
+mod common;
+
+use auth_gateway::admin::ADMIN_SECRET_HEADER;
+use auth_gateway::auth::{cache_decision, create_router, AppState, AuthzCacheKey, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    routing::post,
+    Json,
+};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+/// Mock OpenFGA `/write` keeping the bodies it receives; tuples already
+/// written are rejected as duplicates, like the real server
+async fn spawn_write_openfga() -> (String, Arc<Mutex<Vec<Value>>>) {
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = writes.clone();
+    let app = axum::Router::new().route(
+        "/stores/:store_id/write",
+        post(move |Json(body): Json<Value>| async move {
+            let mut writes = recorded.lock().unwrap();
+            let duplicate = writes.contains(&body);
+            writes.push(body);
+            if duplicate {
+                (
+                    StatusCode::BAD_REQUEST,
+                    r#"{"code":"write_failed_due_to_invalid_input","message":"cannot write a tuple which already exists"}"#,
+                )
+            } else {
+                (StatusCode::OK, "{}")
+            }
+        }),
+    );
+    (common::spawn_server(app).await, writes)
+}
+
+async fn grant(state: &AppState, secret: &str, body: Value) -> (StatusCode, Value) {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/admin/grants")
+        .header(ADMIN_SECRET_HEADER, secret)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn key(user: &str, feature: &str) -> AuthzCacheKey {
+    AuthzCacheKey::new(user, feature, None)
+}
+
+#[tokio::test]
+async fn test_grant_writes_tuple_and_invalidates_cache() {
+    let (openfga_url, writes) = spawn_write_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+    cache_decision(&state, key("user-1", "reports"), false, None).await;
+    cache_decision(&state, key("user-2", "reports"), false, None).await;
+
+    let request = json!({ "user": "user-1", "feature": "reports", "relation": "viewer" });
+    let (status, tuple) = grant(&state, common::ADMIN_SECRET, request.clone()).await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    let expected =
+        json!({ "user": "user:user-1", "relation": "viewer", "object": "feature:reports" });
+    assert_eq!(tuple, expected);
+    assert_eq!(
+        writes.lock().unwrap()[0]["writes"]["tuple_keys"][0],
+        expected
+    );
+    assert!(state.cache.get(&key("user-1", "reports")).await.is_none());
+    assert!(state.cache.get(&key("user-2", "reports")).await.is_some());
+
+    // Granting again is harmless
+    let (status, tuple) = grant(&state, common::ADMIN_SECRET, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(tuple, expected);
+}
+
+#[tokio::test]
+async fn test_grant_requires_admin_secret() {
+    let (openfga_url, writes) = spawn_write_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+
+    let request = json!({ "user": "user-1", "feature": "reports", "relation": "viewer" });
+    let (status, _) = grant(&state, "wrong-secret", request).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(writes.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_grant_rejects_malformed_names() {
+    let (openfga_url, writes) = spawn_write_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+
+    for request in [
+        json!({ "user": "", "feature": "reports", "relation": "viewer" }),
+        json!({ "user": "user-1", "feature": "reports#viewer", "relation": "viewer" }),
+        json!({ "user": "team:ops", "feature": "reports", "relation": "viewer" }),
+    ] {
+        let (status, _) = grant(&state, common::ADMIN_SECRET, request.clone()).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", request);
+    }
+    assert!(writes.lock().unwrap().is_empty());
+}
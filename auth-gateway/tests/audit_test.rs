@@ -0,0 +1,264 @@
+mod common;
+
+use auth_gateway::audit::{self, AuditEvent, AuditQueue, AuditSink, DecisionSink};
+use auth_gateway::auth::{create_router, load_access_rules, AppState, CorsConfig};
+use auth_gateway::telemetry;
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::post,
+    Json,
+};
+use futures_util::future::BoxFuture;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tower::ServiceExt;
+
+const RULES: &str = r#"[
+    { "path": "/reports", "method": "GET", "feature": "reports", "action": "view" },
+    { "path": "/public/*path", "method": "*", "feature": "public_access" }
+]"#;
+
+/// State auditing to a mock HTTP sink; returns the events it receives
+async fn state_with_sink(allowed: bool) -> (AppState, Arc<Mutex<Vec<Value>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let received = events.clone();
+    let sink = axum::Router::new().route(
+        "/audit",
+        post(move |Json(event): Json<Value>| async move {
+            received.lock().unwrap().push(event);
+            StatusCode::NO_CONTENT
+        }),
+    );
+
+    let path = common::write_temp_file("audit_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    state.fga_client.url = common::spawn_openfga(allowed).await;
+    let sink_url = format!("{}/audit", common::spawn_server(sink).await);
+    let sink = AuditSink::Http(sink_url).build(&state.redis_client, &state.http_client);
+    state.audit_queue = Some(AuditQueue::spawn(sink, 16));
+    common::install_test_key(&state).await;
+    (state, events)
+}
+
+async fn get(state: &AppState, uri: &str, token: Option<String>) -> StatusCode {
+    let mut req = Request::builder().uri(uri);
+    if let Some(token) = token {
+        req = req.header(header::AUTHORIZATION, token);
+    }
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+        .status()
+}
+
+/// Events are written in the background, so give them a moment to land
+async fn wait_for_events(events: &Mutex<Vec<Value>>, count: usize) -> Vec<Value> {
+    for _ in 0..50 {
+        if events.lock().unwrap().len() >= count {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    events.lock().unwrap().clone()
+}
+
+#[tokio::test]
+async fn test_forbidden_request_is_audited() {
+    let (state, events) = state_with_sink(false).await;
+
+    let token = common::bearer_token("user-1");
+    assert_eq!(
+        get(&state, "/reports", Some(token)).await,
+        StatusCode::FORBIDDEN
+    );
+
+    let events = wait_for_events(&events, 1).await;
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event["user"], "user-1");
+    assert_eq!(event["feature"], "reports");
+    assert_eq!(event["action"], "view");
+    assert_eq!(event["method"], "GET");
+    assert_eq!(event["path"], "/reports");
+    assert_eq!(event["status"], 403);
+    assert_eq!(event["result"], "forbidden");
+    assert!(event["timestamp"].as_i64().unwrap() > 0);
+    assert!(event["request_id"].is_string());
+}
+
+#[tokio::test]
+async fn test_unauthorized_request_is_audited() {
+    let (state, events) = state_with_sink(true).await;
+
+    assert_eq!(
+        get(&state, "/reports", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let events = wait_for_events(&events, 1).await;
+    assert_eq!(events[0]["status"], 401);
+    assert_eq!(events[0]["user"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_allowed_requests_are_not_audited() {
+    let (state, events) = state_with_sink(true).await;
+
+    let token = common::bearer_token("user-1");
+    assert_eq!(get(&state, "/reports", Some(token)).await, StatusCode::OK);
+    assert_eq!(get(&state, "/public/a", None).await, StatusCode::OK);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(events.lock().unwrap().is_empty());
+}
+
+/// Sink keeping every event it's given
+#[derive(Default)]
+struct CapturingSink(Mutex<Vec<AuditEvent>>);
+
+impl DecisionSink for CapturingSink {
+    fn record(&self, event: AuditEvent) -> BoxFuture<'_, Result<(), String>> {
+        self.0.lock().unwrap().push(event);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+async fn state_with_capturing_sink(allow_sample_rate: f64) -> (AppState, Arc<CapturingSink>) {
+    let (mut state, _events) = state_with_sink(true).await;
+    let sink = Arc::new(CapturingSink::default());
+    state.audit_queue = Some(AuditQueue::spawn(sink.clone(), 16));
+    state.audit_allow_sample_rate = allow_sample_rate;
+    (state, sink)
+}
+
+/// `(result, allowed)` of each captured event, once `count` have arrived
+async fn wait_for_captured(
+    sink: &CapturingSink,
+    count: usize,
+) -> Vec<(&'static str, Option<bool>)> {
+    for _ in 0..50 {
+        if sink.0.lock().unwrap().len() >= count {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let events = sink.0.lock().unwrap();
+    events
+        .iter()
+        .map(|event| (event.result, event.allowed))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_custom_sink_records_sampled_allows_and_denials() {
+    let (state, sink) = state_with_capturing_sink(1.0).await;
+
+    let token = common::bearer_token("user-1");
+    assert_eq!(get(&state, "/reports", Some(token)).await, StatusCode::OK);
+    let recorded = wait_for_captured(&sink, 1).await;
+    assert_eq!(recorded, [("allowed", Some(true))]);
+
+    assert_eq!(
+        get(&state, "/reports", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+    let recorded = wait_for_captured(&sink, 2).await;
+    assert_eq!(recorded[1], ("unauthorized", None));
+}
+
+#[tokio::test]
+async fn test_custom_sink_gets_only_denials_unsampled() {
+    let (state, sink) = state_with_capturing_sink(0.0).await;
+
+    let token = common::bearer_token("user-1");
+    assert_eq!(get(&state, "/reports", Some(token)).await, StatusCode::OK);
+    assert_eq!(
+        get(&state, "/reports", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let recorded = wait_for_captured(&sink, 1).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(recorded, [("unauthorized", None)]);
+    assert_eq!(sink.0.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_upstream_denials_are_not_audited() {
+    let (mut state, sink) = state_with_capturing_sink(0.0).await;
+    let upstream = axum::Router::new().fallback(|| async { StatusCode::FORBIDDEN });
+    state.upstream_url = common::spawn_server(upstream).await;
+
+    let token = common::bearer_token("user-1");
+    assert_eq!(
+        get(&state, "/reports", Some(token)).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(get(&state, "/public/a", None).await, StatusCode::FORBIDDEN);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(sink.0.lock().unwrap().is_empty());
+}
+
+/// Sink that never finishes recording
+struct StuckSink;
+
+impl DecisionSink for StuckSink {
+    fn record(&self, _event: AuditEvent) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(std::future::pending())
+    }
+}
+
+#[tokio::test]
+async fn test_full_queue_drops_and_counts_events() {
+    let handle = telemetry::install_recorder();
+    let mut state = common::test_state(matchit::Router::new());
+    state.audit_queue = Some(AuditQueue::spawn(Arc::new(StuckSink), 1));
+
+    for _ in 0..3 {
+        audit::emit(
+            &state,
+            AuditEvent {
+                timestamp: AuditEvent::now(),
+                request_id: None,
+                method: "GET".into(),
+                path: "/reports".into(),
+                client_ip: None,
+                status: 401,
+                result: "unauthorized",
+                allowed: None,
+                user: None,
+                feature: None,
+                action: None,
+            },
+        );
+    }
+
+    // One event is buffered and the rest are dropped, without waiting
+    let rendered = handle.render();
+    let dropped = rendered
+        .lines()
+        .find_map(|line| line.strip_prefix("audit_events_dropped_total "))
+        .unwrap();
+    assert_eq!(dropped, "2");
+}
+
+#[test]
+fn test_audit_sink_parsing() {
+    assert_eq!("tracing".parse(), Ok(AuditSink::Tracing));
+    assert_eq!(
+        "redis:audit:denials".parse(),
+        Ok(AuditSink::RedisStream("audit:denials".into()))
+    );
+    assert_eq!(
+        "https://siem.example.com/events".parse(),
+        Ok(AuditSink::Http("https://siem.example.com/events".into()))
+    );
+    assert!("redis:".parse::<AuditSink>().is_err());
+    assert!("kafka://topic".parse::<AuditSink>().is_err());
+}
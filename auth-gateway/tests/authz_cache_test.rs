@@ -0,0 +1,177 @@
+mod common;
+
+use auth_gateway::auth::{
+    build_authz_cache, cache_decision, create_router, load_access_rules, AppState, AuthzCacheKey,
+    CorsConfig,
+};
+use auth_gateway::telemetry;
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+const RULES: &str = r#"[{ "path": "/reports", "method": "GET", "feature": "reports" }]"#;
+
+async fn get_reports(state: &AppState, user: &str) -> StatusCode {
+    let req = Request::builder()
+        .uri("/reports")
+        .header(header::AUTHORIZATION, common::bearer_token(user))
+        .body(Body::empty())
+        .unwrap();
+
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn state_with_openfga(allowed: bool) -> (AppState, common::MockOpenFga) {
+    let path = common::write_temp_file("cache_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    let openfga = common::spawn_mock_openfga(allowed).await;
+    state.fga_client.url = openfga.url.clone();
+    common::install_test_key(&state).await;
+    (state, openfga)
+}
+
+#[tokio::test]
+async fn test_grant_takes_effect_after_short_negative_ttl() {
+    let (mut state, openfga) = state_with_openfga(false).await;
+    state.authz_negative_cache_ttl = Duration::from_millis(200);
+
+    assert_eq!(get_reports(&state, "user-1").await, StatusCode::FORBIDDEN);
+
+    // Admin grants access; the denial is still cached briefly...
+    openfga.allowed.store(true, Ordering::SeqCst);
+    assert_eq!(get_reports(&state, "user-1").await, StatusCode::FORBIDDEN);
+
+    // ...but expires well before the 30s grant TTL would
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(get_reports(&state, "user-1").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_denials_not_cached_with_zero_ttl() {
+    let (mut state, openfga) = state_with_openfga(false).await;
+    state.authz_negative_cache_ttl = Duration::ZERO;
+
+    assert_eq!(get_reports(&state, "user-1").await, StatusCode::FORBIDDEN);
+    openfga.allowed.store(true, Ordering::SeqCst);
+    assert_eq!(get_reports(&state, "user-1").await, StatusCode::OK);
+
+    // Grants are still cached for the positive TTL
+    openfga.allowed.store(false, Ordering::SeqCst);
+    assert_eq!(get_reports(&state, "user-1").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_features_expire_on_their_own_ttl() {
+    let rules = r#"[
+        { "path": "/trial", "method": "GET", "feature": "trial", "cache_ttl_secs": 1 },
+        { "path": "/org", "method": "GET", "feature": "org", "cache_ttl_secs": 300 }
+    ]"#;
+    let path = common::write_temp_file("feature_ttl_rules.json", rules);
+    let (state, openfga) = state_with_openfga(true).await;
+    state.router.store(load_access_rules(&path).await.unwrap());
+
+    let get = |uri: &'static str| {
+        let state = state.clone();
+        async move {
+            let req = Request::builder()
+                .uri(uri)
+                .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+                .body(Body::empty())
+                .unwrap();
+            create_router(state, CorsConfig::default())
+                .oneshot(req)
+                .await
+                .unwrap()
+                .status()
+        }
+    };
+    assert_eq!(get("/trial").await, StatusCode::OK);
+    assert_eq!(get("/org").await, StatusCode::OK);
+
+    // Access revoked: the trial grant lapses after 1s, the org grant holds
+    openfga.allowed.store(false, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(get("/trial").await, StatusCode::FORBIDDEN);
+    assert_eq!(get("/org").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_concurrent_misses_share_one_check() {
+    // Slow OpenFGA, so every request arrives while the first check is in flight
+    let checks = Arc::new(AtomicUsize::new(0));
+    let counter = checks.clone();
+    let app = axum::Router::new().route(
+        "/stores/:store_id/check",
+        axum::routing::post(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                axum::Json(serde_json::json!({ "allowed": true }))
+            }
+        }),
+    );
+    let (mut state, _openfga) = state_with_openfga(true).await;
+    state.fga_client.url = common::spawn_server(app).await;
+
+    let requests = (0..50).map(|_| {
+        let state = state.clone();
+        tokio::spawn(async move { get_reports(&state, "user-1").await })
+    });
+    for status in futures_util::future::join_all(requests).await {
+        assert_eq!(status.unwrap(), StatusCode::OK);
+    }
+
+    assert_eq!(checks.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_cache_evicts_beyond_max_capacity() {
+    let mut state = common::test_state(matchit::Router::new());
+    state.cache = build_authz_cache(10);
+
+    for i in 0..100 {
+        let key = AuthzCacheKey::new(&format!("user-{}", i), "reports", None);
+        cache_decision(&state, key, true, None).await;
+    }
+    state.cache.run_pending_tasks().await;
+
+    assert!(
+        state.cache.entry_count() <= 10,
+        "{} entries",
+        state.cache.entry_count()
+    );
+}
+
+#[tokio::test]
+async fn test_sampled_cache_hit_detects_stale_grant() {
+    let handle = telemetry::install_recorder();
+    let (mut state, openfga) = state_with_openfga(true).await;
+    state.authz_cache_verify_rate = 1.0;
+    assert_eq!(get_reports(&state, "user-1").await, StatusCode::OK);
+
+    // Revoked in OpenFGA, but the grant is still cached and still served
+    openfga.allowed.store(false, Ordering::SeqCst);
+    assert_eq!(get_reports(&state, "user-1").await, StatusCode::OK);
+
+    // The re-check runs in the background
+    let metric = r#"authz_cache_staleness_total{kind="stale_allow"}"#;
+    for _ in 0..100 {
+        if handle.render().contains(metric) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(handle.render().contains(metric));
+    assert_eq!(openfga.checks.lock().unwrap().len(), 2);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, AppState, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use tower::ServiceExt;
+
+const RULES: &str = r#"[{
+    "path": "/rpc",
+    "method": "POST",
+    "feature": "accounts",
+    "action_from_body": {
+        "pointer": "/operation",
+        "actions": { "delete": "delete", "list": "view" }
+    }
+}]"#;
+
+async fn state_with_openfga() -> (AppState, common::MockOpenFga) {
+    // Upstream echoes the body it received
+    let upstream = axum::Router::new().fallback(|body: String| async move { body });
+    let path = common::write_temp_file("body_action_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+    let openfga = common::spawn_mock_openfga(true).await;
+    state.fga_client.url = openfga.url.clone();
+    common::install_test_key(&state).await;
+    (state, openfga)
+}
+
+async fn post_rpc(state: &AppState, body: &str) -> (StatusCode, String) {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/rpc")
+        .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+#[tokio::test]
+async fn test_action_resolved_from_body() {
+    let (state, openfga) = state_with_openfga().await;
+    let body = r#"{"operation":"delete","id":42}"#;
+
+    let (status, echoed) = post_rpc(&state, body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    // The buffered body still reaches upstream intact
+    assert_eq!(echoed, body);
+    let checks = openfga.checks.lock().unwrap();
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0]["tuple_key"]["relation"], "delete");
+    assert_eq!(checks[0]["tuple_key"]["object"], "feature:accounts");
+}
+
+#[tokio::test]
+async fn test_operations_are_cached_separately() {
+    let (state, openfga) = state_with_openfga().await;
+
+    post_rpc(&state, r#"{"operation":"list"}"#).await;
+    post_rpc(&state, r#"{"operation":"delete"}"#).await;
+
+    let checks = openfga.checks.lock().unwrap();
+    let relations: Vec<_> = checks
+        .iter()
+        .map(|c| c["tuple_key"]["relation"].clone())
+        .collect();
+    assert_eq!(relations, ["view", "delete"]);
+}
+
+#[tokio::test]
+async fn test_unmapped_operation_is_forbidden() {
+    let (state, openfga) = state_with_openfga().await;
+
+    let (status, _) = post_rpc(&state, r#"{"operation":"drop_table"}"#).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(openfga.checks.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_body_without_operation_is_rejected() {
+    let (state, openfga) = state_with_openfga().await;
+
+    for body in ["not json", r#"{"query":"{ users }"}"#, r#"{"operation":7}"#] {
+        let (status, _) = post_rpc(&state, body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+    assert!(openfga.checks.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_oversize_body_is_rejected() {
+    let (mut state, _openfga) = state_with_openfga().await;
+    state.max_body_bytes = 16;
+
+    let (status, _) = post_rpc(&state, r#"{"operation":"delete","padding":"xxxxxxxx"}"#).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
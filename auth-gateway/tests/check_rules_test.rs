@@ -0,0 +1,49 @@
+mod common;
+
+use std::process::{Command, Output};
+
+/// Run `auth-gateway --check-rules <path>` with none of the service env vars
+fn check_rules(path: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_auth-gateway"))
+        .args(["--check-rules", path])
+        .env_clear()
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_valid_rules_file_passes() {
+    let rules = r#"[
+        { "path": "/reports", "method": "GET", "feature": "reports", "action": "view" },
+        { "path": "/auth/*path", "method": "*", "feature": "public_access", "target": "zitadel" }
+    ]"#;
+    let path = common::write_temp_file("check_good_rules.json", rules);
+
+    let output = check_rules(&path);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("OK"));
+}
+
+#[test]
+fn test_invalid_rules_file_fails_with_report() {
+    let rules = r#"[
+        { "path": "/reports", "method": "GET", "feature": "reports", "action": "veiw" },
+        { "path": "/reports", "method": "GET", "feature": "reports" }
+    ]"#;
+    let path = common::write_temp_file("check_bad_rules.json", rules);
+
+    let output = check_rules(&path);
+
+    assert_eq!(output.status.code(), Some(1));
+    let report = String::from_utf8_lossy(&output.stderr);
+    assert!(report.contains("INVALID"), "{}", report);
+    assert!(report.contains("unknown action 'veiw'"), "{}", report);
+    assert!(report.contains("duplicate rule"), "{}", report);
+}
+
+#[test]
+fn test_unreadable_rules_file_fails() {
+    let output = check_rules("/nonexistent/access_rules.json");
+    assert_eq!(output.status.code(), Some(1));
+}
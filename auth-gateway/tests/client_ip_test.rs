@@ -0,0 +1,114 @@
+mod common;
+
+use auth_gateway::auth::{client_ip, AppState};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+};
+use std::net::{IpAddr, SocketAddr};
+
+const PROXY: &str = "10.0.0.1";
+
+/// Request accepted from `peer`, carrying each `X-Forwarded-For` value given
+fn request(peer: &str, forwarded_for: &[&str]) -> Request {
+    let mut req = Request::builder();
+    for value in forwarded_for {
+        req = req.header("x-forwarded-for", *value);
+    }
+    let mut req = req.body(Body::empty()).unwrap();
+    let addr = SocketAddr::new(peer.parse().unwrap(), 40000);
+    req.extensions_mut().insert(ConnectInfo(addr));
+    req
+}
+
+fn ip(s: &str) -> Option<IpAddr> {
+    Some(s.parse().unwrap())
+}
+
+fn state_with_hops(hops: usize) -> AppState {
+    let mut state = common::test_state(matchit::Router::new());
+    state.trusted_proxy_hops = hops;
+    state
+}
+
+#[test]
+fn test_no_connection_info() {
+    let state = state_with_hops(1);
+    let req = Request::builder().body(Body::empty()).unwrap();
+    assert_eq!(client_ip(&state, &req), None);
+}
+
+#[test]
+fn test_headers_ignored_without_trusted_proxies() {
+    let state = state_with_hops(0);
+
+    let mut req = request("192.0.2.7", &["203.0.113.9"]);
+    req.headers_mut()
+        .insert("x-real-ip", "203.0.113.9".parse().unwrap());
+    assert_eq!(client_ip(&state, &req), ip("192.0.2.7"));
+}
+
+#[test]
+fn test_single_hop_takes_last_entry() {
+    let state = state_with_hops(1);
+
+    // Whatever the client prepends, the proxy's own entry is last
+    let cases: &[(&[&str], &str)] = &[
+        (&["203.0.113.9"], "203.0.113.9"),
+        (&["1.1.1.1, 203.0.113.9"], "203.0.113.9"),
+        (&["1.1.1.1,203.0.113.9"], "203.0.113.9"),
+        (&["1.1.1.1", "203.0.113.9"], "203.0.113.9"),
+        (&["2001:db8::1"], "2001:db8::1"),
+    ];
+    for (forwarded_for, expected) in cases {
+        let req = request(PROXY, forwarded_for);
+        assert_eq!(client_ip(&state, &req), ip(expected), "{:?}", forwarded_for);
+    }
+}
+
+#[test]
+fn test_multiple_hops() {
+    let state = state_with_hops(2);
+
+    // CDN -> load balancer -> gateway: the CDN saw the client
+    let req = request(PROXY, &["1.1.1.1, 203.0.113.9, 10.0.0.2"]);
+    assert_eq!(client_ip(&state, &req), ip("203.0.113.9"));
+
+    // Fewer entries than hops: the leftmost is as far back as we can see
+    let req = request(PROXY, &["10.0.0.2"]);
+    assert_eq!(client_ip(&state, &req), ip("10.0.0.2"));
+}
+
+#[test]
+fn test_missing_or_garbled_header_falls_back_to_peer() {
+    let state = state_with_hops(1);
+
+    assert_eq!(client_ip(&state, &request(PROXY, &[])), ip(PROXY));
+    assert_eq!(client_ip(&state, &request(PROXY, &["unknown"])), ip(PROXY));
+    assert_eq!(
+        client_ip(&state, &request(PROXY, &["203.0.113.9, not-an-ip"])),
+        ip(PROXY)
+    );
+}
+
+#[test]
+fn test_trusted_proxy_list() {
+    let mut state = state_with_hops(0);
+    state.trusted_proxies = vec![PROXY.parse().unwrap(), "10.0.0.2".parse().unwrap()];
+
+    // Direct clients can't claim another address
+    let req = request("192.0.2.7", &["203.0.113.9"]);
+    assert_eq!(client_ip(&state, &req), ip("192.0.2.7"));
+
+    // Behind proxies, the nearest untrusted hop wins over anything it prepended
+    let req = request(PROXY, &["1.1.1.1, 203.0.113.9, 10.0.0.2"]);
+    assert_eq!(client_ip(&state, &req), ip("203.0.113.9"));
+
+    let mut req = request(PROXY, &[]);
+    req.headers_mut()
+        .insert("x-real-ip", "203.0.113.9".parse().unwrap());
+    assert_eq!(client_ip(&state, &req), ip("203.0.113.9"));
+
+    // A proxy that says nothing is itself the client
+    assert_eq!(client_ip(&state, &request(PROXY, &[])), ip(PROXY));
+}
@@ -0,0 +1,260 @@
+// Shared helpers for integration tests
+#![allow(dead_code)]
+
+use arc_swap::ArcSwap;
+use auth_gateway::auth::{
+    build_authz_cache, build_list_objects_cache, default_method_actions, AppState, DefaultPolicy,
+    HttpClientConfig, JwtIssuer, MethodRoutes, OpenFgaClient, RateLimitFailMode, RetryPolicy,
+    DEFAULT_AUTHZ_CACHE_MAX_CAPACITY, DEFAULT_MAINTENANCE_RETRY_AFTER_SECS, DEFAULT_MAX_BODY_BYTES,
+    DEFAULT_MAX_URL_LEN, DEFAULT_UPSTREAM_RETRY_ATTEMPTS, DEFAULT_UPSTREAM_TIMEOUT_SECS,
+};
+use auth_gateway::introspection::{
+    build_introspection_cache, DEFAULT_INTROSPECTION_CACHE_TTL_SECS,
+    DEFAULT_INTROSPECTION_NEGATIVE_CACHE_TTL_SECS,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header};
+use matchit::Router;
+use moka::future::Cache;
+use redis::Client as RedisClient;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Admin secret configured on `test_state`
+pub const ADMIN_SECRET: &str = "test-admin-secret";
+
+/// Signing secret configured on `test_state` for webhook verification
+pub const WEBHOOK_SECRET: &str = "test-webhook-secret";
+
+/// Hex HMAC-SHA256 of `body` under `WEBHOOK_SECRET`
+pub fn sign_webhook(body: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(WEBHOOK_SECRET.as_bytes()).unwrap();
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Build an `AppState` pointing at dummy dependencies.
+///
+/// Nothing here connects eagerly, so tests that never reach Redis/OpenFGA
+/// can use it as-is and override individual fields as needed. The rate
+/// limiter fails open so middleware tests work without a Redis server.
+pub fn test_state(router: Router<MethodRoutes>) -> AppState {
+    AppState {
+        http_client: reqwest::Client::new(),
+        upstream_client: HttpClientConfig::default().build_upstream().unwrap(),
+        upstream_h2_client: HttpClientConfig::default().build_upstream_h2().unwrap(),
+        fga_client: OpenFgaClient::new("http://openfga:8080".into(), "dummy-store-id".into()),
+        router: Arc::new(ArcSwap::from_pointee(router)),
+        access_rules_path: "access_rules.json".into(),
+        default_policy: DefaultPolicy::Deny,
+        unmatched_status: Default::default(),
+        cache: build_authz_cache(DEFAULT_AUTHZ_CACHE_MAX_CAPACITY),
+        authz_cache_ttl: Duration::from_secs(30),
+        authz_negative_cache_ttl: Duration::from_secs(5),
+        authz_cache_verify_rate: 0.0,
+        stale_grants: None,
+        model_error_status: Default::default(),
+        jwks_cache: Cache::new(10),
+        jwt_issuers: issuers(&[(TEST_ISSUER, "http://jwks")]),
+        jwt_audience: None,
+        jwt_algorithms: vec![Algorithm::RS256],
+        jwt_leeway_secs: 60,
+        jwks_retry: RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+        },
+        introspection: None,
+        token_cache: None,
+        token_denylist: false,
+        tenant_source: None,
+        introspection_cache: build_introspection_cache(
+            Duration::from_secs(DEFAULT_INTROSPECTION_CACHE_TTL_SECS),
+            Duration::from_secs(DEFAULT_INTROSPECTION_NEGATIVE_CACHE_TTL_SECS),
+        ),
+        zitadel_api_url: "http://zitadel".into(),
+        openfga_url: "http://openfga:8080".into(),
+        redis_client: RedisClient::open("redis://127.0.0.1/").unwrap(),
+        trusted_proxies: Vec::new(),
+        trusted_proxy_hops: 0,
+        concurrency_limiter: None,
+        // No Redis in most test environments, so let requests past the limiter
+        rate_limit_fail_mode: RateLimitFailMode::Open,
+        openfga_context_headers: Vec::new(),
+        method_actions: Arc::new(default_method_actions()),
+        upstream_url: "http://upstream".into(),
+        services: Arc::new(HashMap::new()),
+        http2_services: Arc::new(HashSet::new()),
+        max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+        max_url_len: DEFAULT_MAX_URL_LEN,
+        upstream_timeout: Duration::from_secs(DEFAULT_UPSTREAM_TIMEOUT_SECS),
+        upstream_retry_attempts: DEFAULT_UPSTREAM_RETRY_ATTEMPTS,
+        maintenance_mode: Arc::new(AtomicBool::new(false)),
+        maintenance_retry_after_secs: DEFAULT_MAINTENANCE_RETRY_AFTER_SECS,
+        webhook_signing_secret: Some(WEBHOOK_SECRET.into()),
+        webhook_timestamp_tolerance: None,
+        user_registration: Default::default(),
+        admin_secret: Some(ADMIN_SECRET.into()),
+        upstream_secret: None,
+        strip_upstream_auth: true,
+        forward_header_mode: Default::default(),
+        forward_headers: Vec::new(),
+        response_compression: false,
+        access_log: false,
+        debug_headers: false,
+        audit_queue: None,
+        audit_allow_sample_rate: 0.0,
+        listable_objects: vec![("feature".into(), "viewer".into())],
+        list_objects_cache: build_list_objects_cache(Duration::from_secs(30)),
+    }
+}
+
+/// Write `contents` to a unique temp file and return its path
+pub fn write_temp_file(name: &str, contents: &str) -> String {
+    let path = std::env::temp_dir().join(format!(
+        "auth-gateway-{}-{}-{}",
+        std::process::id(),
+        next_id(),
+        name
+    ));
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn next_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Serve `app` on an ephemeral local port and return its base URL
+pub async fn spawn_server(app: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+/// Redis client for tests that need a live server.
+///
+/// Returns `None` unless `TEST_REDIS_URL` is set, so those tests are skipped
+/// in environments without Redis.
+pub fn test_redis() -> Option<RedisClient> {
+    let url = std::env::var("TEST_REDIS_URL").ok()?;
+    Some(RedisClient::open(url).expect("Invalid TEST_REDIS_URL"))
+}
+
+/// Unique identifier so tests sharing a Redis don't collide on keys
+pub fn unique_id(prefix: &str) -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    format!("{}-{}-{}-{}", prefix, std::process::id(), nanos, next_id())
+}
+
+pub const RSA_PRIVATE: &[u8] = include_bytes!("../fixtures/rsa_private.pem");
+pub const RSA_PUBLIC: &[u8] = include_bytes!("../fixtures/rsa_public.pem");
+pub const TEST_KID: &str = "test-key";
+pub const TEST_ISSUER: &str = "https://issuer.test";
+
+/// Trusted issuers for `AppState::jwt_issuers`, as `(iss, jwks_url)` pairs.
+/// No refresh throttling, so tests can rotate keys back to back.
+pub fn issuers(entries: &[(&str, &str)]) -> Arc<HashMap<String, JwtIssuer>> {
+    Arc::new(
+        entries
+            .iter()
+            .map(|(iss, jwks_url)| {
+                (
+                    iss.to_string(),
+                    JwtIssuer::new(jwks_url.to_string(), Duration::ZERO),
+                )
+            })
+            .collect(),
+    )
+}
+
+pub fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// Sign `claims` as RS256 with the fixture key under `TEST_KID`
+/// (`iss` defaults to `TEST_ISSUER`)
+pub fn sign_rs256(mut claims: serde_json::Value) -> String {
+    if claims.get("iss").is_none() {
+        claims["iss"] = TEST_ISSUER.into();
+    }
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(TEST_KID.into());
+    jsonwebtoken::encode(
+        &header,
+        &claims,
+        &EncodingKey::from_rsa_pem(RSA_PRIVATE).unwrap(),
+    )
+    .unwrap()
+}
+
+/// A valid bearer token for `sub`, expiring in five minutes
+pub fn bearer_token(sub: &str) -> String {
+    format!(
+        "Bearer {}",
+        sign_rs256(serde_json::json!({ "sub": sub, "exp": now() + 300 }))
+    )
+}
+
+/// Pre-populate the JWKS cache with the fixture key so no fetch happens
+pub async fn install_test_key(state: &AppState) {
+    state
+        .jwks_cache
+        .insert(
+            (TEST_ISSUER.into(), TEST_KID.into()),
+            DecodingKey::from_rsa_pem(RSA_PUBLIC).unwrap(),
+        )
+        .await;
+}
+
+/// Mock OpenFGA answering every `/check` with a fixed decision
+pub async fn spawn_openfga(allowed: bool) -> String {
+    spawn_mock_openfga(allowed).await.url
+}
+
+/// Handle to a running mock OpenFGA
+pub struct MockOpenFga {
+    pub url: String,
+    /// Decision returned by `/check`; can be flipped while running
+    pub allowed: Arc<AtomicBool>,
+    /// Every `/check` request body received, in order
+    pub checks: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+pub async fn spawn_mock_openfga(allowed: bool) -> MockOpenFga {
+    let decision = Arc::new(AtomicBool::new(allowed));
+    let checks = Arc::new(Mutex::new(Vec::new()));
+    let (decision_handle, checks_handle) = (decision.clone(), checks.clone());
+
+    let app = axum::Router::new().route(
+        "/stores/:store_id/check",
+        axum::routing::post(move |axum::Json(body): axum::Json<serde_json::Value>| {
+            checks_handle.lock().unwrap().push(body);
+            let allowed = decision_handle.load(Ordering::SeqCst);
+            async move { axum::Json(serde_json::json!({ "allowed": allowed })) }
+        }),
+    );
+
+    MockOpenFga {
+        url: spawn_server(app).await,
+        allowed: decision,
+        checks,
+    }
+}
+
+/// Mock upstream answering every request with 200 "upstream ok"
+pub async fn spawn_upstream() -> String {
+    let app = axum::Router::new().fallback(|| async { "upstream ok" });
+    spawn_server(app).await
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, AppState, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Request, StatusCode},
+};
+use tower::ServiceExt;
+
+const RULES: &str = r#"[
+    { "path": "/public/*path", "method": "GET", "feature": "public_access" }
+]"#;
+
+/// Upstream returning a large JSON document, or a zip archive under /public/archive
+async fn state_with_large_upstream(response_compression: bool) -> AppState {
+    let json = serde_json::to_string(&vec!["the same row again"; 1000]).unwrap();
+    let upstream = axum::Router::new()
+        .route(
+            "/public/archive",
+            axum::routing::get(|| async {
+                ([(header::CONTENT_TYPE, "application/zip")], vec![0u8; 4096])
+            }),
+        )
+        .fallback(move || async move { ([(header::CONTENT_TYPE, "application/json")], json) });
+
+    let path = common::write_temp_file("compression_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+    state.response_compression = response_compression;
+    state
+}
+
+async fn get(state: &AppState, uri: &str, accept_encoding: &str) -> (HeaderMap, usize) {
+    let req = Request::builder()
+        .uri(uri)
+        .header(header::ACCEPT_ENCODING, accept_encoding)
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers().clone();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (headers, body.len())
+}
+
+#[tokio::test]
+async fn test_large_response_is_gzipped() {
+    let state = state_with_large_upstream(true).await;
+
+    let (plain, plain_len) = get(&state, "/public/data", "identity").await;
+    let (gzipped, gzipped_len) = get(&state, "/public/data", "gzip").await;
+
+    assert!(plain.get(header::CONTENT_ENCODING).is_none());
+    assert_eq!(gzipped[header::CONTENT_ENCODING], "gzip");
+    assert!(
+        gzipped_len < plain_len / 10,
+        "{} vs {}",
+        gzipped_len,
+        plain_len
+    );
+}
+
+#[tokio::test]
+async fn test_brotli_preferred_when_accepted() {
+    let state = state_with_large_upstream(true).await;
+
+    let (headers, _) = get(&state, "/public/data", "br;q=1.0, gzip;q=0.5").await;
+
+    assert_eq!(headers[header::CONTENT_ENCODING], "br");
+}
+
+#[tokio::test]
+async fn test_compressed_formats_pass_through() {
+    let state = state_with_large_upstream(true).await;
+
+    let (headers, len) = get(&state, "/public/archive", "gzip").await;
+
+    assert!(headers.get(header::CONTENT_ENCODING).is_none());
+    assert_eq!(len, 4096);
+}
+
+#[tokio::test]
+async fn test_compression_disabled_by_default() {
+    let state = state_with_large_upstream(false).await;
+
+    let (headers, _) = get(&state, "/public/data", "gzip").await;
+
+    assert!(headers.get(header::CONTENT_ENCODING).is_none());
+}
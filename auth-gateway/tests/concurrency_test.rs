@@ -0,0 +1,135 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, AppState, CorsConfig};
+use auth_gateway::concurrency::ConcurrencyLimiter;
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tower::ServiceExt;
+
+const RULES: &str = r#"[
+    { "path": "/slow", "method": "GET", "feature": "reports", "action": "view" }
+]"#;
+
+const LIMIT: usize = 2;
+
+/// Upstream that holds every request until `release` gets a permit; returns
+/// how many requests have arrived
+async fn state_with_slow_upstream() -> (AppState, Arc<Semaphore>, Arc<AtomicUsize>) {
+    let release = Arc::new(Semaphore::new(0));
+    let arrived = Arc::new(AtomicUsize::new(0));
+    let upstream = {
+        let release = release.clone();
+        let arrived = arrived.clone();
+        axum::Router::new().fallback(move || async move {
+            arrived.fetch_add(1, Ordering::SeqCst);
+            release.acquire().await.unwrap().forget();
+            "upstream ok"
+        })
+    };
+
+    let path = common::write_temp_file("concurrency_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+    state.fga_client.url = common::spawn_openfga(true).await;
+    state.concurrency_limiter = Some(Arc::new(ConcurrencyLimiter::new(LIMIT)));
+    common::install_test_key(&state).await;
+    (state, release, arrived)
+}
+
+async fn get(state: &AppState, user: &str) -> StatusCode {
+    let req = Request::builder()
+        .uri("/slow")
+        .header(header::AUTHORIZATION, common::bearer_token(user))
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let status = response.status();
+    axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    status
+}
+
+async fn wait_until(arrived: &AtomicUsize, count: usize) {
+    for _ in 0..100 {
+        if arrived.load(Ordering::SeqCst) >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!(
+        "only {} requests reached upstream",
+        arrived.load(Ordering::SeqCst)
+    );
+}
+
+#[tokio::test]
+async fn test_request_over_in_flight_limit_is_rejected() {
+    let (state, release, arrived) = state_with_slow_upstream().await;
+
+    let in_flight: Vec<_> = (0..LIMIT)
+        .map(|_| {
+            let state = state.clone();
+            tokio::spawn(async move { get(&state, "user-1").await })
+        })
+        .collect();
+    wait_until(&arrived, LIMIT).await;
+
+    assert_eq!(get(&state, "user-1").await, StatusCode::TOO_MANY_REQUESTS);
+    let limiter = state.concurrency_limiter.as_ref().unwrap();
+    assert_eq!(limiter.in_flight("user-1"), LIMIT);
+
+    // Other users have their own slots
+    let other_user = {
+        let state = state.clone();
+        tokio::spawn(async move { get(&state, "user-2").await })
+    };
+    wait_until(&arrived, LIMIT + 1).await;
+
+    release.add_permits(LIMIT + 1);
+    for request in in_flight {
+        assert_eq!(request.await.unwrap(), StatusCode::OK);
+    }
+    assert_eq!(other_user.await.unwrap(), StatusCode::OK);
+    assert_eq!(limiter.in_flight("user-1"), 0);
+
+    // Slots freed by completed requests can be reused
+    release.add_permits(1);
+    assert_eq!(get(&state, "user-1").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_slot_released_when_upstream_fails() {
+    let (mut state, _release, _arrived) = state_with_slow_upstream().await;
+    // Nothing listens here
+    state.upstream_url = "http://127.0.0.1:1".to_string();
+
+    for _ in 0..LIMIT + 1 {
+        assert_eq!(get(&state, "user-1").await, StatusCode::BAD_GATEWAY);
+    }
+    let limiter = state.concurrency_limiter.as_ref().unwrap();
+    assert_eq!(limiter.in_flight("user-1"), 0);
+}
+
+#[test]
+fn test_guard_releases_slot_on_drop() {
+    let limiter = Arc::new(ConcurrencyLimiter::new(1));
+
+    let guard = limiter.try_acquire("user-1").unwrap();
+    assert!(limiter.try_acquire("user-1").is_none());
+    assert!(limiter.try_acquire("user-2").is_some());
+
+    drop(guard);
+    assert_eq!(limiter.in_flight("user-1"), 0);
+    assert!(limiter.try_acquire("user-1").is_some());
+}
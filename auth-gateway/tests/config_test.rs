@@ -0,0 +1,160 @@
+use auth_gateway::auth::{
+    default_method_actions, Consistency, DefaultPolicy, UnmatchedStatus, DEFAULT_MAX_URL_LEN,
+};
+use auth_gateway::config::Config;
+use jsonwebtoken::Algorithm;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+
+const REQUIRED: [(&str, &str); 5] = [
+    ("OPENFGA_URL", "http://openfga:8080"),
+    ("OPENFGA_STORE_ID", "store-1"),
+    ("ZITADEL_ISSUER_URL", "https://issuer.test"),
+    ("ZITADEL_API_URL", "https://zitadel.test"),
+    ("REDIS_URL", "redis://redis:6379"),
+];
+
+#[test]
+fn test_all_missing_vars_reported_at_once() {
+    let errors = Config::from_vars([("OPENFGA_URL", "http://openfga:8080")])
+        .unwrap_err()
+        .0;
+
+    assert_eq!(
+        errors,
+        vec![
+            "OPENFGA_STORE_ID must be set",
+            "ZITADEL_ISSUER_URL must be set",
+            "ZITADEL_API_URL must be set",
+            "REDIS_URL must be set",
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_values_reported_with_missing_ones() {
+    let err = Config::from_vars([
+        ("ZITADEL_API_URL", "https://zitadel.test"),
+        ("DEFAULT_POLICY", "allow"),
+        ("TRUSTED_PROXIES", "10.0.0.1, not-an-ip"),
+        ("JWT_ALGORITHMS", "RS256,XS999"),
+        ("WEBHOOK_TIMESTAMP_TOLERANCE_SECS", "300s"),
+        ("STRIP_UPSTREAM_AUTH", "nope"),
+    ])
+    .unwrap_err();
+
+    let message = err.to_string();
+    for expected in [
+        "OPENFGA_URL must be set",
+        "REDIS_URL must be set",
+        r#"DEFAULT_POLICY must be 'deny' or 'proxy' (got "allow")"#,
+        r#"TRUSTED_PROXIES must be IP addresses (got ["not-an-ip"])"#,
+        r#"JWT_ALGORITHMS must be signing algorithms such as RS256 (got ["XS999"])"#,
+        r#"WEBHOOK_TIMESTAMP_TOLERANCE_SECS must be a whole number of seconds (got "300s")"#,
+        r#"STRIP_UPSTREAM_AUTH must be true or false (got "nope")"#,
+    ] {
+        assert!(message.contains(expected), "{} in {}", expected, message);
+    }
+    assert_eq!(err.0.len(), 9);
+}
+
+#[test]
+fn test_valid_vars_and_defaults() {
+    let config = Config::from_vars(REQUIRED.into_iter().chain([
+        ("UNMATCHED_STATUS", "404"),
+        ("TRUSTED_PROXIES", "10.0.0.1,10.0.0.2"),
+        ("AUDIT_SINK", ""),
+        ("OPENFGA_CONSISTENCY", "higher_consistency"),
+        ("WEBHOOK_TIMESTAMP_TOLERANCE_SECS", "300"),
+    ]))
+    .unwrap();
+
+    assert_eq!(config.openfga_store_id, "store-1");
+    assert_eq!(config.unmatched_status, UnmatchedStatus::NotFound);
+    assert_eq!(config.default_policy, DefaultPolicy::Deny);
+    assert_eq!(config.trusted_proxies.len(), 2);
+    assert_eq!(config.jwt_algorithms, vec![Algorithm::RS256]);
+    assert_eq!(
+        config.openfga_consistency,
+        Some(Consistency::HigherConsistency)
+    );
+    assert_eq!(config.webhook_timestamp_tolerance_secs, Some(300));
+    assert!(config.strip_upstream_auth);
+    // Empty counts as unset
+    assert!(config.audit_sink.is_none());
+    assert_eq!(config.jwt_issuers, vec!["https://issuer.test"]);
+    assert_eq!(config.max_url_len, DEFAULT_MAX_URL_LEN);
+    assert_eq!(config.method_actions, default_method_actions());
+    assert_eq!(
+        config.listable_objects,
+        vec![("feature".to_string(), "viewer".to_string())]
+    );
+}
+
+#[test]
+fn test_optional_settings_are_validated_too() {
+    let err = Config::from_vars(REQUIRED.into_iter().chain([
+        ("JWT_LEEWAY_SECS", "1m"),
+        ("MAX_URL_LEN", "8k"),
+        ("ALLOWED_METHODS", "get, NOT A METHOD"),
+        ("METHOD_DEFAULT_ACTIONS", "{bad"),
+        ("UPSTREAM_SERVICES", r#"["billing"]"#),
+        ("AUDIT_ALLOW_SAMPLE_RATE", "2"),
+        ("INTROSPECTION_CLIENT_ID", "gateway"),
+    ]))
+    .unwrap_err();
+
+    let message = err.to_string();
+    for expected in [
+        r#"JWT_LEEWAY_SECS must be a whole number of seconds (got "1m")"#,
+        r#"MAX_URL_LEN must be a number of bytes (got "8k")"#,
+        r#"ALLOWED_METHODS must be HTTP methods (got ["NOT A METHOD"])"#,
+        "METHOD_DEFAULT_ACTIONS must be a JSON object of HTTP method to action",
+        "UPSTREAM_SERVICES must be a JSON object of service name to URL",
+        r#"AUDIT_ALLOW_SAMPLE_RATE must be between 0 and 1 (got "2")"#,
+        "INTROSPECTION_CLIENT_ID and INTROSPECTION_CLIENT_SECRET must be set together",
+    ] {
+        assert!(message.contains(expected), "{} in {}", expected, message);
+    }
+    assert_eq!(err.0.len(), 7);
+}
+
+#[test]
+fn test_non_unicode_environment() {
+    let vars = REQUIRED
+        .into_iter()
+        .filter(|(name, _)| *name != "REDIS_URL")
+        .map(|(name, value)| (OsString::from(name), OsString::from(value)))
+        .chain([
+            (
+                OsString::from("REDIS_URL"),
+                OsString::from_vec(b"redis://\xff".to_vec()),
+            ),
+            // Not a setting, so never looked at
+            (OsString::from("UNRELATED"), OsString::from_vec(vec![0xff])),
+            (OsString::from_vec(vec![0xff]), OsString::from("value")),
+        ]);
+
+    let errors = Config::from_os_vars(vars).unwrap_err().0;
+    assert_eq!(
+        errors,
+        vec!["REDIS_URL must be valid Unicode", "REDIS_URL must be set"]
+    );
+}
+
+#[test]
+fn test_flags_only_disable_on_explicit_false() {
+    let strip = |value: &'static str| {
+        Config::from_vars(REQUIRED.into_iter().chain([("STRIP_UPSTREAM_AUTH", value)]))
+            .map(|config| config.strip_upstream_auth)
+            .map_err(|e| e.to_string())
+    };
+
+    for value in ["TRUE", "yes", "On", "1", ""] {
+        assert_eq!(strip(value), Ok(true), "{:?}", value);
+    }
+    for value in ["false", "0", "No", "OFF"] {
+        assert_eq!(strip(value), Ok(false), "{:?}", value);
+    }
+    assert!(strip("disabled").is_err());
+}
@@ -1,11 +1,16 @@
-use auth_gateway::auth::{create_router, AppState, OpenFgaClient};
+mod common;
+
+use auth_gateway::auth::{
+    auth_middleware, create_router, load_access_rules, proxy_handler, CorsConfig,
+};
 use axum::{
     body::Body,
     http::{header, Method, Request, StatusCode},
+    middleware,
+    routing::any,
 };
 use matchit::Router;
-use moka::future::Cache;
-use redis::Client as RedisClient;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 // use tower::Service removed
 use tower::ServiceExt; // for `oneshot`
@@ -13,31 +18,9 @@ use tower::ServiceExt; // for `oneshot`
 #[tokio::test]
 async fn test_cors_configuration() {
     // 1. Setup Mock State
-    let http_client = reqwest::Client::new();
-    let fga_client = OpenFgaClient::new("http://openfga:8080".into(), "dummy-store-id".into());
-    let router = Arc::new(Router::new());
-
-    // We don't need real redis/cache for CORS options check generally,
-    // but the app state build needs them.
-    // For unit testing, mocking would be better, but we need to supply valid structs.
-    // Redis client is just a client handle, doesn't connect immediately usually unless we call get_connection.
-    // But main() calls config check.
-
-    // Redis Client
-    let redis_client = RedisClient::open("redis://127.0.0.1/").unwrap();
-
-    let state = AppState {
-        http_client,
-        fga_client,
-        router,
-        cache: Cache::new(10),
-        jwks_cache: Cache::new(10),
-        jwks_url: "http://jwks".into(),
-        zitadel_api_url: "http://zitadel".into(),
-        openfga_url: "http://openfga:8080".into(),
-        redis_client,
-        upstream_url: "http://upstream".into(),
-    };
+    // Redis client is just a client handle and doesn't connect until used,
+    // so the shared dummy state is enough for a CORS preflight check.
+    let state = common::test_state(Router::new());
 
     // 2. Define Allowed Origins
     let allowed_origin = "http://localhost:3000"
@@ -46,7 +29,7 @@ async fn test_cors_configuration() {
     let allowed_origins = vec![allowed_origin.clone()];
 
     // 3. Create Router
-    let app = create_router(state.clone(), allowed_origins);
+    let app = create_router(state.clone(), CorsConfig::new(allowed_origins));
 
     // 4. Test OPTIONS request with Allowed Origin
     let req = Request::builder()
@@ -66,7 +49,7 @@ async fn test_cors_configuration() {
     );
 
     // 5. Test OPTIONS request with Disallowed Origin
-    let app = create_router(state, vec![allowed_origin.clone()]); // recreate to be safe/clean
+    let app = create_router(state, CorsConfig::new(vec![allowed_origin.clone()])); // recreate to be safe/clean
     let req = Request::builder()
         .method(Method::OPTIONS)
         .uri("/some/path")
@@ -86,3 +69,134 @@ async fn test_cors_configuration() {
         None
     );
 }
+
+#[tokio::test]
+async fn test_configured_methods_and_headers_are_advertised() {
+    let state = common::test_state(Router::new());
+    let mut cors = CorsConfig::new(vec!["http://localhost:3000".parse().unwrap()]);
+    cors.allowed_methods.push(Method::PATCH);
+    cors.allowed_headers
+        .push(header::HeaderName::from_static("x-tenant-id"));
+
+    let req = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/some/path")
+        .header(header::ORIGIN, "http://localhost:3000")
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PATCH")
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "x-tenant-id")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = create_router(state, cors).oneshot(req).await.unwrap();
+
+    let allowed = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    assert!(allowed(header::ACCESS_CONTROL_ALLOW_METHODS).contains("PATCH"));
+    assert!(allowed(header::ACCESS_CONTROL_ALLOW_HEADERS).contains("x-tenant-id"));
+}
+
+async fn preflight_allow_origin(cors: CorsConfig, origin: &str) -> Option<header::HeaderValue> {
+    let req = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/some/path")
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+        .body(Body::empty())
+        .unwrap();
+    let app = create_router(common::test_state(Router::new()), cors);
+    let response = app.oneshot(req).await.unwrap();
+    response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .cloned()
+}
+
+#[tokio::test]
+async fn test_pattern_origins() {
+    let mut cors = CorsConfig::new(vec!["http://localhost:3000".parse().unwrap()]);
+    cors.allowed_origin_patterns = vec!["https://*.preview.example.com".into()];
+
+    for allowed in [
+        "https://pr-1234.preview.example.com",
+        "http://localhost:3000",
+    ] {
+        assert_eq!(
+            preflight_allow_origin(cors.clone(), allowed).await,
+            Some(allowed.parse().unwrap()),
+            "{allowed}"
+        );
+    }
+    for denied in [
+        "https://preview.example.com",
+        "https://a.b.preview.example.com",
+        "https://pr-1.preview.example.com.evil.com",
+        "http://pr-1.preview.example.com",
+    ] {
+        assert_eq!(
+            preflight_allow_origin(cors.clone(), denied).await,
+            None,
+            "{denied}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_patterns_ignored_when_unset() {
+    let cors = CorsConfig::new(vec!["http://localhost:3000".parse().unwrap()]);
+    assert_eq!(
+        preflight_allow_origin(cors, "https://pr-1.preview.example.com").await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_preflight_to_protected_path_needs_no_token() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = {
+        let hits = hits.clone();
+        axum::Router::new().fallback(move || async move {
+            hits.fetch_add(1, Ordering::SeqCst);
+            "upstream ok"
+        })
+    };
+    let path = common::write_temp_file(
+        "preflight_rules.json",
+        r#"[{ "path": "/reports", "method": "*", "feature": "reports" }]"#,
+    );
+    let mut state = common::test_state(Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+
+    // Auth without a CORS layer in front, so the middleware sees the preflight
+    let app = axum::Router::new()
+        .route("/*path", any(proxy_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth_middleware,
+        ))
+        .with_state(state);
+    let options = |preflight: bool| {
+        let mut req = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/reports")
+            .header(header::ORIGIN, "http://localhost:3000");
+        if preflight {
+            req = req.header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET");
+        }
+        req.body(Body::empty()).unwrap()
+    };
+
+    let response = app.clone().oneshot(options(true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A plain OPTIONS is a real request to the resource
+    let response = app.oneshot(options(false)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use auth_gateway::admin::ADMIN_SECRET_HEADER;
+use auth_gateway::auth::{cache_decision, create_router, AppState, AuthzCacheKey, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    routing::post,
+    Json,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+/// Mock `/check` that allows only `feature:reports` and reports how it
+/// resolved the decision
+async fn state_with_openfga() -> AppState {
+    let app = axum::Router::new().route(
+        "/stores/:store_id/check",
+        post(|Json(body): Json<Value>| async move {
+            let allowed = body["tuple_key"]["object"] == "feature:reports";
+            Json(json!({ "allowed": allowed, "resolution": "viewer from role:admin#member" }))
+        }),
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = common::spawn_server(app).await;
+    state
+}
+
+async fn debug_authz(state: &AppState, secret: &str, body: Value) -> (StatusCode, Value) {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/debug/authz")
+        .header(ADMIN_SECRET_HEADER, secret)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[tokio::test]
+async fn test_debug_authz_explains_decision() {
+    let state = state_with_openfga().await;
+    // A stale cached deny must not affect the answer
+    cache_decision(
+        &state,
+        AuthzCacheKey::new("user-1", "reports", None),
+        false,
+        None,
+    )
+    .await;
+
+    let body = json!({ "user": "user-1", "feature": "reports" });
+    let (status, explained) = debug_authz(&state, common::ADMIN_SECRET, body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(explained["allowed"], true);
+    let tuple = json!({ "user": "user:user-1", "relation": "viewer", "object": "feature:reports" });
+    assert_eq!(explained["tuple"], tuple);
+    assert_eq!(explained["openfga_request"]["tuple_key"], tuple);
+    assert_eq!(explained["openfga_status"], 200);
+    assert_eq!(
+        explained["openfga_response"]["resolution"],
+        "viewer from role:admin#member"
+    );
+}
+
+#[tokio::test]
+async fn test_debug_authz_uses_action_relation() {
+    let state = state_with_openfga().await;
+
+    let body = json!({ "user": "user-1", "feature": "billing", "action": "edit" });
+    let (status, explained) = debug_authz(&state, common::ADMIN_SECRET, body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(explained["allowed"], false);
+    assert_eq!(explained["tuple"]["relation"], "edit");
+}
+
+#[tokio::test]
+async fn test_debug_authz_requires_admin_secret() {
+    let state = state_with_openfga().await;
+
+    let body = json!({ "user": "user-1", "feature": "reports" });
+    let (status, _) = debug_authz(&state, "wrong-secret", body).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
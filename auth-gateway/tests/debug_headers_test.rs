@@ -0,0 +1,78 @@
+mod common;
+
+use auth_gateway::auth::{
+    create_router, load_access_rules, AppState, CorsConfig, DEBUG_ACTION_HEADER,
+    DEBUG_DECISION_HEADER, DEBUG_FEATURE_HEADER,
+};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, Response, StatusCode},
+};
+use tower::ServiceExt;
+
+const RULES: &str =
+    r#"[{ "path": "/reports", "method": "POST", "feature": "reports", "action": "edit" }]"#;
+
+async fn state_with_openfga(allowed: bool, debug_headers: bool) -> AppState {
+    let path = common::write_temp_file("debug_header_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    state.fga_client.url = common::spawn_openfga(allowed).await;
+    state.debug_headers = debug_headers;
+    common::install_test_key(&state).await;
+    state
+}
+
+async fn post_reports(state: &AppState) -> Response<Body> {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/reports")
+        .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+        .body(Body::empty())
+        .unwrap();
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_debug_headers_describe_matched_rule() {
+    let state = state_with_openfga(true, true).await;
+
+    let response = post_reports(&state).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers[DEBUG_FEATURE_HEADER], "reports");
+    assert_eq!(headers[DEBUG_ACTION_HEADER], "edit");
+    assert_eq!(headers[DEBUG_DECISION_HEADER], "allowed");
+}
+
+#[tokio::test]
+async fn test_debug_headers_on_denied_request() {
+    let state = state_with_openfga(false, true).await;
+
+    let response = post_reports(&state).await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.headers()[DEBUG_FEATURE_HEADER], "reports");
+    assert_eq!(response.headers()[DEBUG_DECISION_HEADER], "forbidden");
+}
+
+#[tokio::test]
+async fn test_debug_headers_absent_when_disabled() {
+    let state = state_with_openfga(true, false).await;
+
+    let response = post_reports(&state).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    for name in [
+        DEBUG_FEATURE_HEADER,
+        DEBUG_ACTION_HEADER,
+        DEBUG_DECISION_HEADER,
+    ] {
+        assert!(response.headers().get(name).is_none(), "{} was sent", name);
+    }
+}
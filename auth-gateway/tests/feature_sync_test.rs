@@ -0,0 +1,338 @@
+mod common;
+
+use auth_gateway::feature_sync::{migrate_features, MigrationSummary};
+use auth_gateway::openfga::{OpenFgaClient, OPENFGA_MAX_TUPLES_PER_WRITE};
+use axum::{http::StatusCode, routing::post, Json};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+const PREV_RULES: &str = r#"[
+    { "path": "/reports", "method": "GET", "feature": "reports" },
+    { "path": "/legacy", "method": "GET", "feature": "legacy" }
+]"#;
+
+const LATEST_RULES: &str = r#"[
+    { "path": "/reports", "method": "GET", "feature": "analytics" },
+    { "path": "/billing", "method": "GET", "feature": "billing" }
+]"#;
+
+/// Mock OpenFGA whose `/read` returns one viewer tuple per queried object,
+/// in the prefixed `feature:{name}` form OpenFGA uses, and which records
+/// every `/write` body
+async fn spawn_sync_openfga() -> (String, Arc<Mutex<Vec<Value>>>) {
+    spawn_sync_openfga_rejecting(None).await
+}
+
+/// Like `spawn_sync_openfga`, but rejecting any write that deletes a
+/// tuple on `reject_object`
+async fn spawn_sync_openfga_rejecting(
+    reject_object: Option<&'static str>,
+) -> (String, Arc<Mutex<Vec<Value>>>) {
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = writes.clone();
+    let app = axum::Router::new()
+        .route(
+            "/stores/:store_id/read",
+            post(|Json(body): Json<Value>| async move {
+                let object = body["tuple_key"]["object"].clone();
+                Json(json!({
+                    "tuples": [{ "key": { "user": "user:user-1", "relation": "viewer", "object": object } }]
+                }))
+            }),
+        )
+        .route(
+            "/stores/:store_id/write",
+            post(move |Json(body): Json<Value>| async move {
+                let rejected = body["deletes"]["tuple_keys"]
+                    .as_array()
+                    .is_some_and(|keys| keys.iter().any(|k| k["object"].as_str() == reject_object));
+                recorded.lock().unwrap().push(body);
+                if rejected {
+                    (StatusCode::BAD_REQUEST, r#"{"code":"validation_error"}"#)
+                } else {
+                    (StatusCode::OK, "{}")
+                }
+            }),
+        );
+    (common::spawn_server(app).await, writes)
+}
+
+#[tokio::test]
+async fn test_dry_run_reports_changes_without_writing() {
+    let (url, writes) = spawn_sync_openfga().await;
+    let latest = common::write_temp_file("latest_rules.json", LATEST_RULES);
+    let prev = common::write_temp_file("prev_rules.json", PREV_RULES);
+
+    let summary = migrate_features(
+        &reqwest::Client::new(),
+        &OpenFgaClient::new(url, "test-store".into()),
+        &latest,
+        &prev,
+        true,
+    )
+    .await
+    .unwrap();
+
+    assert!(summary.dry_run);
+    assert_eq!(
+        summary.renamed,
+        vec![("reports".to_string(), "analytics".to_string())]
+    );
+    assert_eq!(summary.deleted, vec!["legacy"]);
+    assert_eq!(summary.added, vec!["analytics", "billing"]);
+    assert_eq!(summary.tuples_migrated, 1);
+    assert_eq!(summary.tuples_deleted, 1);
+    assert!(writes.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_renamed_and_deleted_tuples_are_written() {
+    let (url, writes) = spawn_sync_openfga().await;
+    let latest = common::write_temp_file("latest_rules.json", LATEST_RULES);
+    let prev = common::write_temp_file("prev_rules.json", PREV_RULES);
+
+    let summary = migrate_features(
+        &reqwest::Client::new(),
+        &OpenFgaClient::new(url, "test-store".into()),
+        &latest,
+        &prev,
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!((summary.tuples_migrated, summary.tuples_deleted), (1, 1));
+
+    let writes = writes.lock().unwrap();
+    assert_eq!(writes.len(), 2);
+
+    let migration = &writes[0];
+    assert_eq!(
+        migration["deletes"]["tuple_keys"][0]["object"],
+        "feature:reports"
+    );
+    assert_eq!(
+        migration["writes"]["tuple_keys"][0],
+        json!({ "user": "user:user-1", "relation": "viewer", "object": "feature:analytics" })
+    );
+
+    let cleanup = &writes[1];
+    assert_eq!(
+        cleanup["deletes"]["tuple_keys"],
+        json!([{ "user": "user:user-1", "relation": "viewer", "object": "feature:legacy" }])
+    );
+    assert!(cleanup.get("writes").is_none());
+}
+
+#[tokio::test]
+async fn test_failed_cleanup_rolls_back_renames() {
+    let (url, writes) = spawn_sync_openfga_rejecting(Some("feature:legacy")).await;
+    let latest = common::write_temp_file("latest_rules.json", LATEST_RULES);
+    let prev = common::write_temp_file("prev_rules.json", PREV_RULES);
+
+    let result = migrate_features(
+        &reqwest::Client::new(),
+        &OpenFgaClient::new(url, "test-store".into()),
+        &latest,
+        &prev,
+        false,
+    )
+    .await;
+    assert!(result.is_err());
+
+    let writes = writes.lock().unwrap();
+    assert_eq!(writes.len(), 3);
+
+    // The rename is reversed: analytics removed, reports restored
+    let rollback = &writes[2];
+    assert_eq!(
+        rollback["writes"]["tuple_keys"],
+        json!([{ "user": "user:user-1", "relation": "viewer", "object": "feature:reports" }])
+    );
+    assert_eq!(
+        rollback["deletes"]["tuple_keys"],
+        json!([{ "user": "user:user-1", "relation": "viewer", "object": "feature:analytics" }])
+    );
+}
+
+#[tokio::test]
+async fn test_missing_previous_rules_is_a_no_op() {
+    let (url, writes) = spawn_sync_openfga().await;
+    let latest = common::write_temp_file("latest_rules.json", LATEST_RULES);
+
+    let summary = migrate_features(
+        &reqwest::Client::new(),
+        &OpenFgaClient::new(url, "test-store".into()),
+        &latest,
+        "/nonexistent/access_rules_prev.json",
+        false,
+    )
+    .await
+    .unwrap();
+
+    assert!(summary.renamed.is_empty() && summary.deleted.is_empty());
+    assert!(writes.lock().unwrap().is_empty());
+}
+
+/// Dry-run a migration between two rule sets
+async fn plan(prev: &str, latest: &str) -> MigrationSummary {
+    let (url, _writes) = spawn_sync_openfga().await;
+    let latest = common::write_temp_file("latest_rules.json", latest);
+    let prev = common::write_temp_file("prev_rules.json", prev);
+    migrate_features(
+        &reqwest::Client::new(),
+        &OpenFgaClient::new(url, "test-store".into()),
+        &latest,
+        &prev,
+        true,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_endpoint_moving_to_new_feature_is_a_rename() {
+    let summary = plan(
+        r#"[{ "path": "/docs", "method": "GET", "feature": "docs" },
+            { "path": "/docs", "method": "POST", "feature": "docs" }]"#,
+        r#"[{ "path": "/docs", "method": "GET", "feature": "documents" },
+            { "path": "/docs", "method": "POST", "feature": "documents" }]"#,
+    )
+    .await;
+
+    assert_eq!(
+        summary.renamed,
+        vec![("docs".to_string(), "documents".to_string())]
+    );
+    assert!(summary.deleted.is_empty());
+}
+
+#[tokio::test]
+async fn test_reused_path_is_not_a_rename() {
+    let summary = plan(
+        r#"[{ "path": "/reports", "method": "GET", "feature": "reports" },
+            { "path": "/exports", "method": "GET", "feature": "exports" },
+            { "path": "/stats", "method": "GET", "feature": "stats" },
+            { "path": "/summary", "method": "GET", "feature": "stats" }]"#,
+        // /reports now belongs to an existing feature; /stats moves to a new
+        // feature while stats itself lives on at /summary
+        r#"[{ "path": "/reports", "method": "GET", "feature": "exports" },
+            { "path": "/exports", "method": "GET", "feature": "exports" },
+            { "path": "/stats", "method": "GET", "feature": "metrics" },
+            { "path": "/summary", "method": "GET", "feature": "stats" }]"#,
+    )
+    .await;
+
+    assert!(summary.renamed.is_empty());
+    assert_eq!(summary.deleted, vec!["reports"]);
+}
+
+#[tokio::test]
+async fn test_feature_split_is_ambiguous() {
+    let summary = plan(
+        r#"[{ "path": "/docs", "method": "GET", "feature": "docs" },
+            { "path": "/docs", "method": "POST", "feature": "docs" }]"#,
+        r#"[{ "path": "/docs", "method": "GET", "feature": "docs_read" },
+            { "path": "/docs", "method": "POST", "feature": "docs_write" }]"#,
+    )
+    .await;
+
+    assert!(summary.renamed.is_empty());
+    assert_eq!(summary.deleted, vec!["docs"]);
+}
+
+#[tokio::test]
+async fn test_tuples_are_read_across_pages() {
+    let app = axum::Router::new().route(
+        "/stores/:store_id/read",
+        post(|Json(body): Json<Value>| async move {
+            let object = body["tuple_key"]["object"].clone();
+            let tuple = |user: &str| {
+                json!({ "key": { "user": user, "relation": "viewer", "object": object } })
+            };
+            match body["continuation_token"].as_str() {
+                None => Json(json!({
+                    "tuples": [tuple("user:user-1"), tuple("user:user-2")],
+                    "continuation_token": "page-2"
+                })),
+                _ => Json(json!({ "tuples": [tuple("user:user-3")], "continuation_token": "" })),
+            }
+        }),
+    );
+    let url = common::spawn_server(app).await;
+    let latest = common::write_temp_file("latest_rules.json", LATEST_RULES);
+    let prev = common::write_temp_file("prev_rules.json", PREV_RULES);
+
+    let summary = migrate_features(
+        &reqwest::Client::new(),
+        &OpenFgaClient::new(url, "test-store".into()),
+        &latest,
+        &prev,
+        true,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(summary.tuples_migrated, 3);
+    assert_eq!(summary.tuples_deleted, 3);
+}
+
+#[tokio::test]
+async fn test_large_migration_is_chunked_per_write_limit() {
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = writes.clone();
+    let app = axum::Router::new()
+        .route(
+            "/stores/:store_id/read",
+            // 80 viewers per feature on each of two pages
+            post(|Json(body): Json<Value>| async move {
+                let object = body["tuple_key"]["object"].clone();
+                let (offset, token) = match body["continuation_token"].as_str() {
+                    None => (0, "page-2"),
+                    _ => (80, ""),
+                };
+                let tuples: Vec<Value> = (offset..offset + 80)
+                    .map(|i| {
+                        json!({ "key": { "user": format!("user:user-{}", i), "relation": "viewer", "object": object } })
+                    })
+                    .collect();
+                Json(json!({ "tuples": tuples, "continuation_token": token }))
+            }),
+        )
+        .route(
+            "/stores/:store_id/write",
+            post(move |Json(body): Json<Value>| async move {
+                recorded.lock().unwrap().push(body);
+                "{}"
+            }),
+        );
+    let url = common::spawn_server(app).await;
+    let latest = common::write_temp_file("latest_rules.json", LATEST_RULES);
+    let prev = common::write_temp_file("prev_rules.json", PREV_RULES);
+
+    let summary = migrate_features(
+        &reqwest::Client::new(),
+        &OpenFgaClient::new(url, "test-store".into()),
+        &latest,
+        &prev,
+        false,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(summary.tuples_migrated, 160);
+    assert_eq!(summary.tuples_deleted, 160);
+    let count = |body: &Value, half: &str| {
+        body[half]["tuple_keys"]
+            .as_array()
+            .map_or(0, |keys| keys.len())
+    };
+    let sizes: Vec<usize> = writes
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|body| count(body, "writes") + count(body, "deletes"))
+        .collect();
+    // 160 renames at 50 per write, then 160 deletes at 100 per write
+    assert_eq!(sizes, [100, 100, 100, 20, 100, 60]);
+    assert!(sizes.iter().all(|&n| n <= OPENFGA_MAX_TUPLES_PER_WRITE));
+}
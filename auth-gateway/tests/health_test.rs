@@ -0,0 +1,85 @@
+mod common;
+
+use auth_gateway::auth::{create_router, CorsConfig};
+use auth_gateway::health::startup_self_check;
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use tower::ServiceExt;
+
+async fn get(app: axum::Router, uri: &str) -> (StatusCode, serde_json::Value) {
+    let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn test_healthz_is_always_ok() {
+    let state = common::test_state(matchit::Router::new());
+    let (status, _) = get(create_router(state, CorsConfig::default()), "/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_readyz_reports_unreachable_redis() {
+    // OpenFGA and JWKS are healthy; only Redis is down
+    let healthy = common::spawn_upstream().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.redis_client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+    state.fga_client.url = healthy.clone();
+    let jwks_url = format!("{}/oauth/v2/keys", healthy);
+    state.jwt_issuers = common::issuers(&[(common::TEST_ISSUER, &jwks_url)]);
+
+    let (status, body) = get(create_router(state, CorsConfig::default()), "/readyz").await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "unavailable");
+    assert_ne!(body["checks"]["redis"], "ok");
+    assert_eq!(body["checks"]["openfga"], "ok");
+    assert_eq!(body["checks"]["jwks"], "ok");
+}
+
+#[tokio::test]
+async fn test_startup_self_check_reports_unreachable_openfga() {
+    // JWKS serves the fixture keys; OpenFGA and Redis are down
+    let jwks = axum::Router::new().route(
+        "/keys",
+        axum::routing::get(|| async { include_str!("fixtures/jwks.json") }),
+    );
+    let jwks_url = format!("{}/keys", common::spawn_server(jwks).await);
+    let mut state = common::test_state(matchit::Router::new());
+    state.redis_client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+    state.fga_client.url = "http://127.0.0.1:1".to_string();
+    state.jwt_issuers = common::issuers(&[(common::TEST_ISSUER, &jwks_url)]);
+
+    let report = startup_self_check(&state).await;
+
+    assert!(!report.passed());
+    assert!(report.checks["openfga"].is_err());
+    assert!(report.checks["redis"].is_err());
+    assert_eq!(report.checks["jwks"], Ok(()));
+}
+
+#[tokio::test]
+async fn test_startup_self_check_requires_existing_store() {
+    let openfga = axum::Router::new().route(
+        "/stores/dummy-store-id",
+        axum::routing::get(|| async { "{}" }),
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = common::spawn_server(openfga).await;
+
+    assert_eq!(startup_self_check(&state).await.checks["openfga"], Ok(()));
+
+    state.fga_client.store_id = "missing-store".to_string();
+    let report = startup_self_check(&state).await;
+    assert_eq!(
+        report.checks["openfga"],
+        Err("status 404 Not Found".to_string())
+    );
+}
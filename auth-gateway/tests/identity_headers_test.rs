@@ -0,0 +1,139 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Request, StatusCode},
+    Json,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+const RULES: &str = r#"[
+    { "path": "/reports", "method": "GET", "feature": "reports" },
+    { "path": "/public/*path", "method": "*", "feature": "public_access" }
+]"#;
+
+/// Upstream that echoes the identity headers it received as JSON
+async fn spawn_identity_upstream() -> String {
+    let app = axum::Router::new().fallback(|headers: HeaderMap| async move {
+        let get = |name: &str| headers.get(name).and_then(|h| h.to_str().ok());
+        Json(json!({
+            "user": get("x-user-id"),
+            "scopes": get("x-user-scopes"),
+            "roles": get("x-user-roles"),
+            "org": get("x-user-org"),
+        }))
+    });
+    common::spawn_server(app).await
+}
+
+/// Send `uri` with `headers` and return what upstream saw
+async fn forwarded(uri: &str, headers: &[(&str, &str)]) -> Value {
+    let path = common::write_temp_file("identity_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_identity_upstream().await;
+    state.fga_client.url = common::spawn_openfga(true).await;
+    common::install_test_key(&state).await;
+
+    let mut req = Request::builder().uri(uri);
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn token_with_claims() -> String {
+    format!(
+        "Bearer {}",
+        common::sign_rs256(json!({
+            "sub": "user-1",
+            "exp": common::now() + 300,
+            "scope": "openid reports:read",
+            "roles": ["viewer", "editor"],
+            "org_id": "acme",
+        }))
+    )
+}
+
+#[tokio::test]
+async fn test_claims_are_forwarded_as_headers() {
+    let token = token_with_claims();
+    let seen = forwarded("/reports", &[(header::AUTHORIZATION.as_str(), &token)]).await;
+
+    assert_eq!(seen["user"], "user-1");
+    assert_eq!(seen["scopes"], "openid reports:read");
+    assert_eq!(seen["roles"], "viewer,editor");
+    assert_eq!(seen["org"], "acme");
+}
+
+#[tokio::test]
+async fn test_client_identity_headers_are_overwritten() {
+    let token = token_with_claims();
+    let seen = forwarded(
+        "/reports",
+        &[
+            (header::AUTHORIZATION.as_str(), &token),
+            ("x-user-id", "admin"),
+            ("x-user-scopes", "admin:all"),
+        ],
+    )
+    .await;
+
+    assert_eq!(seen["user"], "user-1");
+    assert_eq!(seen["scopes"], "openid reports:read");
+}
+
+#[tokio::test]
+async fn test_client_identity_headers_are_stripped_when_claims_absent() {
+    let token = common::bearer_token("user-1");
+    let seen = forwarded(
+        "/public/home",
+        &[
+            (header::AUTHORIZATION.as_str(), &token),
+            ("x-user-scopes", "admin:all"),
+            ("x-user-roles", "admin"),
+            ("x-user-org", "other-org"),
+        ],
+    )
+    .await;
+
+    assert_eq!(seen["user"], "user-1");
+    assert_eq!(seen["scopes"], Value::Null);
+    assert_eq!(seen["roles"], Value::Null);
+    assert_eq!(seen["org"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_illegal_sub_is_unauthorized() {
+    let path = common::write_temp_file("identity_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_identity_upstream().await;
+    state.fga_client.url = common::spawn_openfga(true).await;
+    common::install_test_key(&state).await;
+
+    let token =
+        common::sign_rs256(json!({ "sub": "user-1\nX-Evil: 1", "exp": common::now() + 300 }));
+    let req = Request::builder()
+        .uri("/reports")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use auth_gateway::auth::{validate_jwt, AppState};
+use auth_gateway::introspection::IntrospectionConfig;
+use axum::{http::HeaderMap, routing::post, Form, Json};
+use common::now;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Mock introspection endpoint: `opaque-active` is active for user-1,
+/// `opaque-foreign` is active but from another issuer, anything else is
+/// inactive. Returns the call counter.
+async fn state_with_introspection() -> (AppState, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let app = axum::Router::new().route(
+        "/oauth/v2/introspect",
+        post(
+            move |headers: HeaderMap, Form(form): Form<HashMap<String, String>>| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    // Client credentials arrive as HTTP basic auth
+                    assert!(headers["authorization"]
+                        .to_str()
+                        .unwrap()
+                        .starts_with("Basic "));
+                    if form["token"] == "opaque-active" {
+                        Json(json!({
+                            "active": true,
+                            "sub": "user-1",
+                            "iss": common::TEST_ISSUER,
+                            "exp": now() + 300,
+                            "scope": "openid profile"
+                        }))
+                    } else if form["token"] == "opaque-foreign" {
+                        Json(json!({
+                            "active": true,
+                            "sub": "user-1",
+                            "iss": "https://other-issuer.test",
+                            "exp": now() + 300
+                        }))
+                    } else {
+                        Json(json!({ "active": false }))
+                    }
+                }
+            },
+        ),
+    );
+    let url = common::spawn_server(app).await;
+
+    let mut state = common::test_state(matchit::Router::new());
+    state.introspection = Some(IntrospectionConfig::new(
+        format!("{}/oauth/v2/introspect", url),
+        "gateway".into(),
+        "secret".into(),
+        4,
+    ));
+    common::install_test_key(&state).await;
+    (state, calls)
+}
+
+#[tokio::test]
+async fn test_jwt_is_validated_locally() {
+    let (state, calls) = state_with_introspection().await;
+
+    let token = common::sign_rs256(json!({ "sub": "user-1", "exp": now() + 300 }));
+    assert_eq!(validate_jwt(&state, &token).await.unwrap().sub, "user-1");
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_opaque_token_is_introspected_and_cached() {
+    let (state, calls) = state_with_introspection().await;
+
+    for _ in 0..2 {
+        let claims = validate_jwt(&state, "opaque-active").await.unwrap();
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.scope.as_deref(), Some("openid profile"));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_inactive_token_is_rejected_and_remembered() {
+    let (state, calls) = state_with_introspection().await;
+
+    for _ in 0..3 {
+        assert!(validate_jwt(&state, "opaque-revoked").await.is_err());
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_introspected_issuer_is_enforced() {
+    let (state, _calls) = state_with_introspection().await;
+    assert!(validate_jwt(&state, "opaque-foreign").await.is_err());
+}
+
+#[tokio::test]
+async fn test_opaque_token_rejected_without_introspection() {
+    let state = common::test_state(matchit::Router::new());
+    assert!(validate_jwt(&state, "opaque-active").await.is_err());
+}
+
+#[tokio::test]
+async fn test_introspected_audience_is_enforced() {
+    let (mut state, _calls) = state_with_introspection().await;
+    state.jwt_audience = Some(vec!["gateway".into()]);
+
+    // The mock returns no `aud`, so a configured audience can't match
+    assert!(validate_jwt(&state, "opaque-active").await.is_err());
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use auth_gateway::auth::{
+    create_router, load_access_rules, AppState, CorsConfig, RateLimitFailMode,
+};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    http::StatusCode,
+};
+use std::net::{Ipv6Addr, SocketAddr};
+use tower::ServiceExt;
+
+const RULES: &str = r#"[
+    { "path": "/public/*path", "method": "GET", "feature": "public_access", "rate_limit": 2, "rate_window_secs": 10 }
+]"#;
+
+const PROXY: &str = "10.0.0.1";
+
+/// Request as if accepted from `peer`
+fn request_from(peer: &str, uri: &str) -> Request {
+    let mut req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let addr = SocketAddr::new(peer.parse().unwrap(), 40000);
+    req.extensions_mut().insert(ConnectInfo(addr));
+    req
+}
+
+/// Address no earlier run has used, so Redis counters start from zero
+fn fresh_ip() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    Ipv6Addr::from(nanos).to_string()
+}
+
+async fn public_state() -> AppState {
+    let path = common::write_temp_file("ip_rate_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    state
+}
+
+async fn send(state: &AppState, req: Request) -> StatusCode {
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_public_route_throttled_by_ip() {
+    let Some(redis_client) = common::test_redis() else {
+        eprintln!("TEST_REDIS_URL not set, skipping");
+        return;
+    };
+    let mut state = public_state().await;
+    state.redis_client = redis_client;
+    let (client, other) = (fresh_ip(), fresh_ip());
+
+    // The budget covers the client, not the path
+    assert_eq!(
+        send(&state, request_from(&client, "/public/a")).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&state, request_from(&client, "/public/b")).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&state, request_from(&client, "/public/a")).await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    assert_eq!(
+        send(&state, request_from(&other, "/public/a")).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_clients_behind_trusted_proxy_are_counted_separately() {
+    let Some(redis_client) = common::test_redis() else {
+        eprintln!("TEST_REDIS_URL not set, skipping");
+        return;
+    };
+    let mut state = public_state().await;
+    state.redis_client = redis_client;
+    state.trusted_proxies = vec![PROXY.parse().unwrap()];
+    let via_proxy = |client: &str| {
+        let mut req = request_from(PROXY, "/public/a");
+        req.headers_mut()
+            .insert("x-forwarded-for", client.parse().unwrap());
+        req
+    };
+    let (client, other) = (fresh_ip(), fresh_ip());
+
+    for _ in 0..2 {
+        assert_eq!(send(&state, via_proxy(&client)).await, StatusCode::OK);
+    }
+    assert_eq!(
+        send(&state, via_proxy(&client)).await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(send(&state, via_proxy(&other)).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_public_route_consults_limiter() {
+    let mut state = public_state().await;
+    state.redis_client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+    state.rate_limit_fail_mode = RateLimitFailMode::Closed;
+
+    assert_eq!(
+        send(&state, request_from("192.0.2.7", "/public/a")).await,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
@@ -0,0 +1,439 @@
+mod common;
+
+use auth_gateway::auth::{
+    build_token_cache, load_jwks_file, resolve_jwks_url, validate_jwt, JwtIssuer,
+};
+use common::{now, sign_rs256, RSA_PUBLIC};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const EC_PRIVATE: &[u8] = include_bytes!("fixtures/ec_private.pem");
+const JWKS: &str = include_str!("fixtures/jwks.json");
+const RSA_CERT: &str = include_str!("fixtures/rsa_cert.pem");
+
+async fn state_with_rsa_key() -> auth_gateway::auth::AppState {
+    let state = common::test_state(matchit::Router::new());
+    common::install_test_key(&state).await;
+    state
+}
+
+#[tokio::test]
+async fn test_audience_is_enforced_when_configured() {
+    let mut state = state_with_rsa_key().await;
+    state.jwt_audience = Some(vec!["gateway".into(), "other-api".into()]);
+
+    let good = sign_rs256(serde_json::json!({
+        "sub": "user-1", "exp": now() + 300, "aud": "gateway"
+    }));
+    let wrong = sign_rs256(serde_json::json!({
+        "sub": "user-1", "exp": now() + 300, "aud": "billing-api"
+    }));
+    let missing = sign_rs256(serde_json::json!({ "sub": "user-1", "exp": now() + 300 }));
+
+    assert_eq!(validate_jwt(&state, &good).await.unwrap().sub, "user-1");
+    assert!(validate_jwt(&state, &wrong).await.is_err());
+    assert!(validate_jwt(&state, &missing).await.is_err());
+}
+
+#[tokio::test]
+async fn test_audience_is_skipped_when_unset() {
+    let state = state_with_rsa_key().await;
+
+    let token = sign_rs256(serde_json::json!({
+        "sub": "user-1", "exp": now() + 300, "aud": ["billing-api"]
+    }));
+
+    assert!(validate_jwt(&state, &token).await.is_ok());
+}
+
+async fn state_with_mock_jwks(algorithms: Vec<Algorithm>) -> auth_gateway::auth::AppState {
+    let jwks = axum::Router::new().route(
+        "/keys",
+        axum::routing::get(|| async {
+            (
+                [(axum::http::header::CONTENT_TYPE, "application/json")],
+                JWKS,
+            )
+        }),
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    let jwks_url = format!("{}/keys", common::spawn_server(jwks).await);
+    state.jwt_issuers = common::issuers(&[(common::TEST_ISSUER, &jwks_url)]);
+    state.jwt_algorithms = algorithms;
+    state
+}
+
+fn sign_es256(mut claims: serde_json::Value) -> String {
+    claims["iss"] = common::TEST_ISSUER.into();
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some("test-ec-key".into());
+    jsonwebtoken::encode(
+        &header,
+        &claims,
+        &EncodingKey::from_ec_pem(EC_PRIVATE).unwrap(),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_rs256_and_es256_keys_from_jwks() {
+    let state = state_with_mock_jwks(vec![Algorithm::RS256, Algorithm::ES256]).await;
+
+    let rsa = sign_rs256(serde_json::json!({ "sub": "rsa-user", "exp": now() + 300 }));
+    let ec = sign_es256(serde_json::json!({ "sub": "ec-user", "exp": now() + 300 }));
+
+    assert_eq!(validate_jwt(&state, &rsa).await.unwrap().sub, "rsa-user");
+    assert_eq!(validate_jwt(&state, &ec).await.unwrap().sub, "ec-user");
+}
+
+#[tokio::test]
+async fn test_algorithm_outside_allow_list_is_rejected() {
+    let state = state_with_mock_jwks(vec![Algorithm::RS256]).await;
+
+    let ec = sign_es256(serde_json::json!({ "sub": "ec-user", "exp": now() + 300 }));
+    assert!(validate_jwt(&state, &ec).await.is_err());
+
+    // An HMAC token claiming the RSA key's kid must never validate
+    let mut header = Header::new(Algorithm::HS256);
+    header.kid = Some("test-key".into());
+    let hmac = jsonwebtoken::encode(
+        &header,
+        &serde_json::json!({ "sub": "attacker", "iss": common::TEST_ISSUER, "exp": now() + 300 }),
+        &EncodingKey::from_secret(RSA_PUBLIC),
+    )
+    .unwrap();
+    assert!(validate_jwt(&state, &hmac).await.is_err());
+}
+
+#[tokio::test]
+async fn test_expiry_leeway() {
+    let state = state_with_rsa_key().await;
+    assert_eq!(state.jwt_leeway_secs, 60);
+
+    let recently_expired = sign_rs256(serde_json::json!({ "sub": "user-1", "exp": now() - 30 }));
+    let long_expired = sign_rs256(serde_json::json!({ "sub": "user-1", "exp": now() - 120 }));
+
+    assert!(validate_jwt(&state, &recently_expired).await.is_ok());
+    assert!(validate_jwt(&state, &long_expired).await.is_err());
+}
+
+#[tokio::test]
+async fn test_not_before_is_honored() {
+    let state = state_with_rsa_key().await;
+
+    let skewed = sign_rs256(serde_json::json!({
+        "sub": "user-1", "exp": now() + 300, "nbf": now() + 30
+    }));
+    let future = sign_rs256(serde_json::json!({
+        "sub": "user-1", "exp": now() + 600, "nbf": now() + 300
+    }));
+
+    assert!(validate_jwt(&state, &skewed).await.is_ok());
+    assert!(validate_jwt(&state, &future).await.is_err());
+}
+
+/// Mock JWKS endpoint whose body (or failure) can be swapped mid-test;
+/// returns the counter of fetches it served
+async fn spawn_rotating_jwks(
+    state: &mut auth_gateway::auth::AppState,
+) -> (Arc<Mutex<Option<String>>>, Arc<AtomicUsize>) {
+    let body = Arc::new(Mutex::new(Some(JWKS.to_string())));
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let (served, counter) = (body.clone(), fetches.clone());
+    let jwks = axum::Router::new().route(
+        "/keys",
+        axum::routing::get(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let body = served.lock().unwrap().clone();
+            async move {
+                match body {
+                    Some(body) => (axum::http::StatusCode::OK, body),
+                    None => (axum::http::StatusCode::SERVICE_UNAVAILABLE, String::new()),
+                }
+            }
+        }),
+    );
+    let jwks_url = format!("{}/keys", common::spawn_server(jwks).await);
+    state.jwt_issuers = common::issuers(&[(common::TEST_ISSUER, &jwks_url)]);
+    (body, fetches)
+}
+
+fn sign_rs256_with_kid(kid: &str, sub: &str) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(kid.into());
+    jsonwebtoken::encode(
+        &header,
+        &serde_json::json!({ "sub": sub, "iss": common::TEST_ISSUER, "exp": now() + 300 }),
+        &EncodingKey::from_rsa_pem(common::RSA_PRIVATE).unwrap(),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_rotated_key_is_picked_up_by_forced_refresh() {
+    let mut state = common::test_state(matchit::Router::new());
+    let (body, fetches) = spawn_rotating_jwks(&mut state).await;
+
+    let old = sign_rs256_with_kid("test-key", "user-1");
+    assert!(validate_jwt(&state, &old).await.is_ok());
+
+    // IdP rotates: same key material published under a new kid
+    *body.lock().unwrap() = Some(JWKS.replace("\"test-key\"", "\"rotated-key\""));
+    let rotated = sign_rs256_with_kid("rotated-key", "user-1");
+
+    assert_eq!(validate_jwt(&state, &rotated).await.unwrap().sub, "user-1");
+    assert_eq!(fetches.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_concurrent_misses_share_one_fetch() {
+    let mut state = common::test_state(matchit::Router::new());
+    let (_body, fetches) = spawn_rotating_jwks(&mut state).await;
+    let token = sign_rs256_with_kid("test-key", "user-1");
+
+    let results =
+        futures_util::future::join_all((0..20).map(|_| validate_jwt(&state, &token))).await;
+
+    assert!(results.iter().all(|r| r.is_ok()));
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_jwks_fetch_retried_after_transient_failures() {
+    // Fails twice, then serves the JWKS
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let counter = fetches.clone();
+    let jwks = axum::Router::new().route(
+        "/keys",
+        axum::routing::get(move || {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt < 2 {
+                    (axum::http::StatusCode::SERVICE_UNAVAILABLE, String::new())
+                } else {
+                    (axum::http::StatusCode::OK, JWKS.to_string())
+                }
+            }
+        }),
+    );
+    let jwks_url = format!("{}/keys", common::spawn_server(jwks).await);
+    let mut state = common::test_state(matchit::Router::new());
+    state.jwt_issuers = common::issuers(&[(common::TEST_ISSUER, &jwks_url)]);
+
+    let token = sign_rs256_with_kid("test-key", "user-1");
+    assert_eq!(validate_jwt(&state, &token).await.unwrap().sub, "user-1");
+    assert_eq!(fetches.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_last_good_keys_survive_failed_refresh() {
+    let mut state = common::test_state(matchit::Router::new());
+    let (body, _fetches) = spawn_rotating_jwks(&mut state).await;
+    let token = sign_rs256_with_kid("test-key", "user-1");
+    assert!(validate_jwt(&state, &token).await.is_ok());
+
+    // Cached key expires while the JWKS endpoint is down
+    state.jwks_cache.invalidate_all();
+    *body.lock().unwrap() = None;
+
+    assert!(validate_jwt(&state, &token).await.is_ok());
+    assert!(
+        validate_jwt(&state, &sign_rs256_with_kid("unknown", "user-1"))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_cached_token_skips_verification() {
+    let mut state = common::test_state(matchit::Router::new());
+    state.token_cache = Some(build_token_cache(Duration::from_secs(60)));
+    let (_body, fetches) = spawn_rotating_jwks(&mut state).await;
+    let token = sign_rs256_with_kid("test-key", "user-1");
+    assert!(validate_jwt(&state, &token).await.is_ok());
+
+    // Verifying again would need the key, and so another JWKS fetch
+    state.jwks_cache.invalidate_all();
+
+    assert_eq!(validate_jwt(&state, &token).await.unwrap().sub, "user-1");
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_cached_token_not_served_past_exp() {
+    let mut state = state_with_rsa_key().await;
+    state.token_cache = Some(build_token_cache(Duration::from_secs(60)));
+    state.jwt_leeway_secs = 0;
+    let token = sign_rs256(serde_json::json!({ "sub": "user-1", "exp": now() + 1 }));
+    assert!(validate_jwt(&state, &token).await.is_ok());
+
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+
+    assert!(validate_jwt(&state, &token).await.is_err());
+}
+
+/// Two tenants, each publishing different key material under the same kid
+#[tokio::test]
+async fn test_each_issuer_validates_only_its_own_tokens() {
+    let rsa_jwks =
+        axum::Router::new().route("/keys", axum::routing::get(|| async { JWKS.to_string() }));
+    // Tenant B publishes only its EC key, under the kid tenant A uses for RSA
+    let mut ec_only: serde_json::Value = serde_json::from_str(JWKS).unwrap();
+    ec_only["keys"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|key| key["kty"] == "EC");
+    ec_only["keys"][0]["kid"] = "test-key".into();
+    let ec_only = ec_only.to_string();
+    let ec_jwks =
+        axum::Router::new().route("/keys", axum::routing::get(move || async move { ec_only }));
+    let url_a = format!("{}/keys", common::spawn_server(rsa_jwks).await);
+    let url_b = format!("{}/keys", common::spawn_server(ec_jwks).await);
+
+    let mut state = common::test_state(matchit::Router::new());
+    state.jwt_algorithms = vec![Algorithm::RS256, Algorithm::ES256];
+    state.jwt_issuers = common::issuers(&[
+        ("https://tenant-a.test", &url_a),
+        ("https://tenant-b.test", &url_b),
+    ]);
+
+    let sign = |alg: Algorithm, kid: &str, iss: &str, key: EncodingKey| {
+        let mut header = Header::new(alg);
+        header.kid = Some(kid.into());
+        let claims = serde_json::json!({ "sub": iss, "iss": iss, "exp": now() + 300 });
+        jsonwebtoken::encode(&header, &claims, &key).unwrap()
+    };
+    let rsa_key = || EncodingKey::from_rsa_pem(common::RSA_PRIVATE).unwrap();
+    let ec_key = || EncodingKey::from_ec_pem(EC_PRIVATE).unwrap();
+
+    let token_a = sign(
+        Algorithm::RS256,
+        "test-key",
+        "https://tenant-a.test",
+        rsa_key(),
+    );
+    let token_b = sign(
+        Algorithm::ES256,
+        "test-key",
+        "https://tenant-b.test",
+        ec_key(),
+    );
+    assert_eq!(
+        validate_jwt(&state, &token_a).await.unwrap().iss,
+        "https://tenant-a.test"
+    );
+    assert_eq!(
+        validate_jwt(&state, &token_b).await.unwrap().iss,
+        "https://tenant-b.test"
+    );
+
+    // Tenant A's token relabelled as tenant B is checked against B's keys
+    let forged = sign(
+        Algorithm::RS256,
+        "test-key",
+        "https://tenant-b.test",
+        rsa_key(),
+    );
+    assert!(validate_jwt(&state, &forged).await.is_err());
+
+    let unknown = sign(Algorithm::RS256, "test-key", "https://evil.test", rsa_key());
+    assert!(validate_jwt(&state, &unknown).await.is_err());
+}
+
+#[tokio::test]
+async fn test_sub_must_be_a_valid_header_value() {
+    let state = state_with_rsa_key().await;
+
+    for sub in ["user-1\r\nX-Admin: true", "", "user\u{7f}"] {
+        let token = sign_rs256(serde_json::json!({ "sub": sub, "exp": now() + 300 }));
+        assert!(validate_jwt(&state, &token).await.is_err(), "{:?}", sub);
+    }
+    let missing = sign_rs256(serde_json::json!({ "exp": now() + 300 }));
+    assert!(validate_jwt(&state, &missing).await.is_err());
+}
+
+#[tokio::test]
+async fn test_key_published_only_as_x5c_certificate() {
+    // The fixture key's self-signed certificate, without n/e
+    let der: String = RSA_CERT
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    let jwks = serde_json::json!({
+        "keys": [{ "kty": "RSA", "kid": "cert-key", "alg": "RS256", "x5c": [der] }]
+    })
+    .to_string();
+    let app = axum::Router::new().route("/keys", axum::routing::get(move || async move { jwks }));
+    let url = format!("{}/keys", common::spawn_server(app).await);
+
+    let mut state = common::test_state(matchit::Router::new());
+    state.jwt_issuers = common::issuers(&[(common::TEST_ISSUER, &url)]);
+
+    let token = sign_rs256_with_kid("cert-key", "user-1");
+    assert_eq!(validate_jwt(&state, &token).await.unwrap().sub, "user-1");
+}
+
+#[tokio::test]
+async fn test_static_jwks_validates_without_fetching() {
+    let keys = load_jwks_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/jwks.json"
+    ))
+    .unwrap();
+    // Nothing listens here, so any fetch would fail
+    let issuer =
+        JwtIssuer::new("http://127.0.0.1:1/keys".into(), Duration::ZERO).with_static_keys(keys);
+    let mut state = common::test_state(matchit::Router::new());
+    state.jwt_issuers = Arc::new([(common::TEST_ISSUER.to_string(), issuer)].into());
+
+    let token = sign_rs256(serde_json::json!({ "sub": "user-1", "exp": now() + 300 }));
+    assert_eq!(validate_jwt(&state, &token).await.unwrap().sub, "user-1");
+
+    // Still served from the static set once the cached key is gone
+    state.jwks_cache.invalidate_all();
+    assert_eq!(validate_jwt(&state, &token).await.unwrap().sub, "user-1");
+
+    let unknown = sign_rs256_with_kid("unknown-key", "user-1");
+    assert!(validate_jwt(&state, &unknown).await.is_err());
+}
+
+#[test]
+fn test_static_jwks_file_must_exist() {
+    assert!(load_jwks_file("/nonexistent/jwks.json").is_err());
+}
+
+#[tokio::test]
+async fn test_jwks_url_discovered_from_openid_configuration() {
+    let discovery = axum::Router::new().route(
+        "/.well-known/openid-configuration",
+        axum::routing::get(|| async {
+            axum::Json(serde_json::json!({
+                "issuer": "https://idp.test",
+                "jwks_uri": "https://idp.test/protocol/openid-connect/certs"
+            }))
+        }),
+    );
+    let issuer = common::spawn_server(discovery).await;
+    let client = reqwest::Client::new();
+
+    assert_eq!(
+        resolve_jwks_url(&client, &issuer, None).await,
+        "https://idp.test/protocol/openid-connect/certs"
+    );
+    // An explicit URL wins without asking the issuer
+    assert_eq!(
+        resolve_jwks_url(&client, &issuer, Some("https://keys.test/jwks.json")).await,
+        "https://keys.test/jwks.json"
+    );
+}
+
+#[tokio::test]
+async fn test_jwks_url_falls_back_to_zitadel_path() {
+    let client = reqwest::Client::new();
+    assert_eq!(
+        resolve_jwks_url(&client, "http://127.0.0.1:1", None).await,
+        "http://127.0.0.1:1/oauth/v2/keys"
+    );
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use auth_gateway::admin::ADMIN_SECRET_HEADER;
+use auth_gateway::auth::{create_router, load_access_rules, AppState, CorsConfig};
+use auth_gateway::webhooks::WEBHOOK_SIGNATURE_HEADER;
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    response::Response,
+};
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use tower::ServiceExt;
+
+const RULES: &str = r#"[
+    { "path": "/reports", "method": "GET", "feature": "reports", "action": "view" },
+    { "path": "/public/*path", "method": "*", "feature": "public_access" }
+]"#;
+
+async fn state_in_maintenance() -> AppState {
+    let path = common::write_temp_file("maintenance_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    state.fga_client.url = common::spawn_openfga(true).await;
+    state.maintenance_mode.store(true, Ordering::Relaxed);
+    state.maintenance_retry_after_secs = 120;
+    common::install_test_key(&state).await;
+    state
+}
+
+async fn send(state: &AppState, req: Request<Body>) -> Response {
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::builder()
+        .uri(uri)
+        .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn set_maintenance(enabled: bool) -> Request<Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri("/admin/maintenance")
+        .header(ADMIN_SECRET_HEADER, common::ADMIN_SECRET)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "enabled": enabled }).to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_proxied_routes_return_503() {
+    let state = state_in_maintenance().await;
+
+    for uri in ["/reports", "/public/a", "/unknown"] {
+        let response = send(&state, get(uri)).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::SERVICE_UNAVAILABLE,
+            "{}",
+            uri
+        );
+        assert_eq!(response.headers()[header::RETRY_AFTER], "120");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], "maintenance");
+    }
+}
+
+#[tokio::test]
+async fn test_health_and_webhooks_stay_up() {
+    let state = state_in_maintenance().await;
+
+    let healthz = Request::builder()
+        .uri("/healthz")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&state, healthz).await.status(), StatusCode::OK);
+
+    // Reaches the handler, which rejects the empty payload
+    let body = "{}";
+    let webhook = Request::builder()
+        .method(Method::POST)
+        .uri("/webhooks/user-created")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            common::sign_webhook(body.as_bytes()),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    assert_eq!(
+        send(&state, webhook).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn test_admin_toggles_maintenance_at_runtime() {
+    let state = state_in_maintenance().await;
+
+    assert_eq!(
+        send(&state, set_maintenance(false)).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(send(&state, get("/reports")).await.status(), StatusCode::OK);
+
+    assert_eq!(
+        send(&state, set_maintenance(true)).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&state, get("/reports")).await.status(),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
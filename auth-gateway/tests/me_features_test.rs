@@ -0,0 +1,100 @@
+mod common;
+
+use auth_gateway::auth::{create_router, AppState, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::post,
+    Json,
+};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// Mock `/list-objects` answering per object type; returns the call counter
+async fn spawn_list_objects() -> (String, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let app = axum::Router::new().route(
+        "/stores/:store_id/list-objects",
+        post(move |Json(body): Json<Value>| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                assert_eq!(body["user"], "user:user-1");
+                assert_eq!(body["relation"], "viewer");
+                let objects = match body["type"].as_str().unwrap() {
+                    "feature" => json!(["feature:reports", "feature:billing"]),
+                    _ => json!(["document:42"]),
+                };
+                Json(json!({ "objects": objects }))
+            }
+        }),
+    );
+    (common::spawn_server(app).await, calls)
+}
+
+async fn state() -> (AppState, Arc<AtomicUsize>) {
+    let (url, calls) = spawn_list_objects().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = url;
+    common::install_test_key(&state).await;
+    (state, calls)
+}
+
+async fn get_me_features(state: AppState, token: Option<String>) -> (StatusCode, Value) {
+    let mut req = Request::builder().uri("/me/features");
+    if let Some(token) = token {
+        req = req.header(header::AUTHORIZATION, token);
+    }
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_lists_accessible_features() {
+    let (mut state, _calls) = state().await;
+    state
+        .listable_objects
+        .push(("document".into(), "viewer".into()));
+
+    let (status, body) = get_me_features(state, Some(common::bearer_token("user-1"))).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["user_id"], "user-1");
+    assert_eq!(body["features"], json!(["billing", "reports"]));
+    assert_eq!(
+        body["objects"],
+        json!(["document:42", "feature:billing", "feature:reports"])
+    );
+}
+
+#[tokio::test]
+async fn test_listing_is_cached() {
+    let (state, calls) = state().await;
+
+    for _ in 0..2 {
+        let (status, _) =
+            get_me_features(state.clone(), Some(common::bearer_token("user-1"))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_requires_token() {
+    let (state, calls) = state().await;
+
+    let (status, _) = get_me_features(state, None).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, CorsConfig};
+use auth_gateway::telemetry;
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn test_metrics_endpoint_exposes_gateway_metrics() {
+    telemetry::install_recorder();
+
+    let path = common::write_temp_file(
+        "metrics_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    let app = create_router(state, CorsConfig::default());
+
+    // One proxied public request and one unmatched path
+    for uri in ["/public/index.html", "/unknown"] {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(req).await.unwrap();
+    }
+
+    let req = Request::builder()
+        .uri("/metrics")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(body.contains(r#"auth_requests_total{result="public"}"#));
+    assert!(body.contains(r#"auth_requests_total{result="no_rule"}"#));
+    assert!(body.contains("authz_cache_hits_total"));
+    assert!(body.contains("authz_cache_misses_total"));
+    assert!(body.contains("rate_limit_rejections_total"));
+    assert!(body.contains(r#"proxy_request_duration_seconds_count{status="200"}"#));
+    assert!(body.contains(r#"cache_entries{cache="authz"}"#));
+    assert!(body.contains(r#"cache_entries{cache="jwks"}"#));
+}
+
+#[tokio::test]
+async fn test_upstream_throttling_is_counted_and_passed_through() {
+    telemetry::install_recorder();
+
+    let upstream = axum::Router::new().fallback(|| async {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, "7")],
+            "slow down",
+        )
+    });
+    let path = common::write_temp_file(
+        "throttled_rules.json",
+        r#"[{ "path": "/busy/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+    let app = create_router(state, CorsConfig::default());
+
+    let req = Request::builder()
+        .uri("/busy/report")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "7");
+
+    let req = Request::builder()
+        .uri("/metrics")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains(r#"upstream_throttled_total{feature="public_access"} 1"#));
+}
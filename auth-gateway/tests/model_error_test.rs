@@ -0,0 +1,91 @@
+mod common;
+
+use auth_gateway::auth::{
+    build_stale_grant_cache, create_router, load_access_rules, AppState, CorsConfig,
+    ModelErrorStatus,
+};
+use auth_gateway::telemetry;
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use std::time::Duration;
+use tower::ServiceExt;
+
+const RULES: &str = r#"[{ "path": "/reports", "method": "GET", "feature": "reports" }]"#;
+
+/// OpenFGA rejecting every check the way it does for a relation the
+/// model doesn't define
+async fn spawn_drifted_openfga() -> String {
+    let app = axum::Router::new().fallback(|| async {
+        (
+            StatusCode::BAD_REQUEST,
+            r#"{"code":"validation_error","message":"relation 'feature#viewer' not found"}"#,
+        )
+    });
+    common::spawn_server(app).await
+}
+
+async fn drifted_state() -> AppState {
+    let path = common::write_temp_file("model_error_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    state.fga_client.url = spawn_drifted_openfga().await;
+    common::install_test_key(&state).await;
+    state
+}
+
+async fn get_reports(state: &AppState) -> StatusCode {
+    let req = Request::builder()
+        .uri("/reports")
+        .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+        .body(Body::empty())
+        .unwrap();
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_model_error_is_counted_and_rejected() {
+    let handle = telemetry::install_recorder();
+    let state = drifted_state().await;
+
+    assert_eq!(get_reports(&state).await, StatusCode::FORBIDDEN);
+    assert!(handle
+        .render()
+        .contains(r#"openfga_check_errors_total{kind="model"}"#));
+}
+
+#[tokio::test]
+async fn test_model_error_status_is_configurable() {
+    let mut state = drifted_state().await;
+    state.model_error_status = ModelErrorStatus::BadGateway;
+
+    assert_eq!(get_reports(&state).await, StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn test_model_error_does_not_serve_stale_grant() {
+    let mut state = drifted_state().await;
+    state.model_error_status = ModelErrorStatus::BadGateway;
+    let openfga_url = state.fga_client.url.clone();
+    state.fga_client.url = common::spawn_openfga(true).await;
+    state.stale_grants = Some(build_stale_grant_cache(Duration::from_secs(3600)));
+    assert_eq!(get_reports(&state).await, StatusCode::OK);
+
+    // Drift is not an outage, so the last-known grant isn't a fallback
+    state.fga_client.url = openfga_url;
+    state.cache.invalidate_all();
+    assert_eq!(get_reports(&state).await, StatusCode::BAD_GATEWAY);
+}
+
+#[test]
+fn test_model_error_status_parses() {
+    assert_eq!("403".parse(), Ok(ModelErrorStatus::Forbidden));
+    assert_eq!(" 502 ".parse(), Ok(ModelErrorStatus::BadGateway));
+    assert!("500".parse::<ModelErrorStatus>().is_err());
+}
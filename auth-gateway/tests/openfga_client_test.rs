@@ -0,0 +1,237 @@
+mod common;
+
+use auth_gateway::openfga::{Consistency, OpenFgaClient, OpenFgaError, TupleFilter, TupleKey};
+use axum::{extract::Path, http::StatusCode, response::IntoResponse, routing::post, Json};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Request bodies received by a mock endpoint, in order
+type Bodies = Arc<Mutex<Vec<Value>>>;
+
+/// Serve `handler` at `/stores/:store_id/{endpoint}`, recording bodies.
+/// Requests for any store other than `test-store` get a 404.
+async fn spawn_endpoint(
+    endpoint: &str,
+    handler: impl Fn(&Value) -> (StatusCode, Value) + Clone + Send + Sync + 'static,
+) -> (OpenFgaClient, Bodies) {
+    let bodies: Bodies = Arc::default();
+    let recorded = bodies.clone();
+    let app = axum::Router::new().route(
+        &format!("/stores/:store_id/{}", endpoint),
+        post(
+            move |Path(store_id): Path<String>, Json(body): Json<Value>| async move {
+                if store_id != "test-store" {
+                    return StatusCode::NOT_FOUND.into_response();
+                }
+                let (status, response) = handler(&body);
+                recorded.lock().unwrap().push(body);
+                (status, Json(response)).into_response()
+            },
+        ),
+    );
+    let url = common::spawn_server(app).await;
+    (OpenFgaClient::new(url, "test-store".into()), bodies)
+}
+
+#[tokio::test]
+async fn test_check_sends_tuple_and_returns_decision() {
+    let (client, bodies) = spawn_endpoint("check", |body| {
+        let allowed = body["tuple_key"]["object"] == "feature:reports";
+        (StatusCode::OK, json!({ "allowed": allowed }))
+    })
+    .await;
+    let http = reqwest::Client::new();
+
+    let reports = TupleKey::new("user:1", "viewer", "feature:reports");
+    let billing = TupleKey::new("user:1", "viewer", "feature:billing");
+    assert!(client.check(&http, &reports, None, None).await.unwrap());
+    assert!(!client.check(&http, &billing, None, None).await.unwrap());
+
+    assert_eq!(
+        bodies.lock().unwrap()[0],
+        json!({ "tuple_key": { "user": "user:1", "relation": "viewer", "object": "feature:reports" } })
+    );
+}
+
+#[tokio::test]
+async fn test_check_surfaces_client_errors() {
+    let (client, _bodies) = spawn_endpoint("check", |_| {
+        (
+            StatusCode::BAD_REQUEST,
+            json!({ "message": "type not found" }),
+        )
+    })
+    .await;
+
+    let tuple = TupleKey::new("user:1", "viewer", "widget:1");
+    let err = client
+        .check(&reqwest::Client::new(), &tuple, None, None)
+        .await
+        .unwrap_err();
+
+    assert!(
+        matches!(err, OpenFgaError::Status { status, .. } if status == StatusCode::BAD_REQUEST)
+    );
+    assert!(!err.is_transient());
+    assert!(err.is_model_error());
+    assert_eq!(err.kind(), "model");
+}
+
+#[tokio::test]
+async fn test_read_follows_continuation_tokens() {
+    // Two pages: user:1 then user:2
+    let (client, bodies) = spawn_endpoint("read", |body| {
+        let (user, token) = match body["continuation_token"].as_str() {
+            None => ("user:1", "page-2"),
+            Some(_) => ("user:2", ""),
+        };
+        let tuple =
+            json!({ "key": { "user": user, "relation": "viewer", "object": "feature:reports" } });
+        (
+            StatusCode::OK,
+            json!({ "tuples": [tuple], "continuation_token": token }),
+        )
+    })
+    .await;
+
+    let filter = TupleFilter {
+        object: Some("feature:reports".into()),
+        ..Default::default()
+    };
+    let tuples = client.read(&reqwest::Client::new(), &filter).await.unwrap();
+
+    let users: Vec<&str> = tuples.iter().map(|t| t.key.user.as_str()).collect();
+    assert_eq!(users, ["user:1", "user:2"]);
+
+    // Unset filter fields aren't sent
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(
+        bodies[0]["tuple_key"],
+        json!({ "object": "feature:reports" })
+    );
+    assert_eq!(bodies[1]["continuation_token"], "page-2");
+}
+
+#[tokio::test]
+async fn test_write_omits_empty_halves() {
+    let (client, bodies) = spawn_endpoint("write", |_| (StatusCode::OK, json!({}))).await;
+    let http = reqwest::Client::new();
+    let tuple = TupleKey::new("user:1", "member", "role:admin");
+
+    client
+        .write(&http, std::slice::from_ref(&tuple), &[])
+        .await
+        .unwrap();
+    client.write(&http, &[], &[tuple]).await.unwrap();
+    // Nothing to write: no request at all
+    client.write(&http, &[], &[]).await.unwrap();
+
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies.len(), 2);
+    assert!(bodies[0].get("deletes").is_none());
+    assert_eq!(bodies[0]["writes"]["tuple_keys"][0]["object"], "role:admin");
+    assert!(bodies[1].get("writes").is_none());
+    assert_eq!(bodies[1]["deletes"]["tuple_keys"][0]["user"], "user:1");
+}
+
+#[tokio::test]
+async fn test_list_objects_returns_object_ids() {
+    let (client, bodies) = spawn_endpoint("list-objects", |_| {
+        (
+            StatusCode::OK,
+            json!({ "objects": ["feature:reports", "feature:billing"] }),
+        )
+    })
+    .await;
+
+    let objects = client
+        .list_objects(&reqwest::Client::new(), "user:1", "viewer", "feature")
+        .await
+        .unwrap();
+
+    assert_eq!(objects, ["feature:reports", "feature:billing"]);
+    assert_eq!(
+        bodies.lock().unwrap()[0],
+        json!({ "type": "feature", "relation": "viewer", "user": "user:1" })
+    );
+}
+
+#[tokio::test]
+async fn test_model_id_pins_every_request() {
+    let http = reqwest::Client::new();
+    let tuple = TupleKey::new("user:1", "viewer", "feature:reports");
+
+    let (client, checks) =
+        spawn_endpoint("check", |_| (StatusCode::OK, json!({ "allowed": true }))).await;
+    let client = client.with_model_id(Some("01HMODEL".into()));
+    client.check(&http, &tuple, None, None).await.unwrap();
+
+    let (client, reads) =
+        spawn_endpoint("read", |_| (StatusCode::OK, json!({ "tuples": [] }))).await;
+    let client = client.with_model_id(Some("01HMODEL".into()));
+    client.read(&http, &TupleFilter::default()).await.unwrap();
+
+    let (client, writes) = spawn_endpoint("write", |_| (StatusCode::OK, json!({}))).await;
+    let client = client.with_model_id(Some("01HMODEL".into()));
+    client
+        .write(&http, std::slice::from_ref(&tuple), &[])
+        .await
+        .unwrap();
+
+    for bodies in [checks, reads, writes] {
+        assert_eq!(
+            bodies.lock().unwrap()[0]["authorization_model_id"],
+            "01HMODEL"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_model_id_omitted_when_unset() {
+    let (client, bodies) =
+        spawn_endpoint("check", |_| (StatusCode::OK, json!({ "allowed": true }))).await;
+    let tuple = TupleKey::new("user:1", "viewer", "feature:reports");
+
+    client
+        .check(&reqwest::Client::new(), &tuple, None, None)
+        .await
+        .unwrap();
+
+    assert!(bodies.lock().unwrap()[0]
+        .get("authorization_model_id")
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_consistency_is_sent_when_set() {
+    let (client, bodies) =
+        spawn_endpoint("check", |_| (StatusCode::OK, json!({ "allowed": true }))).await;
+    let client = client.with_consistency(Some(Consistency::MinimizeLatency));
+    let http = reqwest::Client::new();
+    let tuple = TupleKey::new("user:1", "viewer", "feature:reports");
+
+    client.check(&http, &tuple, None, None).await.unwrap();
+    client
+        .check(&http, &tuple, None, Some(Consistency::HigherConsistency))
+        .await
+        .unwrap();
+
+    // The client default applies unless the call asks for something else
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies[0]["consistency"], "MINIMIZE_LATENCY");
+    assert_eq!(bodies[1]["consistency"], "HIGHER_CONSISTENCY");
+}
+
+#[test]
+fn test_consistency_parses() {
+    assert_eq!(
+        "higher_consistency".parse(),
+        Ok(Consistency::HigherConsistency)
+    );
+    assert_eq!(
+        " MINIMIZE_LATENCY ".parse(),
+        Ok(Consistency::MinimizeLatency)
+    );
+    assert!("strong".parse::<Consistency>().is_err());
+}
@@ -0,0 +1,466 @@
+mod common;
+
+use auth_gateway::auth::{
+    check_openfga_permission, check_openfga_permissions_batch, create_router, load_access_rules,
+    prefetch_permissions, AuthzCacheKey, CheckContext, Consistency, CorsConfig, RetryPolicy,
+    TenantSource,
+};
+use auth_gateway::circuit_breaker::{BreakerConfig, BreakerState};
+use axum::{
+    body::Body,
+    http::{header, HeaderName, Method, Request, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Json,
+};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+/// Mock `/batch-check` that allows `feature:reports`, denies everything
+/// else, and reports an error for `feature:broken`
+async fn spawn_batch_openfga() -> String {
+    let app = axum::Router::new().route(
+        "/stores/:store_id/batch-check",
+        post(|Json(body): Json<Value>| async move {
+            let mut result = serde_json::Map::new();
+            for check in body["checks"].as_array().unwrap() {
+                let object = check["tuple_key"]["object"].as_str().unwrap();
+                let outcome = match object {
+                    "feature:reports" => json!({ "allowed": true }),
+                    "feature:broken" => json!({ "error": { "message": "type not found" } }),
+                    _ => json!({ "allowed": false }),
+                };
+                result.insert(check["correlation_id"].as_str().unwrap().into(), outcome);
+            }
+            Json(json!({ "result": result }))
+        }),
+    );
+    common::spawn_server(app).await
+}
+
+#[tokio::test]
+async fn test_batch_check_returns_mixed_results() {
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = spawn_batch_openfga().await;
+
+    let checks = vec![
+        ("reports".to_string(), Some("view".to_string())),
+        ("billing".to_string(), None),
+        ("broken".to_string(), None),
+    ];
+    let results =
+        check_openfga_permissions_batch(&state.http_client, &state.fga_client, "user-1", &checks)
+            .await
+            .unwrap();
+
+    assert_eq!(results.get(&checks[0]), Some(&true));
+    assert_eq!(results.get(&checks[1]), Some(&false));
+    assert_eq!(results.get(&checks[2]), None, "errors are not decisions");
+}
+
+const PREFETCH_RULES: &str = r#"[
+    { "path": "/documents/:id", "method": "GET", "feature": "documents", "object": "document:{id}" },
+    { "path": "/reports", "method": "GET", "feature": "reports" }
+]"#;
+
+#[tokio::test]
+async fn test_prefetch_warms_the_keys_the_middleware_looks_up() {
+    // `/batch-check` grants only acme's document 42; `/check` counts the
+    // checks the middleware had to make itself
+    let checks = Arc::new(AtomicUsize::new(0));
+    let counter = checks.clone();
+    let openfga = axum::Router::new()
+        .route(
+            "/stores/:store_id/batch-check",
+            post(|Json(body): Json<Value>| async move {
+                let mut result = serde_json::Map::new();
+                for check in body["checks"].as_array().unwrap() {
+                    let key = &check["tuple_key"];
+                    let allowed = key["object"] == "document:acme/42" && key["relation"] == "view";
+                    result.insert(
+                        check["correlation_id"].as_str().unwrap().into(),
+                        json!({ "allowed": allowed }),
+                    );
+                }
+                Json(json!({ "result": result }))
+            }),
+        )
+        .route(
+            "/stores/:store_id/check",
+            post(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Json(json!({ "allowed": false }))
+            }),
+        );
+
+    let path = common::write_temp_file("prefetch_rules.json", PREFETCH_RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    state.fga_client.url = common::spawn_server(openfga).await;
+    state.tenant_source = Some(TenantSource::Header(HeaderName::from_static("x-tenant-id")));
+    common::install_test_key(&state).await;
+
+    let requests = [
+        (Method::GET, "/documents/42".to_string()),
+        (Method::GET, "/reports".to_string()),
+    ];
+    prefetch_permissions(&state, "user-1", Some("acme"), &requests)
+        .await
+        .unwrap();
+
+    let get = |uri: &'static str| {
+        let req = Request::builder()
+            .uri(uri)
+            .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+            .header("x-tenant-id", "acme")
+            .body(Body::empty())
+            .unwrap();
+        create_router(state.clone(), CorsConfig::default()).oneshot(req)
+    };
+    assert_eq!(get("/documents/42").await.unwrap().status(), StatusCode::OK);
+    assert_eq!(
+        get("/reports").await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(checks.load(Ordering::SeqCst), 0, "both were cache hits");
+}
+
+/// Mock `/check` that returns 503 for the first `failures` calls, then
+/// `allowed`. Returns the base URL and a call counter.
+async fn spawn_flaky_openfga(failures: usize, allowed: bool) -> (String, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let app = axum::Router::new().route(
+        "/stores/:store_id/check",
+        post(move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                    StatusCode::SERVICE_UNAVAILABLE.into_response()
+                } else {
+                    Json(json!({ "allowed": allowed })).into_response()
+                }
+            }
+        }),
+    );
+    (common::spawn_server(app).await, calls)
+}
+
+fn fast_retry() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+    }
+}
+
+#[tokio::test]
+async fn test_check_retries_transient_failures() {
+    let (url, calls) = spawn_flaky_openfga(2, true).await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = url;
+    state.fga_client.retry = fast_retry();
+
+    let allowed = check_openfga_permission(
+        &state.http_client,
+        &state.fga_client,
+        "user-1",
+        "feature:reports",
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert!(allowed);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_check_does_not_retry_clean_deny() {
+    let (url, calls) = spawn_flaky_openfga(0, false).await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = url;
+    state.fga_client.retry = fast_retry();
+
+    let allowed = check_openfga_permission(
+        &state.http_client,
+        &state.fga_client,
+        "user-1",
+        "feature:reports",
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert!(!allowed);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_check_sends_context_and_contextual_tuples() {
+    let openfga = common::spawn_mock_openfga(true).await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga.url.clone();
+
+    let context = CheckContext {
+        context: Some(json!({ "current_time": "2024-01-01T09:00:00Z" })),
+        contextual_tuples: vec![json!({
+            "user": "user:user-1",
+            "relation": "owner",
+            "object": "document:42"
+        })],
+    };
+
+    check_openfga_permission(
+        &state.http_client,
+        &state.fga_client,
+        "user-1",
+        "feature:reports",
+        Some("view"),
+        Some(&context),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let checks = openfga.checks.lock().unwrap();
+    assert_eq!(checks[0]["context"]["current_time"], "2024-01-01T09:00:00Z");
+    assert_eq!(
+        checks[0]["contextual_tuples"]["tuple_keys"][0]["object"],
+        "document:42"
+    );
+}
+
+#[tokio::test]
+async fn test_check_sends_requested_consistency() {
+    let openfga = common::spawn_mock_openfga(true).await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga.url.clone();
+
+    for consistency in [None, Some(Consistency::HigherConsistency)] {
+        check_openfga_permission(
+            &state.http_client,
+            &state.fga_client,
+            "user-1",
+            "feature:reports",
+            None,
+            None,
+            consistency,
+        )
+        .await
+        .unwrap();
+    }
+
+    let checks = openfga.checks.lock().unwrap();
+    assert!(checks[0].get("consistency").is_none());
+    assert_eq!(checks[1]["consistency"], "HIGHER_CONSISTENCY");
+}
+
+#[test]
+fn test_context_is_part_of_cache_key() {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("x-org-id", "acme".parse().unwrap());
+    let names = vec!["X-Org-ID".to_string()];
+
+    let acme = CheckContext::from_headers(&names, &headers).unwrap();
+    assert_eq!(acme.context, Some(json!({ "x_org_id": "acme" })));
+
+    headers.insert("x-org-id", "globex".parse().unwrap());
+    let globex = CheckContext::from_headers(&names, &headers).unwrap();
+
+    assert_ne!(
+        AuthzCacheKey::new("user-1", "reports", Some(&acme)),
+        AuthzCacheKey::new("user-1", "reports", Some(&globex))
+    );
+    assert!(CheckContext::from_headers(&names, &axum::http::HeaderMap::new()).is_none());
+}
+
+fn breaker_state(threshold: u32, cooldown: Duration) -> auth_gateway::auth::AppState {
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client = state.fga_client.with_breaker(
+        BreakerConfig {
+            failure_threshold: threshold,
+            cooldown,
+        },
+        false,
+    );
+    state.fga_client.retry = RetryPolicy {
+        max_attempts: 1,
+        base_delay: Duration::from_millis(1),
+    };
+    state
+}
+
+async fn check(state: &auth_gateway::auth::AppState) -> bool {
+    check_openfga_permission(
+        &state.http_client,
+        &state.fga_client,
+        "user-1",
+        "feature:reports",
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_repeated_failures_open_breaker() {
+    let (url, calls) = spawn_flaky_openfga(usize::MAX, true).await;
+    let mut state = breaker_state(3, Duration::from_secs(60));
+    state.fga_client.url = url;
+
+    for _ in 0..3 {
+        assert!(!check(&state).await);
+    }
+    assert_eq!(state.fga_client.breaker.state(), BreakerState::Open);
+
+    // Open: checks resolve to the configured decision without calling OpenFGA
+    for _ in 0..5 {
+        assert!(!check(&state).await);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_breaker_half_opens_after_cooldown() {
+    let (url, calls) = spawn_flaky_openfga(2, true).await;
+    let mut state = breaker_state(2, Duration::from_millis(50));
+    state.fga_client.url = url;
+
+    check(&state).await;
+    check(&state).await;
+    assert_eq!(state.fga_client.breaker.state(), BreakerState::Open);
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(state.fga_client.breaker.allow(), "one trial call goes out");
+    assert_eq!(state.fga_client.breaker.state(), BreakerState::HalfOpen);
+    assert!(
+        !state.fga_client.breaker.allow(),
+        "only one trial at a time"
+    );
+
+    // OpenFGA has recovered, so the trial closes the breaker
+    state.fga_client.breaker.record_success();
+    assert_eq!(state.fga_client.breaker.state(), BreakerState::Closed);
+    assert!(check(&state).await);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_failed_trial_reopens_breaker() {
+    let (url, calls) = spawn_flaky_openfga(usize::MAX, true).await;
+    let mut state = breaker_state(1, Duration::from_millis(50));
+    state.fga_client.url = url;
+    state.fga_client.breaker_open_decision = true;
+
+    check(&state).await;
+    assert!(
+        check(&state).await,
+        "open breaker returns the configured decision"
+    );
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(
+        !check(&state).await,
+        "the trial call reaches OpenFGA and fails"
+    );
+    assert_eq!(state.fga_client.breaker.state(), BreakerState::Open);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+fn relation_map() -> std::collections::HashMap<String, String> {
+    [("view", "can_view"), ("edit", "can_edit")]
+        .into_iter()
+        .map(|(action, relation)| (action.to_string(), relation.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_action_is_mapped_to_relation() {
+    let openfga = common::spawn_mock_openfga(true).await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga.url.clone();
+    state.fga_client = state.fga_client.with_relations(relation_map());
+
+    for action in [Some("view"), Some("edit"), None] {
+        check_openfga_permission(
+            &state.http_client,
+            &state.fga_client,
+            "user-1",
+            "feature:reports",
+            action,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    let checks = openfga.checks.lock().unwrap();
+    let relations: Vec<&str> = checks
+        .iter()
+        .map(|check| check["tuple_key"]["relation"].as_str().unwrap())
+        .collect();
+    assert_eq!(relations, vec!["can_view", "can_edit", "viewer"]);
+}
+
+#[tokio::test]
+async fn test_unmapped_action_errors_without_calling_openfga() {
+    let openfga = common::spawn_mock_openfga(true).await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga.url.clone();
+    state.fga_client = state.fga_client.with_relations(relation_map());
+
+    let result = check_openfga_permission(
+        &state.http_client,
+        &state.fga_client,
+        "user-1",
+        "feature:reports",
+        Some("delete"),
+        None,
+        None,
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(openfga.checks.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_actions_pass_through_without_mapping() {
+    let state = common::test_state(matchit::Router::new());
+    assert_eq!(state.fga_client.relation_for(Some("edit")), Ok("edit"));
+    assert_eq!(state.fga_client.relation_for(None), Ok("viewer"));
+}
+
+#[tokio::test]
+async fn test_cancelled_trial_does_not_wedge_breaker() {
+    let hanging = axum::Router::new().fallback(|| async {
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+        StatusCode::OK
+    });
+    let mut state = breaker_state(1, Duration::from_millis(50));
+    state.fga_client.url = common::spawn_server(hanging).await;
+    state.fga_client.breaker.record_failure();
+    assert_eq!(state.fga_client.breaker.state(), BreakerState::Open);
+
+    // The trial call is dropped mid-flight, as on a client disconnect
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    let trial = tokio::time::timeout(Duration::from_millis(50), check(&state)).await;
+    assert!(trial.is_err());
+    assert_eq!(state.fga_client.breaker.state(), BreakerState::HalfOpen);
+
+    // The next call becomes the trial and closes the breaker
+    state.fga_client.url = common::spawn_openfga(true).await;
+    assert!(check(&state).await);
+    assert_eq!(state.fga_client.breaker.state(), BreakerState::Closed);
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, CorsConfig};
+use auth_gateway::otel;
+use axum::{
+    body::Body,
+    http::{HeaderMap, Request, StatusCode},
+};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Upstream answering with the `traceparent` header it received
+async fn spawn_echo_upstream() -> String {
+    let app = axum::Router::new().fallback(|headers: HeaderMap| async move {
+        headers
+            .get("traceparent")
+            .map(|value| value.to_str().unwrap().to_string())
+            .unwrap_or_default()
+    });
+    common::spawn_server(app).await
+}
+
+#[tokio::test]
+async fn test_traceparent_injected_upstream() {
+    let provider = SdkTracerProvider::builder().build();
+    let subscriber = tracing_subscriber::registry().with(otel::layer(&provider));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let path = common::write_temp_file(
+        "otel_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_echo_upstream().await;
+
+    let req = Request::builder()
+        .uri("/public/report")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let traceparent = String::from_utf8(body.to_vec()).unwrap();
+    let parts: Vec<&str> = traceparent.split('-').collect();
+    assert_eq!(parts.len(), 4, "{:?}", traceparent);
+    assert_eq!(parts[0], "00");
+    assert_eq!(parts[1].len(), 32);
+    assert_eq!(parts[2].len(), 16);
+    assert_eq!(parts[3], "01");
+}
@@ -0,0 +1,760 @@
+mod common;
+
+use auth_gateway::auth::{
+    create_router, load_access_rules, CorsConfig, ForwardHeaderMode, HttpClientConfig,
+    DEFAULT_UPSTREAM_RETRY_ATTEMPTS,
+};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    routing::{get, post},
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tower::ServiceExt;
+
+const LARGE_BODY_BYTES: usize = 8 * 1024 * 1024;
+
+/// Upstream serving a large download and reporting the size of uploads
+async fn spawn_bulk_upstream() -> String {
+    let app = axum::Router::new()
+        .route(
+            "/public/download",
+            get(|| async { vec![b'x'; LARGE_BODY_BYTES] }),
+        )
+        .route(
+            "/public/upload",
+            post(|body: Body| async move {
+                let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+                body.len().to_string()
+            }),
+        );
+    common::spawn_server(app).await
+}
+
+async fn app() -> axum::Router {
+    app_with_body_limit(2 * LARGE_BODY_BYTES).await
+}
+
+async fn app_with_body_limit(max_body_bytes: usize) -> axum::Router {
+    let path = common::write_temp_file(
+        "proxy_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_bulk_upstream().await;
+    state.max_body_bytes = max_body_bytes;
+    create_router(state, CorsConfig::default())
+}
+
+#[tokio::test]
+async fn test_large_response_is_streamed() {
+    let req = Request::builder()
+        .uri("/public/download")
+        .body(Body::empty())
+        .unwrap();
+    let response = app().await.oneshot(req).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_LENGTH],
+        LARGE_BODY_BYTES.to_string()
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body.len(), LARGE_BODY_BYTES);
+}
+
+#[tokio::test]
+async fn test_large_upload_is_streamed() {
+    // A chunked stream with no known length, as a streaming client would send
+    let chunks = (0..8).map(|_| Ok::<_, std::io::Error>(vec![b'y'; LARGE_BODY_BYTES / 8]));
+    let req = Request::builder()
+        .method("POST")
+        .uri("/public/upload")
+        .body(Body::from_stream(futures_util::stream::iter(chunks)))
+        .unwrap();
+    let response = app().await.oneshot(req).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body, LARGE_BODY_BYTES.to_string());
+}
+
+/// Upload `total` bytes as a chunked stream with no declared length
+fn chunked_upload(total: usize) -> Request<Body> {
+    let chunks = (0..8).map(move |_| Ok::<_, std::io::Error>(vec![b'z'; total / 8]));
+    Request::builder()
+        .method("POST")
+        .uri("/public/upload")
+        .body(Body::from_stream(futures_util::stream::iter(chunks)))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_body_over_limit_is_rejected() {
+    let limit = 1024 * 1024;
+
+    // Declared length is checked before anything is forwarded
+    let req = Request::builder()
+        .method("POST")
+        .uri("/public/upload")
+        .header(header::CONTENT_LENGTH, limit + 1)
+        .body(Body::from(vec![b'z'; limit + 1]))
+        .unwrap();
+    let response = app_with_body_limit(limit).await.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Undeclared length is cut off mid-stream
+    let response = app_with_body_limit(limit)
+        .await
+        .oneshot(chunked_upload(2 * limit))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_body_under_limit_passes() {
+    let limit = 1024 * 1024;
+    let response = app_with_body_limit(limit)
+        .await
+        .oneshot(chunked_upload(limit / 2))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn proxy_get(upstream_url: String, timeout: Duration) -> StatusCode {
+    let path = common::write_temp_file(
+        "timeout_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = upstream_url;
+    state.upstream_timeout = timeout;
+
+    let req = Request::builder()
+        .uri("/public/slow")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    response.status()
+}
+
+#[tokio::test]
+async fn test_slow_upstream_returns_gateway_timeout() {
+    let slow = axum::Router::new().fallback(|| async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        "too late"
+    });
+    let upstream_url = common::spawn_server(slow).await;
+
+    let status = proxy_get(upstream_url, Duration::from_millis(200)).await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[tokio::test]
+async fn test_unreachable_upstream_returns_bad_gateway() {
+    let status = proxy_get("http://127.0.0.1:1".into(), Duration::from_secs(5)).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn test_hop_by_hop_headers_are_not_forwarded() {
+    // Upstream reports the request headers it saw and sets its own hop-by-hop ones
+    let upstream = axum::Router::new().fallback(|headers: axum::http::HeaderMap| async move {
+        let seen = [
+            "connection",
+            "keep-alive",
+            "proxy-authorization",
+            "x-debug",
+            "x-kept",
+        ]
+        .iter()
+        .filter(|name| headers.contains_key(**name))
+        .copied()
+        .collect::<Vec<_>>()
+        .join(",");
+        (
+            [("keep-alive", "timeout=5"), ("x-upstream-private", "1")],
+            seen,
+        )
+    });
+    let path = common::write_temp_file(
+        "hop_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+
+    let req = Request::builder()
+        .uri("/public/headers")
+        .header(header::CONNECTION, "keep-alive, X-Debug")
+        .header("keep-alive", "timeout=5")
+        .header("proxy-authorization", "Basic c2VjcmV0")
+        .header("x-debug", "1")
+        .header("x-kept", "1")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("keep-alive").is_none());
+    assert_eq!(response.headers()["x-upstream-private"], "1");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body, "x-kept");
+}
+
+#[tokio::test]
+async fn test_allowlist_mode_forwards_only_listed_headers() {
+    let upstream = axum::Router::new().fallback(|headers: axum::http::HeaderMap| async move {
+        ["authorization", "x-custom", "x-user-id"]
+            .iter()
+            .filter(|name| headers.contains_key(**name))
+            .copied()
+            .collect::<Vec<_>>()
+            .join(",")
+    });
+    let path = common::write_temp_file(
+        "allowlist_rules.json",
+        r#"[{ "path": "/reports", "method": "GET", "feature": "reports" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+    state.fga_client.url = common::spawn_mock_openfga(true).await.url;
+    state.forward_header_mode = ForwardHeaderMode::Allowlist;
+    state.forward_headers = vec![header::AUTHORIZATION];
+    state.strip_upstream_auth = false;
+    common::install_test_key(&state).await;
+
+    let req = Request::builder()
+        .uri("/reports")
+        .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+        .header("x-custom", "1")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    // Identity headers set by the gateway pass without being listed
+    assert_eq!(body, "authorization,x-user-id");
+}
+
+/// Upstream answering every request with `name`
+async fn spawn_named_upstream(name: &'static str) -> String {
+    common::spawn_server(axum::Router::new().fallback(move || async move { name })).await
+}
+
+async fn proxied_by(uri: &str) -> String {
+    let path = common::write_temp_file(
+        "service_rules.json",
+        r#"[
+            { "path": "/billing/*path", "method": "*", "feature": "public_access", "target": "billing" },
+            { "path": "/auth/*path", "method": "*", "feature": "public_access", "target": "zitadel" },
+            { "path": "/mystery/*path", "method": "*", "feature": "public_access", "target": "mystery" }
+        ]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_named_upstream("default").await;
+    state.zitadel_api_url = spawn_named_upstream("zitadel").await;
+    state.services = std::sync::Arc::new(
+        [("billing".to_string(), spawn_named_upstream("billing").await)].into(),
+    );
+
+    let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_target_resolves_through_service_map() {
+    assert_eq!(proxied_by("/billing/invoices").await, "billing");
+}
+
+#[tokio::test]
+async fn test_builtin_targets_still_resolve() {
+    assert_eq!(proxied_by("/auth/users").await, "zitadel");
+}
+
+#[tokio::test]
+async fn test_unknown_target_falls_back_to_default_upstream() {
+    assert_eq!(proxied_by("/mystery/thing").await, "default");
+}
+
+/// Upstream echoing the path and query it was asked for
+async fn spawn_path_echo_upstream() -> String {
+    let app = axum::Router::new().fallback(|uri: axum::http::Uri| async move { uri.to_string() });
+    common::spawn_server(app).await
+}
+
+async fn upstream_path_for(rules: &str, uri: &str) -> (StatusCode, String) {
+    let path = common::write_temp_file("rewrite_rules.json", rules);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_path_echo_upstream().await;
+
+    let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_rewrite_strips_prefix() {
+    let rules = r#"[{
+        "path": "/api/v1/*rest", "method": "*", "feature": "public_access",
+        "rewrite": { "strip_prefix": "/api/v1" }
+    }]"#;
+
+    let (status, path) = upstream_path_for(rules, "/api/v1/users?page=2").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(path, "/users?page=2");
+}
+
+#[tokio::test]
+async fn test_rewrite_fills_template_from_params() {
+    let rules = r#"[{
+        "path": "/api/orgs/:org/users/:id", "method": "GET", "feature": "public_access",
+        "rewrite": { "to": "/internal/users/{id}?org={org}" }
+    }]"#;
+
+    let (status, path) = upstream_path_for(rules, "/api/orgs/acme/users/42?full=1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(path, "/internal/users/42?org=acme&full=1");
+}
+
+#[tokio::test]
+async fn test_path_without_rewrite_is_forwarded_verbatim() {
+    let rules = r#"[{ "path": "/api/v1/*rest", "method": "*", "feature": "public_access" }]"#;
+
+    let (_, path) = upstream_path_for(rules, "/api/v1/users").await;
+    assert_eq!(path, "/api/v1/users");
+}
+
+#[tokio::test]
+async fn test_proxy_uses_configured_upstream_client() {
+    let path = common::write_temp_file(
+        "pooled_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_bulk_upstream().await;
+    state.upstream_client = HttpClientConfig {
+        pool_max_idle_per_host: 4,
+        pool_idle_timeout: Duration::from_secs(10),
+        connect_timeout: Duration::from_secs(1),
+        ..HttpClientConfig::default()
+    }
+    .build()
+    .unwrap();
+    // The authz client can't reach a plain-HTTP upstream, so success means
+    // the proxy went through the upstream pool
+    state.http_client = reqwest::Client::builder().https_only(true).build().unwrap();
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/public/upload")
+        .body(Body::from("pooled"))
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// Proxy `/public/echo` with a forged gateway secret and return the secret
+/// upstream received
+async fn upstream_gateway_secret(configured: Option<&'static str>) -> Option<String> {
+    let upstream = axum::Router::new().fallback(|headers: axum::http::HeaderMap| async move {
+        headers
+            .get("x-gateway-secret")
+            .map(|h| h.to_str().unwrap().to_string())
+            .unwrap_or_default()
+    });
+    let path = common::write_temp_file(
+        "secret_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+    state.upstream_secret = configured.map(|s| s.parse().unwrap());
+
+    let req = Request::builder()
+        .uri("/public/echo")
+        .header("x-gateway-secret", "forged-by-client")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    Some(String::from_utf8(body.to_vec()).unwrap()).filter(|s| !s.is_empty())
+}
+
+#[tokio::test]
+async fn test_gateway_secret_is_attached_upstream() {
+    assert_eq!(
+        upstream_gateway_secret(Some("upstream-secret")).await,
+        Some("upstream-secret".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_client_gateway_secret_is_stripped() {
+    assert_eq!(upstream_gateway_secret(None).await, None);
+}
+
+const GZIP_BODY: &[u8] = include_bytes!("fixtures/upstream_body.json.gz");
+
+#[tokio::test]
+async fn test_compressed_response_passes_through_intact() {
+    let upstream = axum::Router::new().route(
+        "/public/report",
+        get(|| async {
+            (
+                [
+                    (header::CONTENT_TYPE, "application/json"),
+                    (header::CONTENT_ENCODING, "gzip"),
+                ],
+                GZIP_BODY,
+            )
+        }),
+    );
+    let path = common::write_temp_file(
+        "gzip_proxy_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+
+    let req = Request::builder()
+        .uri("/public/report")
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    if let Some(len) = response.headers().get(header::CONTENT_LENGTH) {
+        assert_eq!(len.to_str().unwrap(), GZIP_BODY.len().to_string());
+    }
+    // Still the upstream's gzip stream, so the client can decode it
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body.as_ref(), GZIP_BODY);
+    assert_eq!(&body[..2], [0x1f, 0x8b]);
+}
+
+/// Raw upstream that drops its first `drops` connections without answering,
+/// then replies "ok"; returns its URL and the count of connections accepted
+async fn spawn_flaky_upstream(drops: usize) -> (String, Arc<AtomicUsize>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let seen = counter.fetch_add(1, Ordering::SeqCst);
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            if seen >= drops {
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+                    )
+                    .await;
+            }
+        }
+    });
+    (format!("http://{}", addr), connections)
+}
+
+async fn send_to_flaky(method: Method, attempts: u32) -> (StatusCode, usize) {
+    let path = common::write_temp_file(
+        "retry_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    let (url, connections) = spawn_flaky_upstream(1).await;
+    state.upstream_url = url;
+    state.upstream_retry_attempts = attempts;
+
+    let body = if method == Method::POST {
+        Body::from("side effect")
+    } else {
+        Body::empty()
+    };
+    let req = Request::builder()
+        .method(method)
+        .uri("/public/item")
+        .body(body)
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    (response.status(), connections.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn test_get_is_retried_after_connection_reset() {
+    let (status, connections) = send_to_flaky(Method::GET, 2).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(connections, 2);
+}
+
+#[tokio::test]
+async fn test_post_is_never_retried() {
+    let (status, connections) = send_to_flaky(Method::POST, 3).await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(connections, 1);
+}
+
+#[tokio::test]
+async fn test_no_retry_by_default() {
+    let (status, connections) = send_to_flaky(Method::GET, DEFAULT_UPSTREAM_RETRY_ATTEMPTS).await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(connections, 1);
+}
+
+#[tokio::test]
+async fn test_bearer_token_kept_only_for_builtin_targets() {
+    // Upstream reporting whether it received the caller's token
+    let upstream = axum::Router::new().fallback(|headers: axum::http::HeaderMap| async move {
+        headers.contains_key(header::AUTHORIZATION).to_string()
+    });
+    let path = common::write_temp_file(
+        "strip_auth_rules.json",
+        r#"[
+            { "path": "/reports", "method": "GET", "feature": "reports" },
+            { "path": "/auth/*path", "method": "GET", "feature": "reports", "target": "zitadel" }
+        ]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+    state.zitadel_api_url = state.upstream_url.clone();
+    state.fga_client.url = common::spawn_openfga(true).await;
+    common::install_test_key(&state).await;
+
+    for (uri, forwarded) in [("/reports", "false"), ("/auth/userinfo", "true")] {
+        let req = Request::builder()
+            .uri(uri)
+            .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+            .body(Body::empty())
+            .unwrap();
+        let response = create_router(state.clone(), CorsConfig::default())
+            .oneshot(req)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, forwarded, "{}", uri);
+    }
+}
+
+/// Send `req` from `peer` to a public route; returns the upstream's view of
+/// its X-Forwarded-* headers
+async fn forwarded_seen_upstream(
+    state: &auth_gateway::auth::AppState,
+    peer: &str,
+    mut req: Request<Body>,
+) -> serde_json::Value {
+    let addr = std::net::SocketAddr::new(peer.parse().unwrap(), 40000);
+    req.extensions_mut()
+        .insert(axum::extract::ConnectInfo(addr));
+    let response = create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_forwarded_headers_added_for_upstream() {
+    let upstream = axum::Router::new().fallback(|headers: axum::http::HeaderMap| async move {
+        let seen = |name: &str| headers.get(name).map(|v| v.to_str().unwrap().to_string());
+        axum::Json(serde_json::json!({
+            "for": seen("x-forwarded-for"),
+            "proto": seen("x-forwarded-proto"),
+            "host": seen("x-forwarded-host"),
+        }))
+    });
+    let path = common::write_temp_file(
+        "forwarded_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_server(upstream).await;
+    state.trusted_proxies = vec!["10.0.0.1".parse().unwrap()];
+    let request = || {
+        Request::builder()
+            .uri("/public/a")
+            .header(header::HOST, "app.example.com")
+            .header("x-forwarded-for", "203.0.113.7, 198.51.100.2")
+            .header("x-forwarded-proto", "https")
+            .header("x-forwarded-host", "public.example.com")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // A trusted proxy's chain is extended with it, and its view kept
+    let seen = forwarded_seen_upstream(&state, "10.0.0.1", request()).await;
+    assert_eq!(seen["for"], "203.0.113.7, 198.51.100.2, 10.0.0.1");
+    assert_eq!(seen["proto"], "https");
+    assert_eq!(seen["host"], "public.example.com");
+
+    // A direct client's claims are replaced by what the gateway saw
+    let seen = forwarded_seen_upstream(&state, "192.0.2.9", request()).await;
+    assert_eq!(seen["for"], "192.0.2.9");
+    assert_eq!(seen["proto"], "http");
+    assert_eq!(seen["host"], "app.example.com");
+}
+
+/// gRPC-Web unary response: one message frame, then the trailer frame
+/// gRPC-Web carries in the body
+const GRPC_WEB_RESPONSE: &[u8] = b"\x00\x00\x00\x00\x02hi\x80\x00\x00\x00\x0fgrpc-status:0\r\n";
+
+#[tokio::test]
+async fn test_grpc_web_proxied_over_http2_with_trailers() {
+    use http_body_util::{BodyExt, Full};
+
+    // Answers with HTTP trailers too, as a gRPC server over HTTP/2 does
+    let upstream = axum::Router::new().route(
+        "/grpc.Echo/Say",
+        post(|req: Request<Body>| async move {
+            let mut trailers = axum::http::HeaderMap::new();
+            trailers.insert("grpc-status", "0".parse().unwrap());
+            let body = Full::new(axum::body::Bytes::from_static(GRPC_WEB_RESPONSE))
+                .with_trailers(async { Some(Ok(trailers)) });
+            axum::response::Response::builder()
+                .header(header::CONTENT_TYPE, "application/grpc-web+proto")
+                .header("x-upstream-version", format!("{:?}", req.version()))
+                .body(Body::new(body))
+                .unwrap()
+        }),
+    );
+    let path = common::write_temp_file(
+        "grpc_rules.json",
+        r#"[{ "path": "/grpc.Echo/Say", "method": "POST", "feature": "public_access", "target": "echo" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.services = Arc::new([("echo".to_string(), common::spawn_server(upstream).await)].into());
+    state.http2_services = Arc::new(["echo".to_string()].into());
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/grpc.Echo/Say")
+        .header(header::CONTENT_TYPE, "application/grpc-web+proto")
+        .header(header::TE, "trailers")
+        .body(Body::from(&b"\x00\x00\x00\x00\x02hi"[..]))
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-upstream-version"], "HTTP/2.0");
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/grpc-web+proto"
+    );
+    let body = response.into_body().collect().await.unwrap();
+    assert_eq!(body.trailers().unwrap()["grpc-status"], "0");
+    assert_eq!(body.to_bytes(), GRPC_WEB_RESPONSE);
+}
+
+#[tokio::test]
+async fn test_head_keeps_content_length_without_body() {
+    let path = common::write_temp_file(
+        "head_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_bulk_upstream().await;
+
+    let req = Request::builder()
+        .method(Method::HEAD)
+        .uri("/public/download")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_LENGTH],
+        LARGE_BODY_BYTES.to_string()
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(body.is_empty());
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use auth_gateway::auth::{
+    create_router, load_access_rules, CorsConfig, DefaultPolicy, UnmatchedStatus,
+};
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Request, StatusCode},
+};
+use tower::ServiceExt;
+
+/// Upstream that answers with the X-User-ID it received (empty if none)
+async fn spawn_identity_upstream() -> String {
+    let app = axum::Router::new().fallback(|headers: HeaderMap| async move {
+        headers
+            .get("x-user-id")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    });
+    common::spawn_server(app).await
+}
+
+/// Request a public page and return what upstream saw as X-User-ID
+async fn forwarded_user(headers: &[(&str, &str)]) -> String {
+    let (status, user) = send(DefaultPolicy::Deny, "/public/home", headers).await;
+    assert_eq!(status, StatusCode::OK);
+    user
+}
+
+async fn send(policy: DefaultPolicy, uri: &str, headers: &[(&str, &str)]) -> (StatusCode, String) {
+    let path = common::write_temp_file(
+        "public_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_identity_upstream().await;
+    state.default_policy = policy;
+    common::install_test_key(&state).await;
+
+    let mut req = Request::builder().uri(uri);
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_public_route_forwards_user_with_valid_token() {
+    let token = common::bearer_token("user-42");
+    let user = forwarded_user(&[(header::AUTHORIZATION.as_str(), &token)]).await;
+
+    assert_eq!(user, "user-42");
+}
+
+#[tokio::test]
+async fn test_public_route_without_token_has_no_user() {
+    assert_eq!(forwarded_user(&[]).await, "");
+}
+
+#[tokio::test]
+async fn test_public_route_ignores_invalid_token_and_forged_user() {
+    let user = forwarded_user(&[
+        (header::AUTHORIZATION.as_str(), "Bearer not-a-jwt"),
+        ("x-user-id", "admin"),
+    ])
+    .await;
+
+    assert_eq!(user, "");
+}
+
+#[tokio::test]
+async fn test_forged_user_without_token_does_not_reach_upstream() {
+    assert_eq!(forwarded_user(&[("x-user-id", "admin")]).await, "");
+
+    let (status, user) = send(DefaultPolicy::Proxy, "/unlisted", &[("x-user-id", "admin")]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user, "");
+}
+
+#[tokio::test]
+async fn test_unmatched_path_denied_by_default() {
+    let (status, _) = send(DefaultPolicy::Deny, "/unlisted", &[]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_unmatched_path_proxied_under_proxy_policy() {
+    let (status, user) = send(DefaultPolicy::Proxy, "/unlisted", &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user, "");
+
+    let token = common::bearer_token("user-7");
+    let (status, user) = send(
+        DefaultPolicy::Proxy,
+        "/unlisted",
+        &[(header::AUTHORIZATION.as_str(), &token)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user, "user-7");
+}
+
+#[test]
+fn test_default_policy_parses() {
+    assert_eq!("deny".parse::<DefaultPolicy>(), Ok(DefaultPolicy::Deny));
+    assert_eq!(" Proxy ".parse::<DefaultPolicy>(), Ok(DefaultPolicy::Proxy));
+    assert!("allow".parse::<DefaultPolicy>().is_err());
+}
+
+/// Statuses for an unmatched path and for a governed path OpenFGA denies
+async fn unmatched_and_denied(unmatched_status: UnmatchedStatus) -> (StatusCode, StatusCode) {
+    let path = common::write_temp_file(
+        "unmatched_rules.json",
+        r#"[{ "path": "/reports", "method": "GET", "feature": "reports", "action": "view" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.fga_client.url = common::spawn_openfga(false).await;
+    state.unmatched_status = unmatched_status;
+    common::install_test_key(&state).await;
+
+    let app = create_router(state, CorsConfig::default());
+    let mut statuses = Vec::new();
+    for uri in ["/unlisted", "/reports"] {
+        let req = Request::builder()
+            .uri(uri)
+            .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+            .body(Body::empty())
+            .unwrap();
+        statuses.push(app.clone().oneshot(req).await.unwrap().status());
+    }
+    (statuses[0], statuses[1])
+}
+
+#[tokio::test]
+async fn test_unmatched_status_defaults_to_forbidden() {
+    assert_eq!(
+        unmatched_and_denied(UnmatchedStatus::default()).await,
+        (StatusCode::FORBIDDEN, StatusCode::FORBIDDEN)
+    );
+}
+
+#[tokio::test]
+async fn test_unmatched_status_not_found_keeps_authz_failures_forbidden() {
+    assert_eq!(
+        unmatched_and_denied(UnmatchedStatus::NotFound).await,
+        (StatusCode::NOT_FOUND, StatusCode::FORBIDDEN)
+    );
+}
+
+#[test]
+fn test_unmatched_status_parsing() {
+    assert_eq!("403".parse(), Ok(UnmatchedStatus::Forbidden));
+    assert_eq!(" 404 ".parse(), Ok(UnmatchedStatus::NotFound));
+    assert!("401".parse::<UnmatchedStatus>().is_err());
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use auth_gateway::auth::{
+    check_rate_limit, create_router, load_access_rules, CorsConfig, RateLimitFailMode,
+    RateLimitStatus, DEFAULT_RATE_LIMIT, DEFAULT_RATE_WINDOW_SECS,
+};
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::response::IntoResponse;
+use tower::ServiceExt;
+
+const RULES: &str = r#"[
+    { "path": "/search", "method": "GET", "feature": "search", "rate_limit": 2, "rate_window_secs": 10 },
+    { "path": "/config", "method": "GET", "feature": "config", "rate_limit": 5 },
+    { "path": "/reports", "method": "GET", "feature": "reports" }
+]"#;
+
+#[tokio::test]
+async fn test_rate_limits_parsed_per_feature() {
+    let path = common::write_temp_file("rate_rules.json", RULES);
+    let router = load_access_rules(&path).await.unwrap();
+
+    let limit = |path: &str| {
+        router
+            .at(path)
+            .unwrap()
+            .value
+            .get(&Method::GET)
+            .unwrap()
+            .effective_rate_limit()
+    };
+
+    assert_eq!(limit("/search"), (2, 10));
+    assert_eq!(limit("/config"), (5, DEFAULT_RATE_WINDOW_SECS));
+    assert_eq!(
+        limit("/reports"),
+        (DEFAULT_RATE_LIMIT, DEFAULT_RATE_WINDOW_SECS)
+    );
+}
+
+#[tokio::test]
+async fn test_features_are_counted_independently() {
+    let Some(redis_client) = common::test_redis() else {
+        eprintln!("TEST_REDIS_URL not set, skipping");
+        return;
+    };
+
+    let path = common::write_temp_file("rate_rules.json", RULES);
+    let router = load_access_rules(&path).await.unwrap();
+    let mut state = common::test_state(matchit::Router::new());
+    state.redis_client = redis_client;
+
+    let search = router
+        .at("/search")
+        .unwrap()
+        .value
+        .get(&Method::GET)
+        .unwrap();
+    let config = router
+        .at("/config")
+        .unwrap()
+        .value
+        .get(&Method::GET)
+        .unwrap();
+    let user = common::unique_id("user");
+
+    // Exhaust the search budget (2 requests)
+    let first = check_rate_limit(&state, &user, search).await.unwrap();
+    assert!(first.allowed);
+    assert_eq!(first.remaining, 1);
+    assert!(
+        check_rate_limit(&state, &user, search)
+            .await
+            .unwrap()
+            .allowed
+    );
+    let blocked = check_rate_limit(&state, &user, search).await.unwrap();
+    assert!(!blocked.allowed);
+    assert_eq!(blocked.remaining, 0);
+    assert!(blocked.reset_secs <= 10);
+
+    // The config feature still has its own budget of 5
+    for _ in 0..5 {
+        assert!(
+            check_rate_limit(&state, &user, config)
+                .await
+                .unwrap()
+                .allowed
+        );
+    }
+    assert!(
+        !check_rate_limit(&state, &user, config)
+            .await
+            .unwrap()
+            .allowed
+    );
+}
+
+#[test]
+fn test_rejection_carries_rate_limit_headers() {
+    let status = RateLimitStatus {
+        allowed: false,
+        limit: 100,
+        remaining: 0,
+        reset_secs: 42,
+    };
+
+    let response = status.into_response();
+    let headers = response.headers();
+
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(headers["x-ratelimit-limit"], "100");
+    assert_eq!(headers["x-ratelimit-remaining"], "0");
+    assert_eq!(headers["x-ratelimit-reset"], "42");
+    assert_eq!(headers[header::RETRY_AFTER], "42");
+}
+
+#[tokio::test]
+async fn test_sliding_window_blocks_boundary_burst() {
+    let Some(redis_client) = common::test_redis() else {
+        eprintln!("TEST_REDIS_URL not set, skipping");
+        return;
+    };
+
+    let path = common::write_temp_file(
+        "burst_rules.json",
+        r#"[{ "path": "/burst", "method": "GET", "feature": "burst", "rate_limit": 100, "rate_window_secs": 2 }]"#,
+    );
+    let router = load_access_rules(&path).await.unwrap();
+    let route = router
+        .at("/burst")
+        .unwrap()
+        .value
+        .get(&Method::GET)
+        .unwrap();
+    let mut state = common::test_state(matchit::Router::new());
+    state.redis_client = redis_client;
+    let user = common::unique_id("burst-user");
+
+    // Fill the window, then keep sending across where a fixed window would reset
+    let mut allowed = 0;
+    for _ in 0..100 {
+        allowed += check_rate_limit(&state, &user, route)
+            .await
+            .unwrap()
+            .allowed as u32;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
+    for _ in 0..100 {
+        allowed += check_rate_limit(&state, &user, route)
+            .await
+            .unwrap()
+            .allowed as u32;
+    }
+    assert_eq!(allowed, 100, "no more than the limit within one window");
+
+    // Once the first entries slide out of the window, requests are allowed again
+    tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+    assert!(
+        check_rate_limit(&state, &user, route)
+            .await
+            .unwrap()
+            .allowed
+    );
+}
+
+async fn request_with_unreachable_redis(mode: RateLimitFailMode) -> StatusCode {
+    let path = common::write_temp_file(
+        "fail_mode_rules.json",
+        r#"[{ "path": "/reports", "method": "GET", "feature": "reports" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.redis_client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+    state.rate_limit_fail_mode = mode;
+    state.fga_client.url = common::spawn_openfga(true).await;
+    state.upstream_url = common::spawn_upstream().await;
+    common::install_test_key(&state).await;
+
+    let app = create_router(state, CorsConfig::default());
+    let req = Request::builder()
+        .uri("/reports")
+        .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+        .body(Body::empty())
+        .unwrap();
+
+    app.oneshot(req).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_unreachable_redis_fails_closed() {
+    assert_eq!(
+        request_with_unreachable_redis(RateLimitFailMode::Closed).await,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
+
+#[tokio::test]
+async fn test_unreachable_redis_fails_open() {
+    assert_eq!(
+        request_with_unreachable_redis(RateLimitFailMode::Open).await,
+        StatusCode::OK
+    );
+}
+
+#[test]
+fn test_fail_mode_parsing() {
+    assert_eq!("open".parse(), Ok(RateLimitFailMode::Open));
+    assert_eq!(" Closed ".parse(), Ok(RateLimitFailMode::Closed));
+    assert!("sometimes".parse::<RateLimitFailMode>().is_err());
+}
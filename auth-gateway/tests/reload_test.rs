@@ -0,0 +1,109 @@
+mod common;
+
+use auth_gateway::admin::ADMIN_SECRET_HEADER;
+use auth_gateway::auth::{create_router, CorsConfig};
+use auth_gateway::rules_watcher::spawn_rules_watcher;
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use std::time::Duration;
+use tower::ServiceExt;
+
+const RULES_A: &str = r#"[{ "path": "/a/*path", "method": "*", "feature": "public_access" }]"#;
+const RULES_B: &str = r#"[{ "path": "/b/*path", "method": "*", "feature": "public_access" }]"#;
+
+async fn status(app: &axum::Router, uri: &str) -> StatusCode {
+    let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    app.clone().oneshot(req).await.unwrap().status()
+}
+
+async fn reload(app: &axum::Router, secret: &str) -> StatusCode {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/admin/reload-rules")
+        .header(ADMIN_SECRET_HEADER, secret)
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(req).await.unwrap().status()
+}
+
+/// State serving `RULES_A` from a rules file the test can rewrite
+async fn state_with_rules_file(name: &str) -> auth_gateway::auth::AppState {
+    let path = common::write_temp_file(name, RULES_A);
+    let mut state = common::test_state(matchit::Router::new());
+    state.access_rules_path = path;
+    state.upstream_url = common::spawn_upstream().await;
+    auth_gateway::auth::reload_access_rules(&state)
+        .await
+        .unwrap();
+    state
+}
+
+#[tokio::test]
+async fn test_reload_endpoint_swaps_rules_live() {
+    let state = state_with_rules_file("reload_rules.json").await;
+    let app = create_router(state.clone(), CorsConfig::default());
+
+    assert_eq!(status(&app, "/a/page").await, StatusCode::OK);
+    assert_eq!(status(&app, "/b/page").await, StatusCode::FORBIDDEN);
+
+    std::fs::write(&state.access_rules_path, RULES_B).unwrap();
+    assert_eq!(reload(&app, common::ADMIN_SECRET).await, StatusCode::OK);
+
+    assert_eq!(status(&app, "/a/page").await, StatusCode::FORBIDDEN);
+    assert_eq!(status(&app, "/b/page").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_invalid_rules_keep_current_router() {
+    let state = state_with_rules_file("bad_reload_rules.json").await;
+    let app = create_router(state.clone(), CorsConfig::default());
+
+    std::fs::write(&state.access_rules_path, "not json").unwrap();
+    assert_eq!(
+        reload(&app, common::ADMIN_SECRET).await,
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+    assert_eq!(status(&app, "/a/page").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_rules_failing_validation_keep_current_router() {
+    let state = state_with_rules_file("unvalidated_reload_rules.json").await;
+    let app = create_router(state.clone(), CorsConfig::default());
+
+    let rules = r#"[{ "path": "/b/*path", "method": "*", "feature": "b", "target": "nowhere" }]"#;
+    std::fs::write(&state.access_rules_path, rules).unwrap();
+    assert_eq!(
+        reload(&app, common::ADMIN_SECRET).await,
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+    assert_eq!(status(&app, "/a/page").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_reload_requires_admin_secret() {
+    let state = state_with_rules_file("unauth_reload_rules.json").await;
+    let app = create_router(state, CorsConfig::default());
+
+    assert_eq!(reload(&app, "wrong").await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_watcher_reloads_on_file_change() {
+    let state = state_with_rules_file("watched_rules.json").await;
+    let app = create_router(state.clone(), CorsConfig::default());
+    let _watcher = spawn_rules_watcher(state.clone()).unwrap();
+
+    std::fs::write(&state.access_rules_path, RULES_B).unwrap();
+
+    // Poll rather than sleep a fixed time; file events arrive asynchronously
+    for _ in 0..50 {
+        if status(&app, "/b/page").await == StatusCode::OK {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("rules were not reloaded after the file changed");
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, CorsConfig};
+use axum::{
+    body::Body,
+    http::{HeaderMap, Request, StatusCode},
+};
+use tower::ServiceExt;
+
+/// Upstream that answers with the X-Request-ID it received
+async fn spawn_echo_upstream() -> String {
+    let app = axum::Router::new().fallback(|headers: HeaderMap| async move {
+        headers
+            .get("x-request-id")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    });
+    common::spawn_server(app).await
+}
+
+async fn app() -> axum::Router {
+    let path = common::write_temp_file(
+        "request_id_rules.json",
+        r#"[{ "path": "/public/*path", "method": "*", "feature": "public_access" }]"#,
+    );
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_echo_upstream().await;
+    create_router(state, CorsConfig::default())
+}
+
+async fn send(app: axum::Router, request_id: Option<&str>) -> (String, String) {
+    let mut req = Request::builder().uri("/public/index.html");
+    if let Some(id) = request_id {
+        req = req.header("x-request-id", id);
+    }
+    let response = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let echoed = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (echoed, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_supplied_request_id_is_propagated_and_echoed() {
+    let (echoed, upstream_saw) = send(app().await, Some("trace-abc-123")).await;
+
+    assert_eq!(echoed, "trace-abc-123");
+    assert_eq!(upstream_saw, "trace-abc-123");
+}
+
+#[tokio::test]
+async fn test_missing_request_id_is_generated() {
+    let (echoed, upstream_saw) = send(app().await, None).await;
+
+    assert!(uuid::Uuid::parse_str(&echoed).is_ok());
+    assert_eq!(upstream_saw, echoed);
+}
+
+#[tokio::test]
+async fn test_request_id_is_echoed_on_denied_requests() {
+    let req = Request::builder()
+        .uri("/unknown")
+        .header("x-request-id", "denied-1")
+        .body(Body::empty())
+        .unwrap();
+    let response = app().await.oneshot(req).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.headers()["x-request-id"], "denied-1");
+}
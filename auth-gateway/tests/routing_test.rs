@@ -0,0 +1,259 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+const RULES: &str = r#"[
+    { "path": "/documents", "method": "GET", "feature": "doc_reader", "action": "view" },
+    { "path": "/documents", "method": "POST", "feature": "doc_writer", "action": "edit" },
+    { "path": "/public/*path", "method": "*", "feature": "public_access" }
+]"#;
+
+#[tokio::test]
+async fn test_same_path_resolves_by_method() {
+    let path = common::write_temp_file("rules.json", RULES);
+    let router = load_access_rules(&path).await.unwrap();
+
+    let routes = router.at("/documents").unwrap().value;
+    let get = routes.get(&Method::GET).unwrap();
+    let post = routes.get(&Method::POST).unwrap();
+
+    assert_eq!(get.feature, "doc_reader");
+    assert_eq!(get.action.as_deref(), Some("view"));
+    assert_eq!(post.feature, "doc_writer");
+    assert_eq!(post.action.as_deref(), Some("edit"));
+    assert!(routes.get(&Method::DELETE).is_none());
+
+    // Wildcard rules apply to every method
+    let public = router.at("/public/index.html").unwrap().value;
+    assert_eq!(public.get(&Method::PUT).unwrap().feature, "public_access");
+}
+
+#[tokio::test]
+async fn test_duplicate_method_rule_is_rejected() {
+    let path = common::write_temp_file(
+        "dup_rules.json",
+        r#"[
+            { "path": "/documents", "method": "GET", "feature": "a" },
+            { "path": "/documents", "method": "GET", "feature": "b" }
+        ]"#,
+    );
+
+    assert!(load_access_rules(&path).await.is_err());
+}
+
+#[tokio::test]
+async fn test_unmatched_method_returns_405() {
+    let path = common::write_temp_file("rules.json", RULES);
+    let router = load_access_rules(&path).await.unwrap();
+    let state = common::test_state(matchit::Router::new());
+    state.router.store(router);
+
+    let app = create_router(state, CorsConfig::default());
+    let req = Request::builder()
+        .method(Method::DELETE)
+        .uri("/documents")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+const OBJECT_RULES: &str = r#"[
+    { "path": "/documents/:id", "method": "GET", "feature": "documents", "action": "view", "object": "document:{id}" },
+    { "path": "/folders/:folder", "method": "GET", "feature": "documents", "object": "document:{id}" }
+]"#;
+
+async fn get_with_token(state: auth_gateway::auth::AppState, uri: &str) -> StatusCode {
+    let req = Request::builder()
+        .uri(uri)
+        .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+        .body(Body::empty())
+        .unwrap();
+
+    create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_object_template_filled_from_path_params() {
+    let openfga = common::spawn_mock_openfga(true).await;
+    let path = common::write_temp_file("object_rules.json", OBJECT_RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.fga_client.url = openfga.url.clone();
+    state.upstream_url = common::spawn_upstream().await;
+    common::install_test_key(&state).await;
+
+    assert_eq!(get_with_token(state, "/documents/42").await, StatusCode::OK);
+
+    let checks = openfga.checks.lock().unwrap();
+    assert_eq!(checks[0]["tuple_key"]["object"], "document:42");
+    assert_eq!(checks[0]["tuple_key"]["relation"], "view");
+}
+
+#[tokio::test]
+async fn test_object_template_with_missing_or_bad_param_is_denied() {
+    let openfga = common::spawn_mock_openfga(true).await;
+    let path = common::write_temp_file("object_rules.json", OBJECT_RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.fga_client.url = openfga.url.clone();
+    common::install_test_key(&state).await;
+
+    // Template references {id} but the route only captures {folder}
+    assert_eq!(
+        get_with_token(state.clone(), "/folders/reports").await,
+        StatusCode::FORBIDDEN
+    );
+    // A param that would change the object's shape
+    assert_eq!(
+        get_with_token(state, "/documents/folder:1").await,
+        StatusCode::FORBIDDEN
+    );
+    assert!(openfga.checks.lock().unwrap().is_empty());
+}
+
+const PREFIX_RULES: &str = r#"[
+    { "path": "/admin/*rest", "method": "*", "feature": "admin" },
+    { "path": "/admin/users", "method": "GET", "feature": "user_mgmt", "action": "view" },
+    { "path": "/admin/users/:id", "method": "DELETE", "feature": "user_mgmt", "action": "delete" },
+    { "path": "/admin/billing/*rest", "method": "GET", "feature": "billing" }
+]"#;
+
+#[tokio::test]
+async fn test_specific_rule_wins_over_prefix_default() {
+    let path = common::write_temp_file("prefix_rules.json", PREFIX_RULES);
+    let router = load_access_rules(&path).await.unwrap();
+    let feature = |uri: &str, method: Method| {
+        let routes = router.at(uri).unwrap().value;
+        routes.get(&method).unwrap().feature.clone()
+    };
+
+    assert_eq!(feature("/admin/users", Method::GET), "user_mgmt");
+    assert_eq!(feature("/admin/users/42", Method::DELETE), "user_mgmt");
+    assert_eq!(feature("/admin/settings", Method::GET), "admin");
+    // Deeper than any specific rule, or a near miss: back to the prefix
+    assert_eq!(feature("/admin/users/42/roles", Method::GET), "admin");
+    assert_eq!(feature("/admin/usersx", Method::GET), "admin");
+    // Nested prefixes: the innermost applies
+    assert_eq!(feature("/admin/billing/invoices", Method::GET), "billing");
+}
+
+#[tokio::test]
+async fn test_methods_without_specific_rule_fall_back_to_prefix() {
+    let path = common::write_temp_file("prefix_rules.json", PREFIX_RULES);
+    let router = load_access_rules(&path).await.unwrap();
+
+    let users = router.at("/admin/users").unwrap().value;
+    assert_eq!(users.get(&Method::POST).unwrap().feature, "admin");
+    assert_eq!(users.get(&Method::POST).unwrap().action, None);
+
+    // Inherited through /admin/billing/*rest from /admin/*rest
+    let billing = router.at("/admin/billing/invoices").unwrap().value;
+    assert_eq!(billing.get(&Method::POST).unwrap().feature, "admin");
+}
+
+#[tokio::test]
+async fn test_url_longer_than_limit_returns_414() {
+    let path = common::write_temp_file("rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    state.max_url_len = 64;
+    let app = create_router(state, CorsConfig::default());
+
+    // Path and query both count towards the limit
+    let at_limit = format!("/public/a?q={}", "x".repeat(64 - "/public/a?q=".len()));
+    let over_limit = format!("{}x", at_limit);
+    for (uri, expected) in [
+        (at_limit, StatusCode::OK),
+        (over_limit, StatusCode::URI_TOO_LONG),
+        (format!("/{}", "a".repeat(64)), StatusCode::URI_TOO_LONG),
+    ] {
+        let req = Request::builder().uri(&uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), expected, "{}", uri);
+    }
+}
+
+const METHOD_ACTION_RULES: &str = r#"[
+    { "path": "/items", "method": "*", "feature": "items" },
+    { "path": "/items/archive", "method": "DELETE", "feature": "items", "action": "archive" }
+]"#;
+
+/// Send each `(method, uri)` with a valid token; returns the relations
+/// OpenFGA was asked to check, in order
+async fn checked_relations(
+    state: auth_gateway::auth::AppState,
+    requests: &[(Method, &str)],
+) -> Vec<String> {
+    let openfga = common::spawn_mock_openfga(true).await;
+    let path = common::write_temp_file("method_action_rules.json", METHOD_ACTION_RULES);
+    let mut state = state;
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.fga_client.url = openfga.url.clone();
+    state.upstream_url = common::spawn_upstream().await;
+    common::install_test_key(&state).await;
+
+    let app = create_router(state, CorsConfig::default());
+    for (method, uri) in requests {
+        let req = Request::builder()
+            .method(method)
+            .uri(*uri)
+            .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            app.clone().oneshot(req).await.unwrap().status(),
+            StatusCode::OK
+        );
+    }
+
+    let checks = openfga.checks.lock().unwrap();
+    checks
+        .iter()
+        .map(|check| check["tuple_key"]["relation"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_rule_without_action_checks_method_default() {
+    let state = common::test_state(matchit::Router::new());
+    let relations = checked_relations(
+        state,
+        &[
+            (Method::GET, "/items"),
+            (Method::PUT, "/items"),
+            (Method::DELETE, "/items"),
+            // The rule's own action wins over the method's
+            (Method::DELETE, "/items/archive"),
+        ],
+    )
+    .await;
+
+    assert_eq!(relations, ["view", "edit", "delete", "archive"]);
+}
+
+#[tokio::test]
+async fn test_method_default_actions_are_configurable() {
+    let mut state = common::test_state(matchit::Router::new());
+    state.method_actions = Arc::new([(Method::DELETE, "admin".to_string())].into());
+
+    let relations = checked_relations(
+        state,
+        &[(Method::DELETE, "/items"), (Method::GET, "/items")],
+    )
+    .await;
+
+    assert_eq!(relations, ["admin", "viewer"]);
+}
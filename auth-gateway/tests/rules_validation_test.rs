@@ -0,0 +1,145 @@
+mod common;
+
+use auth_gateway::auth::{validate_rules, InvalidRules, RuleValidation};
+use std::collections::HashMap;
+
+fn known() -> RuleValidation {
+    let services = HashMap::from([("billing".to_string(), "http://billing".to_string())]);
+    RuleValidation::new(&HashMap::new(), &services)
+}
+
+/// Problems reported for `rules`, or empty if they're valid
+async fn problems(name: &str, rules: &str) -> Vec<String> {
+    let path = common::write_temp_file(name, rules);
+    match validate_rules(&path, &known()).await {
+        Ok(()) => Vec::new(),
+        Err(e) => e.downcast::<InvalidRules>().unwrap().0,
+    }
+}
+
+#[tokio::test]
+async fn test_valid_rules_pass() {
+    let rules = r#"[
+        { "path": "/reports", "method": "GET", "feature": "reports", "action": "view" },
+        { "path": "/reports", "method": "POST", "feature": "reports", "action": "edit" },
+        { "path": "/billing/*path", "method": "*", "feature": "billing", "target": "billing" },
+        { "path": "/auth/*path", "method": "*", "feature": "public_access", "target": "zitadel" }
+    ]"#;
+    assert!(problems("valid_rules.json", rules).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_unknown_action_rejected() {
+    let rules =
+        r#"[{ "path": "/reports", "method": "GET", "feature": "reports", "action": "veiw" }]"#;
+    let problems = problems("unknown_action_rules.json", rules).await;
+    assert_eq!(
+        problems,
+        ["GET /reports: unknown action 'veiw' (known: delete, edit, view)"]
+    );
+}
+
+#[tokio::test]
+async fn test_actions_come_from_relation_map() {
+    let relations = HashMap::from([("read".to_string(), "can_read".to_string())]);
+    let known = RuleValidation::new(&relations, &HashMap::new());
+    let rules = r#"[
+        { "path": "/a", "method": "GET", "feature": "a", "action": "read" },
+        { "path": "/b", "method": "GET", "feature": "b", "action": "view" }
+    ]"#;
+    let path = common::write_temp_file("relation_map_rules.json", rules);
+
+    let err = validate_rules(&path, &known).await.unwrap_err();
+    assert_eq!(
+        err.downcast::<InvalidRules>().unwrap().0,
+        ["GET /b: unknown action 'view' (known: read)"]
+    );
+}
+
+#[tokio::test]
+async fn test_body_actions_are_validated() {
+    let rules = r#"[
+        { "path": "/rpc", "method": "POST", "feature": "rpc",
+          "action_from_body": { "pointer": "/op", "actions": { "rm": "remove" } } },
+        { "path": "/graphql", "method": "POST", "feature": "gql", "action": "view",
+          "action_from_body": { "pointer": "/op", "actions": { "q": "view" } } }
+    ]"#;
+    let problems = problems("body_action_rules.json", rules).await;
+    assert_eq!(
+        problems,
+        [
+            "POST /rpc: unknown action 'remove' (known: delete, edit, view)",
+            "POST /graphql: has both action and action_from_body",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_target_rejected() {
+    let rules =
+        r#"[{ "path": "/mystery/*path", "method": "*", "feature": "m", "target": "mystery" }]"#;
+    let problems = problems("unknown_target_rules.json", rules).await;
+    assert_eq!(
+        problems,
+        ["* /mystery/*path: unknown target 'mystery' (known: billing, openfga, zitadel)"]
+    );
+}
+
+#[tokio::test]
+async fn test_public_access_with_action_rejected() {
+    let rules =
+        r#"[{ "path": "/health", "method": "GET", "feature": "public_access", "action": "view" }]"#;
+    let problems = problems("public_action_rules.json", rules).await;
+    assert_eq!(
+        problems,
+        ["GET /health: public_access rule has action 'view'"]
+    );
+}
+
+#[tokio::test]
+async fn test_duplicate_rule_rejected() {
+    let rules = r#"[
+        { "path": "/reports", "method": "GET", "feature": "reports" },
+        { "path": "/reports", "method": "GET", "feature": "analytics" }
+    ]"#;
+    let problems = problems("duplicate_rules.json", rules).await;
+    assert_eq!(problems, ["GET /reports: duplicate rule"]);
+}
+
+#[tokio::test]
+async fn test_conflicting_paths_rejected() {
+    let rules = r#"[
+        { "path": "/documents/:id", "method": "GET", "feature": "documents" },
+        { "path": "/documents/:name", "method": "POST", "feature": "documents" }
+    ]"#;
+    let problems = problems("conflicting_rules.json", rules).await;
+    assert_eq!(problems.len(), 1);
+    assert!(
+        problems[0].starts_with("POST /documents/:name: path conflicts with another rule"),
+        "{}",
+        problems[0]
+    );
+}
+
+#[tokio::test]
+async fn test_every_problem_is_reported() {
+    let rules = r#"[
+        { "path": "/a", "method": "GET", "feature": "a", "action": "frobnicate" },
+        { "path": "/b", "method": "GET", "feature": "public_access", "action": "view" },
+        { "path": "/c", "method": "GET", "feature": "c", "target": "nowhere" }
+    ]"#;
+    let path = common::write_temp_file("many_problem_rules.json", rules);
+
+    let message = validate_rules(&path, &known())
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(
+        message.starts_with("3 problem(s) in access rules:"),
+        "{}",
+        message
+    );
+    for rule in ["GET /a", "GET /b", "GET /c"] {
+        assert!(message.contains(rule), "{} missing from {}", rule, message);
+    }
+}
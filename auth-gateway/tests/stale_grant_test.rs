@@ -0,0 +1,97 @@
+mod common;
+
+use auth_gateway::auth::{
+    build_stale_grant_cache, create_router, load_access_rules, AppState, CorsConfig, RetryPolicy,
+};
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tower::ServiceExt;
+
+const RULES: &str = r#"[{ "path": "/reports", "method": "GET", "feature": "reports" }]"#;
+
+async fn get_reports(state: &AppState) -> StatusCode {
+    let req = Request::builder()
+        .uri("/reports")
+        .header(header::AUTHORIZATION, common::bearer_token("user-1"))
+        .body(Body::empty())
+        .unwrap();
+
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn state_with_openfga(
+    allowed: bool,
+    stale_while_error: bool,
+) -> (AppState, common::MockOpenFga) {
+    let path = common::write_temp_file("stale_grant_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    let openfga = common::spawn_mock_openfga(allowed).await;
+    state.fga_client.url = openfga.url.clone();
+    state.fga_client.retry = RetryPolicy {
+        max_attempts: 1,
+        base_delay: Duration::from_millis(1),
+    };
+    if stale_while_error {
+        state.stale_grants = Some(build_stale_grant_cache(Duration::from_secs(3600)));
+    }
+    common::install_test_key(&state).await;
+    (state, openfga)
+}
+
+/// Fresh decisions expire and OpenFGA starts failing every check
+async fn start_outage(state: &mut AppState) {
+    let app = axum::Router::new().fallback(|| async { StatusCode::SERVICE_UNAVAILABLE });
+    state.fga_client.url = common::spawn_server(app).await;
+    state.cache.invalidate_all();
+}
+
+#[tokio::test]
+async fn test_stale_grant_served_during_outage() {
+    let (mut state, _openfga) = state_with_openfga(true, true).await;
+    assert_eq!(get_reports(&state).await, StatusCode::OK);
+
+    start_outage(&mut state).await;
+    assert_eq!(get_reports(&state).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_stale_deny_not_served() {
+    let (mut state, _openfga) = state_with_openfga(false, true).await;
+    assert_eq!(get_reports(&state).await, StatusCode::FORBIDDEN);
+
+    start_outage(&mut state).await;
+    assert_eq!(get_reports(&state).await, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_revoked_grant_not_served_during_outage() {
+    let (mut state, openfga) = state_with_openfga(true, true).await;
+    assert_eq!(get_reports(&state).await, StatusCode::OK);
+
+    // A fresh denial replaces the last-known grant
+    openfga.allowed.store(false, Ordering::SeqCst);
+    state.cache.invalidate_all();
+    assert_eq!(get_reports(&state).await, StatusCode::FORBIDDEN);
+
+    start_outage(&mut state).await;
+    assert_eq!(get_reports(&state).await, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_outage_denies_when_disabled() {
+    let (mut state, _openfga) = state_with_openfga(true, false).await;
+    assert_eq!(get_reports(&state).await, StatusCode::OK);
+
+    start_outage(&mut state).await;
+    assert_eq!(get_reports(&state).await, StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use auth_gateway::auth::{
+    create_router, load_access_rules, tenant_object, AppState, AuthzCacheKey, CorsConfig,
+    TenantSource,
+};
+use axum::{
+    body::Body,
+    http::{header, HeaderName, Request, StatusCode},
+    routing::post,
+    Json,
+};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+const RULES: &str = r#"[{ "path": "/reports", "method": "GET", "feature": "reports" }]"#;
+
+/// State scoped by `source`, with a mock OpenFGA that grants only
+/// `feature:acme/reports`; returns the objects it was asked to check
+async fn tenant_state(source: TenantSource) -> (AppState, Arc<Mutex<Vec<String>>>) {
+    let objects = Arc::new(Mutex::new(Vec::new()));
+    let recorded = objects.clone();
+    let openfga = axum::Router::new().route(
+        "/stores/:store_id/check",
+        post(move |Json(body): Json<Value>| async move {
+            let object = body["tuple_key"]["object"].as_str().unwrap().to_string();
+            let allowed = object == "feature:acme/reports";
+            recorded.lock().unwrap().push(object);
+            Json(json!({ "allowed": allowed }))
+        }),
+    );
+
+    let path = common::write_temp_file("tenant_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = common::spawn_upstream().await;
+    state.fga_client.url = common::spawn_server(openfga).await;
+    state.tenant_source = Some(source);
+    common::install_test_key(&state).await;
+    (state, objects)
+}
+
+async fn get_reports(state: &AppState, token: String, tenant: Option<&str>) -> StatusCode {
+    let mut req = Request::builder()
+        .uri("/reports")
+        .header(header::AUTHORIZATION, token);
+    if let Some(tenant) = tenant {
+        req = req.header("x-tenant-id", tenant);
+    }
+
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+        .status()
+}
+
+fn tenant_key(tenant: &str) -> AuthzCacheKey {
+    AuthzCacheKey::new("user-1", "reports", None)
+        .with_object(&format!("feature:{}/reports", tenant))
+        .with_relation(Some("view"))
+        .with_tenant(Some(tenant))
+}
+
+#[tokio::test]
+async fn test_tenants_get_independent_checks_and_cache_entries() {
+    let source = TenantSource::Header(HeaderName::from_static("x-tenant-id"));
+    let (state, objects) = tenant_state(source).await;
+    let token = || common::bearer_token("user-1");
+
+    assert_eq!(
+        get_reports(&state, token(), Some("acme")).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        get_reports(&state, token(), Some("globex")).await,
+        StatusCode::FORBIDDEN
+    );
+    // Both decisions are now cached separately
+    assert_eq!(
+        get_reports(&state, token(), Some("acme")).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        get_reports(&state, token(), Some("globex")).await,
+        StatusCode::FORBIDDEN
+    );
+
+    assert_eq!(
+        *objects.lock().unwrap(),
+        ["feature:acme/reports", "feature:globex/reports"]
+    );
+    assert!(state.cache.get(&tenant_key("acme")).await.unwrap().allowed);
+    assert!(
+        !state
+            .cache
+            .get(&tenant_key("globex"))
+            .await
+            .unwrap()
+            .allowed
+    );
+}
+
+#[tokio::test]
+async fn test_tenant_from_org_claim() {
+    let (state, objects) = tenant_state(TenantSource::OrgClaim).await;
+    let token = common::sign_rs256(
+        json!({ "sub": "user-1", "org_id": "acme", "exp": common::now() + 300 }),
+    );
+
+    assert_eq!(
+        get_reports(&state, format!("Bearer {}", token), None).await,
+        StatusCode::OK
+    );
+    assert_eq!(*objects.lock().unwrap(), ["feature:acme/reports"]);
+}
+
+#[tokio::test]
+async fn test_missing_or_malformed_tenant_is_forbidden() {
+    let source = TenantSource::Header(HeaderName::from_static("x-tenant-id"));
+    let (state, objects) = tenant_state(source).await;
+
+    for tenant in [None, Some("acme:evil"), Some("acme/reports")] {
+        assert_eq!(
+            get_reports(&state, common::bearer_token("user-1"), tenant).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+    assert!(objects.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_tenant_object_namespaces_the_id() {
+    assert_eq!(
+        tenant_object("feature:billing", "acme"),
+        "feature:acme/billing"
+    );
+    assert_eq!(tenant_object("document:42", "acme"), "document:acme/42");
+}
+
+#[test]
+fn test_tenant_source_parsing() {
+    assert_eq!("org_id".parse(), Ok(TenantSource::OrgClaim));
+    assert_eq!(
+        "header:X-Tenant-Id".parse(),
+        Ok(TenantSource::Header(HeaderName::from_static("x-tenant-id")))
+    );
+    assert!("cookie:tenant".parse::<TenantSource>().is_err());
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use auth_gateway::admin::ADMIN_SECRET_HEADER;
+use auth_gateway::auth::{create_router, validate_jwt, AppState, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+async fn state_with_denylist(redis_client: redis::Client) -> AppState {
+    let mut state = common::test_state(matchit::Router::new());
+    state.redis_client = redis_client;
+    state.token_denylist = true;
+    common::install_test_key(&state).await;
+    state
+}
+
+/// Nothing listens here, so every Redis call fails
+fn unreachable_redis() -> redis::Client {
+    redis::Client::open("redis://127.0.0.1:1/").unwrap()
+}
+
+fn token_with_jti(jti: &str, exp: i64) -> String {
+    common::sign_rs256(json!({ "sub": "user-1", "jti": jti, "exp": exp }))
+}
+
+async fn revoke(state: &AppState, jti: &str, exp: i64) -> StatusCode {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/admin/tokens/revoke")
+        .header(ADMIN_SECRET_HEADER, common::ADMIN_SECRET)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "jti": jti, "exp": exp }).to_string()))
+        .unwrap();
+    create_router(state.clone(), CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_revoked_jti_is_rejected() {
+    let Some(redis_client) = common::test_redis() else {
+        eprintln!("TEST_REDIS_URL not set, skipping");
+        return;
+    };
+    let state = state_with_denylist(redis_client).await;
+    let exp = common::now() + 300;
+    let jti = common::unique_id("jti");
+    let revoked = token_with_jti(&jti, exp);
+    let kept = token_with_jti(&common::unique_id("jti"), exp);
+    assert!(validate_jwt(&state, &revoked).await.is_ok());
+
+    assert_eq!(revoke(&state, &jti, exp).await, StatusCode::OK);
+
+    assert!(validate_jwt(&state, &revoked).await.is_err());
+    assert!(validate_jwt(&state, &kept).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_denylist_fails_closed_when_redis_is_down() {
+    let state = state_with_denylist(unreachable_redis()).await;
+
+    let token = token_with_jti("jti-1", common::now() + 300);
+    assert!(validate_jwt(&state, &token).await.is_err());
+
+    // Tokens without a `jti` can't be revoked, so Redis isn't consulted
+    let token = common::sign_rs256(json!({ "sub": "user-1", "exp": common::now() + 300 }));
+    assert!(validate_jwt(&state, &token).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_denylist_disabled_skips_redis() {
+    let mut state = state_with_denylist(unreachable_redis()).await;
+    state.token_denylist = false;
+
+    let token = token_with_jti("jti-1", common::now() + 300);
+    assert_eq!(
+        validate_jwt(&state, &token).await.unwrap().jti.as_deref(),
+        Some("jti-1")
+    );
+}
+
+#[tokio::test]
+async fn test_revoking_expired_token_is_a_no_op() {
+    let state = state_with_denylist(unreachable_redis()).await;
+
+    assert_eq!(
+        revoke(&state, "jti-1", common::now() - 10).await,
+        StatusCode::OK
+    );
+    // A live token needs Redis, which is down
+    assert_eq!(
+        revoke(&state, "jti-1", common::now() + 300).await,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
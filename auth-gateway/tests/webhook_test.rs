@@ -0,0 +1,783 @@
+mod common;
+
+use auth_gateway::auth::{
+    cache_decision, create_router, AppState, AuthzCacheKey, CorsConfig, RetryPolicy,
+};
+use auth_gateway::openfga::OPENFGA_MAX_TUPLES_PER_WRITE;
+use auth_gateway::webhooks::{
+    UserRegistration, WEBHOOK_EVENT_ID_HEADER, WEBHOOK_SIGNATURE_HEADER, WEBHOOK_TIMESTAMP_HEADER,
+};
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::post,
+    Json,
+};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tower::ServiceExt;
+
+const EVENT: &str = r#"{"userId":"user-1","userName":"alice","userType":"human"}"#;
+
+/// Mock OpenFGA `/write` that counts the writes it receives and, like the
+/// real server, rejects every write after the first as a duplicate tuple
+async fn spawn_write_openfga() -> (String, Arc<AtomicUsize>) {
+    let writes = Arc::new(AtomicUsize::new(0));
+    let counter = writes.clone();
+    let app = axum::Router::new().route(
+        "/stores/:store_id/write",
+        post(move || {
+            let previous = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if previous == 0 {
+                    (StatusCode::OK, "{}")
+                } else {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        r#"{"code":"write_failed_due_to_invalid_input","message":"cannot write a tuple which already exists"}"#,
+                    )
+                }
+            }
+        }),
+    );
+    (common::spawn_server(app).await, writes)
+}
+
+/// Signed user-created request, optionally tagged with an event ID
+fn signed_user_created(event_id: Option<&str>) -> Request<Body> {
+    let mut req = Request::builder()
+        .method("POST")
+        .uri("/webhooks/user-created")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            common::sign_webhook(EVENT.as_bytes()),
+        );
+    if let Some(event_id) = event_id {
+        req = req.header(WEBHOOK_EVENT_ID_HEADER, event_id);
+    }
+    req.body(Body::from(EVENT)).unwrap()
+}
+
+async fn send_user_created(body: &str, signature: Option<String>) -> (StatusCode, usize) {
+    let (openfga_url, writes) = spawn_write_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+
+    let mut req = Request::builder()
+        .method("POST")
+        .uri("/webhooks/user-created")
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(signature) = signature {
+        req = req.header(WEBHOOK_SIGNATURE_HEADER, signature);
+    }
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+
+    (response.status(), writes.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn test_valid_signature_is_processed() {
+    let signature = format!("sha256={}", common::sign_webhook(EVENT.as_bytes()));
+    let (status, writes) = send_user_created(EVENT, Some(signature)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(writes, 1);
+}
+
+#[tokio::test]
+async fn test_repeated_user_created_returns_ok() {
+    let (openfga_url, writes) = spawn_write_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+    let app = create_router(state, CorsConfig::default());
+
+    for _ in 0..2 {
+        let response = app
+            .clone()
+            .oneshot(signed_user_created(None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(writes.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_redelivered_event_id_is_skipped() {
+    let Some(redis_client) = common::test_redis() else {
+        eprintln!("TEST_REDIS_URL not set, skipping");
+        return;
+    };
+
+    let (openfga_url, writes) = spawn_write_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+    state.redis_client = redis_client;
+    let app = create_router(state, CorsConfig::default());
+
+    let event_id = common::unique_id("event");
+    for _ in 0..2 {
+        let req = signed_user_created(Some(&event_id));
+        let response = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(writes.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_tampered_body_is_rejected() {
+    let signature = common::sign_webhook(EVENT.as_bytes());
+    let tampered = EVENT.replace("user-1", "admin");
+    let (status, writes) = send_user_created(&tampered, Some(signature)).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(writes, 0);
+}
+
+#[tokio::test]
+async fn test_missing_signature_is_rejected() {
+    let (status, writes) = send_user_created(EVENT, None).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(writes, 0);
+}
+
+/// Mock OpenFGA where the user currently holds `roles`; records `/write` bodies
+async fn spawn_role_openfga(roles: &[&str]) -> (String, Arc<Mutex<Vec<Value>>>) {
+    let tuples: Vec<Value> = roles
+        .iter()
+        .map(|role| {
+            json!({ "key": { "user": "user:user-1", "relation": "member", "object": format!("role:{}", role) } })
+        })
+        .collect();
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = writes.clone();
+    let app = axum::Router::new()
+        .route(
+            "/stores/:store_id/read",
+            post(move || async move { Json(json!({ "tuples": tuples })) }),
+        )
+        .route(
+            "/stores/:store_id/write",
+            post(move |Json(body): Json<Value>| async move {
+                recorded.lock().unwrap().push(body);
+                "{}"
+            }),
+        );
+    (common::spawn_server(app).await, writes)
+}
+
+/// Send a signed user-updated event and return the OpenFGA writes it caused
+async fn send_user_updated(current_roles: &[&str], event: Value) -> Vec<Value> {
+    let (openfga_url, writes) = spawn_role_openfga(current_roles).await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+
+    let body = event.to_string();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/webhooks/user-updated")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            common::sign_webhook(body.as_bytes()),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let writes = writes.lock().unwrap().clone();
+    writes
+}
+
+#[tokio::test]
+async fn test_user_updated_adds_new_role() {
+    let writes = send_user_updated(
+        &["viewer"],
+        json!({ "userId": "user-1", "userName": "alice", "roles": ["viewer", "admin"] }),
+    )
+    .await;
+
+    assert_eq!(writes.len(), 1);
+    let added = &writes[0]["writes"]["tuple_keys"];
+    assert_eq!(added.as_array().unwrap().len(), 1);
+    assert_eq!(added[0]["object"], "role:admin");
+    assert_eq!(added[0]["user"], "user:user-1");
+    assert!(writes[0].get("deletes").is_none());
+}
+
+#[tokio::test]
+async fn test_user_updated_removes_dropped_role() {
+    let writes = send_user_updated(
+        &["viewer", "editor"],
+        json!({ "userId": "user-1", "userName": "alice", "roles": ["viewer"] }),
+    )
+    .await;
+
+    assert_eq!(writes.len(), 1);
+    let removed = &writes[0]["deletes"]["tuple_keys"];
+    assert_eq!(removed.as_array().unwrap().len(), 1);
+    assert_eq!(removed[0]["object"], "role:editor");
+    assert!(writes[0].get("writes").is_none());
+}
+
+#[tokio::test]
+async fn test_user_updated_without_roles_writes_nothing() {
+    let writes = send_user_updated(
+        &["viewer"],
+        json!({ "userId": "user-1", "userName": "alice" }),
+    )
+    .await;
+
+    assert!(writes.is_empty());
+}
+
+/// Mock OpenFGA `/read` serving `user:user-1`'s tuples over two pages;
+/// records `/write` bodies
+async fn spawn_paged_openfga() -> (String, Arc<Mutex<Vec<Value>>>) {
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = writes.clone();
+    let tuple = |object: &str| json!({ "key": { "user": "user:user-1", "relation": "viewer", "object": object } });
+    let app = axum::Router::new()
+        .route(
+            "/stores/:store_id/read",
+            post(move |Json(body): Json<Value>| async move {
+                assert_eq!(body["page_size"], 100);
+                match body["continuation_token"].as_str() {
+                    None => Json(json!({
+                        "tuples": [tuple("feature:reports"), tuple("feature:billing")],
+                        "continuation_token": "page-2"
+                    })),
+                    Some("page-2") => Json(json!({
+                        "tuples": [tuple("document:42")],
+                        "continuation_token": ""
+                    })),
+                    Some(other) => panic!("unexpected continuation token {}", other),
+                }
+            }),
+        )
+        .route(
+            "/stores/:store_id/write",
+            post(move |Json(body): Json<Value>| async move {
+                recorded.lock().unwrap().push(body);
+                "{}"
+            }),
+        );
+    (common::spawn_server(app).await, writes)
+}
+
+/// Send a signed user-deleted event for user-1
+async fn send_user_deleted(state: AppState) -> StatusCode {
+    let body = json!({ "userId": "user-1" }).to_string();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/webhooks/user-deleted")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            common::sign_webhook(body.as_bytes()),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_user_deleted_cleans_up_every_page() {
+    let (openfga_url, writes) = spawn_paged_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+
+    assert_eq!(send_user_deleted(state).await, StatusCode::OK);
+
+    let writes = writes.lock().unwrap();
+    let deleted: Vec<&str> = writes[0]["deletes"]["tuple_keys"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|key| key["object"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        deleted,
+        vec!["feature:reports", "feature:billing", "document:42"]
+    );
+}
+
+#[tokio::test]
+async fn test_user_deleted_clears_cached_decisions() {
+    let (openfga_url, _writes) = spawn_paged_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+
+    let key = |user: &str| AuthzCacheKey::new(user, "reports", None);
+    cache_decision(&state, key("user-1"), true, None).await;
+    cache_decision(&state, key("user-2"), true, None).await;
+    assert!(state.cache.get(&key("user-1")).await.is_some());
+
+    assert_eq!(send_user_deleted(state.clone()).await, StatusCode::OK);
+
+    assert!(state.cache.get(&key("user-1")).await.is_none());
+    assert!(
+        state.cache.get(&key("user-2")).await.is_some(),
+        "other users keep their entries"
+    );
+}
+
+#[tokio::test]
+async fn test_user_deleted_chunks_large_cleanup_per_write_limit() {
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = writes.clone();
+    let app = axum::Router::new()
+        .route(
+            "/stores/:store_id/read",
+            // 80 tuples for user-1 on each of two pages
+            post(|Json(body): Json<Value>| async move {
+                let (offset, token) = match body["continuation_token"].as_str() {
+                    None => (0, "page-2"),
+                    _ => (80, ""),
+                };
+                let tuples: Vec<Value> = (offset..offset + 80)
+                    .map(|i| {
+                        json!({ "key": { "user": "user:user-1", "relation": "viewer", "object": format!("document:{}", i) } })
+                    })
+                    .collect();
+                Json(json!({ "tuples": tuples, "continuation_token": token }))
+            }),
+        )
+        .route(
+            "/stores/:store_id/write",
+            post(move |Json(body): Json<Value>| async move {
+                recorded.lock().unwrap().push(body);
+                "{}"
+            }),
+        );
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = common::spawn_server(app).await;
+
+    assert_eq!(send_user_deleted(state).await, StatusCode::OK);
+
+    let sizes: Vec<usize> = writes
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|body| body["deletes"]["tuple_keys"].as_array().unwrap().len())
+        .collect();
+    assert_eq!(
+        sizes,
+        [
+            OPENFGA_MAX_TUPLES_PER_WRITE,
+            160 - OPENFGA_MAX_TUPLES_PER_WRITE
+        ]
+    );
+}
+
+/// Mock OpenFGA `/write` answering 503 to the first `failures` writes
+async fn spawn_flaky_write_openfga(failures: usize) -> (String, Arc<AtomicUsize>) {
+    let writes = Arc::new(AtomicUsize::new(0));
+    let counter = writes.clone();
+    let app = axum::Router::new().route(
+        "/stores/:store_id/write",
+        post(move || {
+            let previous = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if previous < failures {
+                    (StatusCode::SERVICE_UNAVAILABLE, "{}")
+                } else {
+                    (StatusCode::OK, "{}")
+                }
+            }
+        }),
+    );
+    (common::spawn_server(app).await, writes)
+}
+
+async fn created_with_flaky_openfga(failures: usize) -> (StatusCode, usize) {
+    let (openfga_url, writes) = spawn_flaky_write_openfga(failures).await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+    state.fga_client.retry = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+    };
+
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(signed_user_created(None))
+        .await
+        .unwrap();
+    (response.status(), writes.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn test_transient_write_failure_is_retried() {
+    let (status, writes) = created_with_flaky_openfga(1).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(writes, 2);
+}
+
+#[tokio::test]
+async fn test_write_gives_up_after_max_attempts() {
+    let (status, writes) = created_with_flaky_openfga(usize::MAX).await;
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(writes, 3);
+}
+
+/// Send a correctly signed webhook, returning the status and JSON body
+async fn send_signed(uri: &str, body: &str) -> (StatusCode, Value) {
+    let (openfga_url, _writes) = spawn_write_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+
+    let req = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            common::sign_webhook(body.as_bytes()),
+        )
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[tokio::test]
+async fn test_missing_field_names_the_field() {
+    // snake_case instead of Zitadel's camelCase
+    let body = r#"{"user_id":"user-1","userName":"alice"}"#;
+
+    let (status, error) = send_signed("/webhooks/user-created", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["status"], "error");
+    assert_eq!(error["field"], "userId");
+    assert!(
+        error["message"].as_str().unwrap().contains("userId"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_field_names_its_path() {
+    let body = r#"{"userId":"user-1","userName":"alice","roles":["admin",7]}"#;
+
+    let (status, error) = send_signed("/webhooks/user-updated", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["field"], "roles[1]");
+}
+
+#[tokio::test]
+async fn test_malformed_json_is_a_clear_400() {
+    let (status, error) = send_signed("/webhooks/user-deleted", "{not json").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["status"], "error");
+    assert_eq!(error["field"], Value::Null);
+}
+
+/// Mock OpenFGA `/write` recording how many tuples each write carried.
+/// Writes touching `user:dup` fail as duplicates, `user:bad` as invalid,
+/// and `user:down` as if OpenFGA were unavailable.
+async fn spawn_bulk_openfga() -> (String, Arc<Mutex<Vec<usize>>>) {
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = writes.clone();
+    let app = axum::Router::new().route(
+        "/stores/:store_id/write",
+        post(move |Json(body): Json<Value>| async move {
+            let tuples = body["writes"]["tuple_keys"].as_array().unwrap().clone();
+            recorded.lock().unwrap().push(tuples.len());
+            let has = |user: &str| tuples.iter().any(|t| t["user"] == user);
+            if has("user:down") {
+                (StatusCode::SERVICE_UNAVAILABLE, "{}")
+            } else if has("user:dup") {
+                (
+                    StatusCode::BAD_REQUEST,
+                    r#"{"code":"write_failed_due_to_invalid_input","message":"cannot write a tuple which already exists"}"#,
+                )
+            } else if has("user:bad") {
+                (
+                    StatusCode::BAD_REQUEST,
+                    r#"{"code":"validation_error","message":"invalid user"}"#,
+                )
+            } else {
+                (StatusCode::OK, "{}")
+            }
+        }),
+    );
+    (common::spawn_server(app).await, writes)
+}
+
+async fn send_bulk(user_ids: &[String]) -> (StatusCode, Value, Vec<usize>) {
+    let (openfga_url, writes) = spawn_bulk_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+
+    let events: Vec<Value> = user_ids
+        .iter()
+        .map(|id| json!({ "userId": id, "userName": id }))
+        .collect();
+    let body = Value::from(events).to_string();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/webhooks/users-bulk")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            common::sign_webhook(body.as_bytes()),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let writes = writes.lock().unwrap().clone();
+    (status, serde_json::from_slice(&bytes).unwrap(), writes)
+}
+
+#[tokio::test]
+async fn test_bulk_sync_is_chunked_per_write_limit() {
+    let users: Vec<String> = (0..250).map(|i| format!("user-{}", i)).collect();
+
+    let (status, summary, writes) = send_bulk(&users).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        writes,
+        [
+            OPENFGA_MAX_TUPLES_PER_WRITE,
+            OPENFGA_MAX_TUPLES_PER_WRITE,
+            250 - 2 * OPENFGA_MAX_TUPLES_PER_WRITE
+        ]
+    );
+    assert_eq!(summary["status"], "success");
+    assert_eq!(summary["registered"], 250);
+    assert_eq!(summary["failed"], 0);
+    assert_eq!(summary["results"][249]["userId"], "user-249");
+    assert_eq!(summary["results"][249]["status"], "registered");
+}
+
+#[tokio::test]
+async fn test_bulk_sync_reports_each_user_of_a_rejected_chunk() {
+    let users: Vec<String> = ["ok", "dup", "bad", "ok"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    let (status, summary, writes) = send_bulk(&users).await;
+
+    // One chunk, then each (deduplicated) user on its own
+    assert_eq!(writes, [3, 1, 1, 1]);
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(summary["status"], "partial");
+    assert_eq!(summary["registered"], 2);
+    assert_eq!(summary["failed"], 1);
+    let outcomes: Vec<_> = summary["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["userId"].clone(), r["status"].clone()))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            (json!("ok"), json!("registered")),
+            (json!("dup"), json!("already_registered")),
+            (json!("bad"), json!("failed")),
+        ]
+    );
+    assert!(summary["results"][2]["error"].is_string());
+}
+
+#[tokio::test]
+async fn test_bulk_sync_fails_chunk_whole_when_openfga_is_down() {
+    let users: Vec<String> = ["ok", "down"].iter().map(|s| s.to_string()).collect();
+
+    let (status, summary, writes) = send_bulk(&users).await;
+
+    // Only the chunk itself (and its retries) reached OpenFGA
+    assert!(writes.iter().all(|&tuples| tuples == 2), "{:?}", writes);
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(summary["registered"], 0);
+    assert_eq!(summary["failed"], 2);
+}
+
+/// Mock OpenFGA `/write` keeping every body it receives
+async fn spawn_recording_openfga() -> (String, Arc<Mutex<Vec<Value>>>) {
+    let bodies = Arc::new(Mutex::new(Vec::new()));
+    let recorded = bodies.clone();
+    let app = axum::Router::new().route(
+        "/stores/:store_id/write",
+        post(move |Json(body): Json<Value>| async move {
+            recorded.lock().unwrap().push(body);
+            (StatusCode::OK, "{}")
+        }),
+    );
+    (common::spawn_server(app).await, bodies)
+}
+
+/// Registration tuple written for a user-created event under `registration`
+async fn registered_tuple(registration: UserRegistration, user_type: &str) -> Value {
+    let (openfga_url, bodies) = spawn_recording_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+    state.user_registration = registration;
+
+    let body =
+        json!({ "userId": "user-1", "userName": "alice", "userType": user_type }).to_string();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/webhooks/user-created")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            common::sign_webhook(body.as_bytes()),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies.len(), 1);
+    bodies[0]["writes"]["tuple_keys"][0].clone()
+}
+
+#[tokio::test]
+async fn test_registration_uses_configured_object_and_relation() {
+    let registration = UserRegistration {
+        object: "organization:default".into(),
+        relation: "assignee".into(),
+        machine_object: None,
+    };
+
+    let tuple = registered_tuple(registration, "machine").await;
+
+    // Without a machine object, machines register like everyone else
+    assert_eq!(
+        tuple,
+        json!({ "user": "user:user-1", "relation": "assignee", "object": "organization:default" })
+    );
+}
+
+#[tokio::test]
+async fn test_machine_users_registered_to_their_own_object() {
+    let registration = UserRegistration {
+        machine_object: Some("organization:service_accounts".into()),
+        ..Default::default()
+    };
+
+    let machine = registered_tuple(registration.clone(), "machine").await;
+    let human = registered_tuple(registration, "human").await;
+
+    assert_eq!(machine["object"], "organization:service_accounts");
+    assert_eq!(machine["relation"], "member");
+    assert_eq!(human["object"], "organization:users");
+}
+
+/// Send EVENT signed over `{timestamp}.{body}`, with timestamps enforced
+async fn send_timestamped(timestamp: i64, signed_timestamp: i64) -> (StatusCode, usize) {
+    let (openfga_url, writes) = spawn_write_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+    state.webhook_timestamp_tolerance = Some(Duration::from_secs(300));
+
+    let signed = format!("{}.{}", signed_timestamp, EVENT);
+    let req = Request::builder()
+        .method("POST")
+        .uri("/webhooks/user-created")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            common::sign_webhook(signed.as_bytes()),
+        )
+        .header(WEBHOOK_TIMESTAMP_HEADER, timestamp.to_string())
+        .body(Body::from(EVENT))
+        .unwrap();
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    (response.status(), writes.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn test_fresh_timestamped_webhook_is_processed() {
+    let now = common::now();
+    assert_eq!(send_timestamped(now, now).await, (StatusCode::OK, 1));
+    // A little clock skew either way is tolerated
+    let skewed = now + 60;
+    assert_eq!(send_timestamped(skewed, skewed).await, (StatusCode::OK, 1));
+}
+
+#[tokio::test]
+async fn test_stale_webhook_is_rejected_despite_valid_signature() {
+    let stale = common::now() - 600;
+    assert_eq!(
+        send_timestamped(stale, stale).await,
+        (StatusCode::UNAUTHORIZED, 0)
+    );
+}
+
+#[tokio::test]
+async fn test_timestamp_is_covered_by_signature() {
+    // A captured request re-sent with its timestamp bumped to now
+    let now = common::now();
+    assert_eq!(
+        send_timestamped(now, now - 600).await,
+        (StatusCode::UNAUTHORIZED, 0)
+    );
+}
+
+#[tokio::test]
+async fn test_missing_timestamp_is_rejected_when_enforced() {
+    let (openfga_url, writes) = spawn_write_openfga().await;
+    let mut state = common::test_state(matchit::Router::new());
+    state.fga_client.url = openfga_url;
+    state.webhook_timestamp_tolerance = Some(Duration::from_secs(300));
+
+    let req = signed_user_created(None);
+    let response = create_router(state, CorsConfig::default())
+        .oneshot(req)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(writes.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use auth_gateway::auth::{create_router, load_access_rules, CorsConfig};
+use axum::extract::ws::{Message, WebSocketUpgrade};
+use axum::http::{header, HeaderMap};
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest};
+
+const RULES: &str = r#"[{ "path": "/ws/echo", "method": "GET", "feature": "chat" }]"#;
+
+/// Upstream WebSocket that echoes text frames, prefixed with the
+/// X-User-ID the gateway forwarded
+async fn spawn_echo_upstream() -> String {
+    let app = axum::Router::new().route(
+        "/ws/echo",
+        axum::routing::get(|ws: WebSocketUpgrade, headers: HeaderMap| async move {
+            let user = headers
+                .get("x-user-id")
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            ws.on_upgrade(move |mut socket| async move {
+                while let Some(Ok(Message::Text(text))) = socket.recv().await {
+                    let reply = format!("{}: {}", user, text);
+                    if socket.send(Message::Text(reply)).await.is_err() {
+                        break;
+                    }
+                }
+            })
+        }),
+    );
+    common::spawn_server(app).await
+}
+
+async fn spawn_gateway() -> String {
+    let path = common::write_temp_file("ws_rules.json", RULES);
+    let mut state = common::test_state(matchit::Router::new());
+    state.router.store(load_access_rules(&path).await.unwrap());
+    state.upstream_url = spawn_echo_upstream().await;
+    state.fga_client.url = common::spawn_openfga(true).await;
+    common::install_test_key(&state).await;
+
+    let gateway = common::spawn_server(create_router(state, CorsConfig::default())).await;
+    gateway.replacen("http://", "ws://", 1)
+}
+
+#[tokio::test]
+async fn test_websocket_is_bridged_to_upstream() {
+    let gateway = spawn_gateway().await;
+
+    let mut req = format!("{}/ws/echo", gateway)
+        .into_client_request()
+        .unwrap();
+    req.headers_mut().insert(
+        header::AUTHORIZATION,
+        common::bearer_token("user-1").parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(req).await.unwrap();
+
+    for text in ["hello", "again"] {
+        socket
+            .send(tungstenite::Message::Text(text.into()))
+            .await
+            .unwrap();
+        let reply = socket.next().await.unwrap().unwrap();
+        assert_eq!(
+            reply,
+            tungstenite::Message::Text(format!("user-1: {}", text))
+        );
+    }
+    socket.close(None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_websocket_requires_auth() {
+    let gateway = spawn_gateway().await;
+
+    let err = tokio_tungstenite::connect_async(format!("{}/ws/echo", gateway))
+        .await
+        .unwrap_err();
+    match err {
+        tungstenite::Error::Http(response) => assert_eq!(response.status(), 401),
+        other => panic!("expected HTTP 401, got {:?}", other),
+    }
+}